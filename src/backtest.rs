@@ -0,0 +1,90 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::time::{Duration, sleep};
+
+use crate::clock::SimulatedClock;
+use crate::config::Config;
+use crate::error::{ConfigError, IoError, Result};
+use crate::paper::{PaperEvent, PaperTrader, Summary};
+use crate::recorder;
+
+// Feeds one recording through the paper trader and prints every simulated trade. The trader's
+// clock is set to each tick's recorded time. With `speed` the ticks are also paced by it in real
+// time (2.0 plays twice as fast), otherwise they run back to back.
+pub async fn replay(config: Arc<Config>, file: &Path, speed: Option<f64>) -> Result<()> {
+    let ticks = recorder::read(file)?;
+    let clock = Arc::new(SimulatedClock::new(ticks.first().map(|t| t.ts_ms).unwrap_or_default()));
+    let mut trader = PaperTrader::new(config, clock.clone());
+    let mut previous_ms = None;
+
+    for tick in &ticks {
+        if let (Some(speed), Some(previous_ms)) = (speed, previous_ms) {
+            let gap_ms = tick.ts_ms.saturating_sub(previous_ms) as f64 / speed;
+            sleep(Duration::from_millis(gap_ms as u64)).await;
+        }
+        previous_ms = Some(tick.ts_ms);
+        clock.set(tick.ts_ms);
+
+        for event in trader.on_tick(tick) {
+            print_event(tick.ts_ms, &event);
+        }
+    }
+    if let Some(event) = trader.finish() {
+        print_event(ticks.last().map(|t| t.ts_ms).unwrap_or_default(), &event);
+    }
+
+    println!("{} ticks: {}", ticks.len(), trader.summary());
+    Ok(())
+}
+
+// Runs every recording (directories are searched for .jsonl files) through its own paper trader
+// and prints a line per file plus the total.
+pub fn backtest(config: Arc<Config>, paths: &[PathBuf]) -> Result<()> {
+    let files = recordings(paths)?;
+    if files.is_empty() {
+        return Err(ConfigError::Invalid { name: "backtest", reason: "no recordings found".to_string() }.into());
+    }
+
+    let mut total = Summary::default();
+    for file in &files {
+        let ticks = recorder::read(file)?;
+        let clock = Arc::new(SimulatedClock::new(ticks.first().map(|t| t.ts_ms).unwrap_or_default()));
+        let mut trader = PaperTrader::new(config.clone(), clock.clone());
+        for tick in &ticks {
+            clock.set(tick.ts_ms);
+            trader.on_tick(tick);
+        }
+        trader.finish();
+
+        let summary = trader.summary();
+        println!("{}: {} ticks, {}", file.display(), ticks.len(), summary);
+        total.add(&summary);
+    }
+    println!("total over {} recordings: {}", files.len(), total);
+    Ok(())
+}
+
+fn recordings(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let entries = std::fs::read_dir(path)
+                .map_err(|e| IoError::Read { path: path.display().to_string(), reason: e.to_string() })?;
+            let mut found: Vec<_> = entries.flatten().map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|ext| ext == "jsonl"))
+                .collect();
+            found.sort();
+            files.extend(found);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+fn print_event(ts_ms: u64, event: &PaperEvent) {
+    let at = chrono::DateTime::from_timestamp_millis(ts_ms as i64)
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S%.3f").to_string())
+        .unwrap_or_default();
+    println!("{at} {event}");
+}
@@ -0,0 +1,215 @@
+use tracing::info;
+
+use crate::config::{Basis, FeedConfig};
+use crate::get_trend::{QUOTES, Quote};
+use crate::metrics;
+
+// How many dollars a USDT is worth, to put the USDT quoted venues' prices in dollars before
+// they're fused with the USD ones. The markets resolve on a USD price, and USDT drifting a few
+// bps off peg would otherwise shift the aggregate by the USDT venues' share of it. Measured from
+// the gap between the two groups of venues, or from a USDT/USD feed when there is one, as an
+// exponentially weighted mean.
+pub struct BasisEstimator {
+    mode: Basis,
+    half_life_ms: f64,
+    raw: Vec<Option<f64>>, // each venue's latest price as quoted
+    from_venues: Option<(u64, f64)>, // updated at, USD per USDT
+    from_feed: Option<(u64, f64)>,
+    logged: bool,
+}
+
+impl BasisEstimator {
+    pub fn new(config: &FeedConfig) -> Self {
+        BasisEstimator {
+            mode: config.basis,
+            half_life_ms: config.basis_half_life_s * 1000.0,
+            raw: vec![None; QUOTES.len()],
+            from_venues: None,
+            from_feed: None,
+            logged: false,
+        }
+    }
+
+    // USD per USDT, 1 until there's an estimate. The feed's once it has ticked, the venues' until then.
+    pub fn usdt_usd(&self) -> f64 {
+        let estimate = match self.mode {
+            Basis::None => None,
+            Basis::Venues => self.from_venues,
+            Basis::Feed => self.from_feed.or(self.from_venues),
+        };
+        estimate.map_or(1.0, |(_, basis)| basis)
+    }
+
+    // a venue's price as quoted, returned in USD. Only spot venues measure the basis, a perp's
+    // price carries its funding too. Only for ticks that passed the outlier filter.
+    pub fn observe(&mut self, venue: usize, time_ms: u64, price: f64, live: &[bool]) -> f64 {
+        if self.mode == Basis::None {
+            return price;
+        }
+        self.raw[venue] = Some(price);
+
+        let mean = |quote: Quote| {
+            let prices: Vec<f64> = (0..QUOTES.len())
+                .filter(|i| QUOTES[*i] == quote && (live[*i] || *i == venue))
+                .filter_map(|i| self.raw[i])
+                .collect();
+            (!prices.is_empty()).then(|| prices.iter().sum::<f64>() / prices.len() as f64)
+        };
+        if let (Some(usd), Some(usdt)) = (mean(Quote::Usd), mean(Quote::Usdt)) {
+            self.from_venues = Some(self.smooth(self.from_venues, time_ms, usd / usdt));
+        }
+        self.publish();
+        self.to_usd(venue, price)
+    }
+
+    // a trade on the USDT/USD feed, its price in USD per USDT
+    pub fn observe_feed(&mut self, time_ms: u64, price: f64) {
+        if price.is_finite() && price > 0.0 {
+            self.from_feed = Some(self.smooth(self.from_feed, time_ms, price));
+            self.publish();
+        }
+    }
+
+    // a venue's price in USD at the current estimate, without recording it
+    pub fn to_usd(&self, venue: usize, price: f64) -> f64 {
+        match QUOTES[venue] {
+            Quote::Usd => price,
+            Quote::Usdt | Quote::UsdtPerp => price * self.usdt_usd(),
+        }
+    }
+
+    fn smooth(&self, last: Option<(u64, f64)>, time_ms: u64, value: f64) -> (u64, f64) {
+        let Some((last_ms, last_value)) = last else {
+            return (time_ms, value);
+        };
+        let dt = time_ms.saturating_sub(last_ms) as f64;
+        let alpha = 1.0 - (-dt * std::f64::consts::LN_2 / self.half_life_ms).exp();
+        (last_ms.max(time_ms), last_value + alpha * (value - last_value))
+    }
+
+    fn publish(&mut self) {
+        let basis = self.usdt_usd();
+        metrics::USDT_USD.set(basis);
+        if !self.logged && basis != 1.0 {
+            self.logged = true;
+            info!(mode = ?self.mode, usdt_usd = format!("{basis:.5}"), "converting usdt prices to usd");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: u64 = 1_700_000_000_000;
+
+    fn estimator(mode: Basis) -> BasisEstimator {
+        BasisEstimator::new(&FeedConfig { basis: mode, basis_half_life_s: 300.0, ..FeedConfig::default() })
+    }
+
+    // every venue ticks once, the USD ones at usd and the USDT ones at usdt, returning the last
+    // USDT venue's price in USD
+    fn tick_all(basis: &mut BasisEstimator, time_ms: u64, usd: f64, usdt: f64) -> f64 {
+        let live = vec![true; QUOTES.len()];
+        let mut converted = 0.0;
+        for (venue, quote) in QUOTES.iter().enumerate() {
+            match quote {
+                Quote::Usd => { basis.observe(venue, time_ms, usd, &live); }
+                Quote::Usdt => converted = basis.observe(venue, time_ms, usdt, &live),
+                Quote::UsdtPerp => { basis.observe(venue, time_ms, usdt, &live); }
+            }
+        }
+        converted
+    }
+
+    #[test]
+    fn estimates_the_basis_from_the_venue_gap() {
+        let mut basis = estimator(Basis::Venues);
+        assert_eq!(basis.usdt_usd(), 1.0);
+
+        // USDT 10bps under the dollar, so BTC quotes 10bps higher in it
+        let converted = tick_all(&mut basis, START, 60_000.0, 60_060.0);
+        assert!((basis.usdt_usd() - 60_000.0 / 60_060.0).abs() < 1e-12);
+        assert!((converted - 60_000.0).abs() < 1e-6);
+
+        // a USD venue's price passes through
+        let live = vec![true; QUOTES.len()];
+        assert_eq!(basis.observe(1, START, 60_000.0, &live), 60_000.0);
+    }
+
+    #[test]
+    fn the_perp_is_converted_but_left_out_of_the_estimate() {
+        let mut basis = estimator(Basis::Venues);
+        let live = vec![true; QUOTES.len()];
+        let perp = QUOTES.iter().position(|q| *q == Quote::UsdtPerp).unwrap();
+
+        // the perp trading 20bps over spot on funding moves nothing
+        basis.observe(perp, START, 60_180.0, &live);
+        assert_eq!(basis.usdt_usd(), 1.0);
+        tick_all(&mut basis, START, 60_000.0, 60_060.0);
+        let estimate = basis.usdt_usd();
+        assert!((estimate - 60_000.0 / 60_060.0).abs() < 1e-12);
+        // a half-life on, which would move the estimate half way if the perp counted
+        let converted = basis.observe(perp, START + 300_000, 60_180.0, &live);
+        assert_eq!(basis.usdt_usd(), estimate);
+        assert!((converted - 60_180.0 * estimate).abs() < 1e-9);
+    }
+
+    #[test]
+    fn converting_alone_leaves_the_estimate() {
+        let mut basis = estimator(Basis::Venues);
+        tick_all(&mut basis, START, 60_000.0, 60_060.0);
+        let estimate = basis.usdt_usd();
+        let usdt = QUOTES.iter().position(|q| *q == Quote::Usdt).unwrap();
+
+        // a tick the outlier filter drops is only converted, it doesn't widen the gap
+        assert!((basis.to_usd(usdt, 66_000.0) - 66_000.0 * estimate).abs() < 1e-9);
+        assert_eq!(basis.usdt_usd(), estimate);
+        let live = vec![true; QUOTES.len()];
+        let usd = QUOTES.iter().position(|q| *q == Quote::Usd).unwrap();
+        basis.observe(usd, START + 300_000, 60_000.0, &live);
+        assert!((basis.usdt_usd() - 60_000.0 / 60_060.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn none_leaves_prices_as_quoted() {
+        let mut basis = estimator(Basis::None);
+        assert_eq!(tick_all(&mut basis, START, 60_000.0, 60_060.0), 60_060.0);
+        basis.observe_feed(START, 0.999);
+        assert_eq!(basis.usdt_usd(), 1.0);
+    }
+
+    #[test]
+    fn the_feed_takes_precedence_once_it_has_ticked() {
+        let mut basis = estimator(Basis::Feed);
+        tick_all(&mut basis, START, 60_000.0, 60_060.0);
+        let from_venues = 60_000.0 / 60_060.0;
+        assert!((basis.usdt_usd() - from_venues).abs() < 1e-12);
+
+        basis.observe_feed(START + 1_000, 1.0002);
+        assert_eq!(basis.usdt_usd(), 1.0002);
+        tick_all(&mut basis, START + 2_000, 60_000.0, 60_060.0);
+        assert_eq!(basis.usdt_usd(), 1.0002);
+
+        // with the venues' mode the feed is ignored
+        let mut basis = estimator(Basis::Venues);
+        tick_all(&mut basis, START, 60_000.0, 60_060.0);
+        basis.observe_feed(START + 1_000, 1.0002);
+        assert!((basis.usdt_usd() - from_venues).abs() < 1e-12);
+    }
+
+    #[test]
+    fn smooths_with_the_half_life() {
+        let mut basis = estimator(Basis::Feed);
+        basis.observe_feed(START, 1.0);
+        // a half-life later, half way to the new value
+        basis.observe_feed(START + 300_000, 1.002);
+        assert!((basis.usdt_usd() - 1.001).abs() < 1e-12);
+        // and another, half the rest
+        basis.observe_feed(START + 600_000, 1.002);
+        assert!((basis.usdt_usd() - 1.0015).abs() < 1e-12);
+        // no time passing moves nothing
+        basis.observe_feed(START + 600_000, 1.1);
+        assert!((basis.usdt_usd() - 1.0015).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,81 @@
+use polymarket_client_sdk::auth::Signer;
+use tokio::time::{Duration, timeout};
+use tokio_tungstenite::connect_async;
+
+use crate::clock::{self, Clock};
+use crate::config::Config;
+use crate::get_trend::{VENUE_URLS, VENUES};
+use crate::util_functions::{get_token_ids, window_slug, window_start};
+use crate::wallet;
+
+const CONNECT_TIMEOUT_S: u64 = 10;
+
+// Goes through everything `run` needs before it can trade (the same preflight it refuses to start
+// on), printing a line per check, and fails if any of them did.
+pub async fn run(config: &Config, clock: &dyn Clock) -> anyhow::Result<()> {
+    let mut failed = 0;
+    let mut report = |name: &str, result: Result<String, String>| match result {
+        Ok(detail) => println!("ok      {name}: {detail}"),
+        Err(reason) => {
+            failed += 1;
+            println!("FAILED  {name}: {reason}");
+        }
+    };
+
+    match wallet::keys() {
+        Ok((signer, funder)) => {
+            report("keys", Ok(format!("signer {}, funder {funder}", signer.address())));
+            match wallet::authenticate(&signer, funder, config.account.wallet).await {
+                Ok((client, _)) => {
+                    report("clob login", Ok("api key derived and authenticated".to_string()));
+                    match wallet::preflight(&client, signer.address(), funder, config).await {
+                        Ok(preflight) => {
+                            let allowance = preflight.usdc_allowance.map(|a| a.to_string()).unwrap_or("unlimited".to_string());
+                            report("account", Ok(format!("{} wallet, {} usdc tradable, one entry takes {}, exchange {} allowance {allowance}, shares approved {}",
+                                config.account.wallet.as_str(), preflight.balance, preflight.needed, preflight.exchange, preflight.shares_approved)));
+                            for problem in preflight.problems {
+                                report("preflight", Err(problem));
+                            }
+                        }
+                        Err(e) => report("account", Err(e.to_string())),
+                    }
+                }
+                Err(e) => report("clob login", Err(e.to_string())),
+            }
+        }
+        Err(e) => report("keys", Err(e.to_string())),
+    }
+
+    for (venue, url) in VENUES.iter().zip(VENUE_URLS) {
+        report(venue, connect(url).await);
+    }
+
+    // live and paper runs correct for the offset, but past max_clock_skew_ms the book looks
+    // stale until the first sync
+    if let Some(server) = config.ntp_server() {
+        let max_ms = config.health.max_clock_skew_ms;
+        report("clock", match clock::ntp_offset_ms(server).await {
+            Ok(offset_ms) if offset_ms.abs() <= max_ms => Ok(format!("{offset_ms}ms off {server}")),
+            Ok(offset_ms) => Err(format!("{offset_ms}ms off {server}, more than health.max_clock_skew_ms = {max_ms}, sync the system clock")),
+            Err(e) => Err(e.to_string()),
+        });
+    }
+
+    let slug = window_slug(window_start(clock.now_s()));
+    report("market", get_token_ids(&slug).await
+        .map(|tokens| format!("{slug}, up {}", tokens[0]))
+        .map_err(|e| e.to_string()));
+
+    if failed > 0 {
+        anyhow::bail!("{failed} check{} failed", if failed == 1 { "" } else { "s" });
+    }
+    Ok(())
+}
+
+async fn connect(url: &str) -> Result<String, String> {
+    match timeout(Duration::from_secs(CONNECT_TIMEOUT_S), connect_async(url)).await {
+        Ok(Ok(_)) => Ok(format!("connected to {url}")),
+        Ok(Err(e)) => Err(format!("{url}: {e}")),
+        Err(_) => Err(format!("{url} timed out after {CONNECT_TIMEOUT_S}s")),
+    }
+}
@@ -0,0 +1,50 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(about = "Trades the Polymarket BTC 15 minute up/down markets on the spot trend")]
+pub struct Cli {
+    /// Config file, defaults to CONFIG_PATH or ./config.toml
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Trade live (the default)
+    Run {
+        /// Append every trend tick to this file for replay and backtest
+        #[arg(long)]
+        record: Option<PathBuf>,
+    },
+    /// Run the strategy on the live feeds with simulated fills, no keys needed
+    Paper {
+        /// Append every trend tick to this file for replay and backtest
+        #[arg(long)]
+        record: Option<PathBuf>,
+    },
+    /// Paper trade recorded ticks and summarise each file
+    Backtest {
+        /// Recordings, or directories of .jsonl recordings
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Play one recording back through the paper trader, printing every trade
+    Replay {
+        file: PathBuf,
+        /// Pace the ticks at this multiple of real time instead of running them back to back
+        #[arg(long)]
+        speed: Option<f64>,
+    },
+    /// Summarise the trades and problems in a LOG_FILE
+    Report {
+        log_file: PathBuf,
+    },
+    /// List the current and next up/down windows and their tokens
+    Markets,
+    /// Check the keys, balance, allowance and connectivity without trading
+    Check,
+}
@@ -0,0 +1,132 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+use tokio::time::{Duration, Instant, sleep, timeout};
+use tracing::{debug, info};
+
+use crate::error::{FeedError, Result};
+use crate::metrics;
+
+// Where the bot reads the time. Window boundaries, the warmup, feed ages and timestamps all go
+// through one, so they can run on a simulated clock in replays.
+pub trait Clock: Send + Sync {
+    fn now_ms(&self) -> u64; // unix time
+    fn instant(&self) -> Instant; // for ages and elapsed times, never goes backwards
+
+    fn now_s(&self) -> u64 {
+        self.now_ms() / 1000
+    }
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+// Only moves when it's set, e.g. to each recorded tick's timestamp. Instants advance with it
+// from the moment it was created.
+pub struct SimulatedClock {
+    base: Instant,
+    start_ms: u64,
+    now_ms: AtomicU64,
+}
+
+impl SimulatedClock {
+    pub fn new(start_ms: u64) -> Self {
+        SimulatedClock { base: Instant::now(), start_ms, now_ms: AtomicU64::new(start_ms) }
+    }
+
+    // earlier times are ignored, a clock doesn't run backwards
+    pub fn set(&self, now_ms: u64) {
+        self.now_ms.fetch_max(now_ms, Ordering::Relaxed);
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now_ms(&self) -> u64 {
+        self.now_ms.load(Ordering::Relaxed)
+    }
+
+    fn instant(&self) -> Instant {
+        self.base + Duration::from_millis(self.now_ms().saturating_sub(self.start_ms))
+    }
+}
+
+// The system clock plus an offset, which `sync` keeps measured against an NTP server so
+// window boundaries line up with polymarket's even when the host's clock drifts.
+#[derive(Default)]
+pub struct OffsetClock {
+    offset_ms: AtomicI64,
+}
+
+impl Clock for OffsetClock {
+    fn now_ms(&self) -> u64 {
+        SystemClock.now_ms().saturating_add_signed(self.offset_ms.load(Ordering::Relaxed))
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+// seconds from the NTP epoch (1900) to the unix one
+const NTP_UNIX_OFFSET_S: u64 = 2_208_988_800;
+
+// Measures the offset every `interval` and applies it. A failed query returns, the supervisor
+// retries it and the last offset stays in use meanwhile.
+pub async fn sync(clock: Arc<OffsetClock>, server: String, interval: Duration) -> Result<()> {
+    let mut first = true;
+    loop {
+        let offset_ms = ntp_offset_ms(&server).await?;
+        let previous = clock.offset_ms.swap(offset_ms, Ordering::Relaxed);
+        metrics::CLOCK_OFFSET_MS.set(offset_ms as f64);
+        if first {
+            info!(%server, offset_ms, "clock synced");
+            first = false;
+        } else {
+            debug!(%server, offset_ms, drift_ms = offset_ms - previous, "clock resynced");
+        }
+        sleep(interval).await;
+    }
+}
+
+// one SNTP exchange, the offset is ((t1 - t0) + (t2 - t3)) / 2 as in RFC 4330
+pub async fn ntp_offset_ms(server: &str) -> Result<i64, FeedError> {
+    let failed = |reason: String| FeedError::Ntp { server: server.to_string(), reason };
+
+    let socket = UdpSocket::bind("0.0.0.0:0").await.map_err(|e| failed(e.to_string()))?;
+    let address = if server.contains(':') { server.to_string() } else { format!("{server}:123") };
+    socket.connect(&address).await.map_err(|e| failed(e.to_string()))?;
+
+    let mut request = [0u8; 48];
+    request[0] = 0x23; // no leap warning, version 4, client mode
+    let sent_ms = SystemClock.now_ms();
+    socket.send(&request).await.map_err(|e| failed(e.to_string()))?;
+
+    let mut response = [0u8; 48];
+    let read = timeout(Duration::from_secs(5), socket.recv(&mut response)).await
+        .map_err(|_| failed("no reply within 5s".to_string()))?
+        .map_err(|e| failed(e.to_string()))?;
+    let received_ms = SystemClock.now_ms();
+    if read < 48 || response[0] & 0x07 != 4 || response[1] == 0 {
+        return Err(failed("not a valid server reply".to_string()));
+    }
+
+    let server_ms = |at: usize| {
+        let seconds = u32::from_be_bytes(response[at..at + 4].try_into().unwrap()) as u64;
+        let fraction = u32::from_be_bytes(response[at + 4..at + 8].try_into().unwrap()) as u64;
+        (seconds.saturating_sub(NTP_UNIX_OFFSET_S) * 1000 + ((fraction * 1000) >> 32)) as i64
+    };
+    let (server_received_ms, server_sent_ms) = (server_ms(32), server_ms(40));
+    Ok(((server_received_ms - sent_ms as i64) + (server_sent_ms - received_ms as i64)) / 2)
+}
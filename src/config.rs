@@ -1,2 +1,595 @@
-pub const VWAP_WINDOW_MS: u64 = 7500; // the VWAP window for each exchange
-pub const LIN_BEST_FIT_MS: u64 = 2500; // the interval for the line of best fit of the meaned VWAPs
+use polymarket_client_sdk::types::Decimal;
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::error::{ConfigError, Result};
+use crate::get_trend::VENUES;
+
+// Every strategy knob, read from config.toml (or CONFIG_PATH) at startup. Anything left out of the
+// file keeps the default below, a missing file means all defaults.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub entry_mode: EntryMode,
+    pub window: WindowConfig,
+    pub feeds: FeedConfig,
+    pub strategy: StrategyConfig,
+    pub health: HealthConfig,
+    pub taker: TakerConfig,
+    pub exit: ExitConfig,
+    pub maker: MakerConfig,
+    pub account: AccountConfig,
+    pub paper: PaperConfig,
+    pub shutdown: ShutdownConfig,
+    pub clock: ClockConfig,
+    pub telemetry: TelemetryConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryMode {
+    Taker, // FOK market orders, pays the taker fee
+    Maker, // GTD limit orders at or inside the best bid, never priced at or through the live ask
+}
+
+// when in the 15 minute window the bot trades, in seconds since the window opened
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+    pub open_after_s: u64, // give the market time to list before looking it up
+    pub close_at_s: u64, // stop trading and leave the window from here on
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeedConfig {
+    pub vwap_window_ms: u64, // the VWAP window for each exchange
+    pub coinbase_vwap_window_ms: u64, // coinbase trades often enough for a shorter one
+    pub trend_window_ms: u64, // the interval for the line of best fit of the meaned VWAPs
+    pub horizons_ms: Vec<u64>, // the same fit over these intervals too, published alongside it
+    pub vol_half_life_s: f64, // of the EWMA of squared log returns
+    pub vol_windows_ms: Vec<u64>, // Parkinson high-low volatility over each of these
+    pub flow_windows_ms: Vec<u64>, // taker buy and sell imbalance over each of these
+    pub min_live_venues: usize, // venues that must have ticked within max_venue_age_ms
+    pub max_venue_age_ms: u64, // a venue older than this no longer counts as live
+    pub warmup_s: u64, // no trend is published for this long after the first aggregate
+    pub reconnect_ms: u64, // pause before reconnecting a dropped venue feed
+    pub depth: bool, // keep binance, coinbase, kraken, okx and bybit order books and price them by microprice
+    pub max_jump_bps: f64, // a tick this far from the venue's last price and from the others is dropped
+    pub max_deviation_bps: f64, // how far a venue may sit from the others' median
+    pub exclude_after_ms: u64, // a venue deviating for this long is left out of the fused price
+    pub exclude_for_ms: u64, // for at least this long, and until it agrees again
+    pub basis: Basis, // how the USDT quoted venues' prices are put in USD
+    pub basis_half_life_s: f64, // of the USDT/USD estimate
+    pub fuser: Fuser, // how the venues' VWAPs become one price
+    pub weighting: Weighting, // where the weighted mean's weights come from
+    pub weights_half_life_s: f64, // of the volume and leadership measurements
+    pub lead_lag_ms: u64, // how often venue prices are sampled to measure leadership
+    pub weights: VenueWeights,
+    pub kalman: KalmanConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Basis {
+    None, // USDT taken at 1 USD
+    Venues, // from the gap between the USD and USDT venues
+    Feed, // from kraken's USDT/USD trades, the venues' gap until it has ticked
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Fuser {
+    WeightedMean, // the live venues' latest VWAPs, weighted by volume
+    Kalman, // a filtered price and velocity, the velocity stands in for the fitted slope
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Weighting {
+    Static, // feeds.weights as configured
+    Volume, // each venue's recent traded notional
+    Leadership, // how well each venue's moves predict the others' next ones
+}
+
+// the Kalman fuser's tuning, the venues' biases and noise are learned from there
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KalmanConfig {
+    pub velocity_noise: f64, // $/s per √s, how fast the trend itself is expected to change
+    pub venue_noise: f64, // $, each venue's noise before any has been learned
+    pub learning_rate: f64, // per observation, of the venues' bias and noise
+}
+
+// share of each venue in the aggregate, by 24h trading volume. Normalised over the live venues.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VenueWeights {
+    pub binance: f64,
+    pub coinbase: f64,
+    pub kraken: f64,
+    pub bitget: f64,
+    pub okx: f64,
+    pub bybit: f64,
+    pub bybit_perp: f64,
+    pub bitstamp: f64,
+    pub gemini: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StrategyConfig {
+    pub threshold: Threshold,
+    pub trend_limit: f64, // $/s, enter up above +limit, down below -limit
+    pub z_limit_open: f64, // the z-score limit at window.open_after_s
+    pub z_limit_close: f64, // and at window.close_at_s, linear in between
+    pub min_r_squared: f64, // only enter on a fit at least this clean, 0 takes any
+    pub min_points: usize, // only enter on a fit through at least this many aggregates
+    pub min_agreeing_horizons: usize, // feeds.horizons_ms that must slope the same way as the entry
+    pub min_price: f64, // only enter while the up token's mid is inside (min_price, max_price)
+    pub max_price: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Threshold {
+    Slope, // the raw slope over trend_limit, whatever the market's doing
+    ZScore, // the slope's z-score against the recent volatility, over a limit that moves with time left
+}
+
+// feed-health thresholds checked before any order is placed
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfig {
+    pub max_book_age_ms: u64, // the polymarket book must have updated within this
+    pub max_clock_skew_ms: i64, // local clock vs the polymarket book timestamp
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TakerConfig {
+    pub size_usdc: Decimal, // FOK buy amount
+    pub hold_s: u64, // how long a taker entry is held before selling
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExitConfig {
+    pub floor_below_entry: Decimal, // never sell more than this far under the entry mid
+    pub deadline_lead_s: u64, // stop trying to sell this long before the window closes
+    pub retry_ms: u64, // pause between sell attempts, doubled while rate limited
+    pub max_empty_attempts: u32, // sells in a row that find nothing to take above the floor before giving up
+}
+
+// maker (resting limit order) entries, picked with entry_mode = "maker"
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MakerConfig {
+    pub size_shares: Decimal, // polymarket's minimum limit order size is 5
+    pub improve_ticks: u32, // ticks to step inside the best bid while staying below the ask
+    pub cancel_lead_s: u64, // cancel resting orders this long before the window closes
+    pub poll_ms: u64, // how often the resting order is polled for fills
+    pub reprice_ms: u64, // minimum time between reprices of a resting order
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccountConfig {
+    pub reconcile_interval_s: u64, // how often positions are checked against clob and chain balances
+    pub wallet: WalletType, // what kind of wallet FUNDER_KEY is, orders are signed for it
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WalletType {
+    Eoa, // trading straight from the PRIVATE_KEY address, FUNDER_KEY is that address
+    Proxy, // polymarket's proxy wallet for email and magic logins
+    GnosisSafe, // the safe polymarket deploys for browser wallet logins
+}
+
+impl WalletType {
+    pub const ALL: [WalletType; 3] = [WalletType::Eoa, WalletType::Proxy, WalletType::GnosisSafe];
+
+    // as written in config.toml
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WalletType::Eoa => "eoa",
+            WalletType::Proxy => "proxy",
+            WalletType::GnosisSafe => "gnosis-safe",
+        }
+    }
+}
+
+// what ctrl-c (or SIGTERM) does with shares still held once entries have stopped
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownConfig {
+    pub open_positions: OnShutdown,
+    pub sell_floor: Decimal, // lowest price a shutdown sell accepts
+    pub timeout_s: u64, // how long selling may take before the rest is left to resolve
+    pub metrics_linger_s: u64, // metrics stay up this long after the wind-down for a last scrape, 0 to exit at once
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnShutdown {
+    Hold, // leave them to pay out at resolution
+    Sell, // sell them on the way out
+}
+
+// simulated fills for paper, replay and backtest
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaperConfig {
+    pub fee_rate_bps: f64, // taker fee is rate * min(p, 1 - p) per share, 300 is ~3% of notional at even odds
+}
+
+// window boundaries come from the local clock, corrected against an NTP server while trading
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClockConfig {
+    pub ntp_server: String, // host or host:port, empty to trust the system clock as it is
+    pub sync_interval_s: u64, // how often the offset is measured again
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelemetryConfig {
+    pub metrics_addr: String, // prometheus metrics are served here, empty to turn them off
+    pub tui: bool, // swap the console log for a live dashboard, logs then only go to LOG_FILE
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            entry_mode: EntryMode::Taker,
+            window: WindowConfig::default(),
+            feeds: FeedConfig::default(),
+            strategy: StrategyConfig::default(),
+            health: HealthConfig::default(),
+            taker: TakerConfig::default(),
+            exit: ExitConfig::default(),
+            maker: MakerConfig::default(),
+            account: AccountConfig::default(),
+            paper: PaperConfig::default(),
+            shutdown: ShutdownConfig::default(),
+            clock: ClockConfig::default(),
+            telemetry: TelemetryConfig::default(),
+        }
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig { open_after_s: 10, close_at_s: 870 }
+    }
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        FeedConfig {
+            vwap_window_ms: 7500,
+            coinbase_vwap_window_ms: 1000,
+            trend_window_ms: 2500,
+            horizons_ms: vec![1000, 5000, 30_000, 120_000],
+            vol_half_life_s: 60.0,
+            vol_windows_ms: vec![60_000, 300_000, 900_000],
+            flow_windows_ms: vec![5000, 30_000, 120_000],
+            min_live_venues: 7,
+            max_venue_age_ms: 5000,
+            warmup_s: 5,
+            reconnect_ms: 1000,
+            depth: false,
+            max_jump_bps: 50.0,
+            max_deviation_bps: 25.0,
+            exclude_after_ms: 5000,
+            exclude_for_ms: 60_000,
+            basis: Basis::Venues,
+            basis_half_life_s: 300.0,
+            fuser: Fuser::WeightedMean,
+            weighting: Weighting::Static,
+            weights_half_life_s: 900.0,
+            lead_lag_ms: 1000,
+            weights: VenueWeights::default(),
+            kalman: KalmanConfig::default(),
+        }
+    }
+}
+
+impl Default for KalmanConfig {
+    fn default() -> Self {
+        KalmanConfig { velocity_noise: 0.5, venue_noise: 5.0, learning_rate: 0.01 }
+    }
+}
+
+impl Default for VenueWeights {
+    fn default() -> Self {
+        VenueWeights {
+            binance: 0.273, coinbase: 0.238, kraken: 0.046, bitget: 0.080, okx: 0.135,
+            bybit: 0.077, bybit_perp: 0.116, bitstamp: 0.023, gemini: 0.012,
+        }
+    }
+}
+
+impl VenueWeights {
+    // in the order of get_trend::VENUES
+    pub fn as_array(&self) -> [f64; VENUES.len()] {
+        [self.binance, self.coinbase, self.kraken, self.bitget, self.okx, self.bybit, self.bybit_perp, self.bitstamp, self.gemini]
+    }
+}
+
+impl Default for StrategyConfig {
+    fn default() -> Self {
+        StrategyConfig {
+            threshold: Threshold::Slope,
+            trend_limit: 4.0,
+            z_limit_open: 3.0,
+            z_limit_close: 3.0,
+            min_r_squared: 0.0,
+            min_points: 2,
+            min_agreeing_horizons: 0,
+            min_price: 0.05,
+            max_price: 0.95,
+        }
+    }
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        HealthConfig { max_book_age_ms: 30_000, max_clock_skew_ms: 2000 }
+    }
+}
+
+impl Default for TakerConfig {
+    fn default() -> Self {
+        TakerConfig { size_usdc: Decimal::ONE, hold_s: 3 }
+    }
+}
+
+impl Default for ExitConfig {
+    fn default() -> Self {
+        ExitConfig { floor_below_entry: Decimal::new(10, 2), deadline_lead_s: 30, retry_ms: 500, max_empty_attempts: 10 }
+    }
+}
+
+impl Default for MakerConfig {
+    fn default() -> Self {
+        MakerConfig { size_shares: Decimal::from(5), improve_ticks: 1, cancel_lead_s: 60, poll_ms: 1000, reprice_ms: 2000 }
+    }
+}
+
+impl Default for AccountConfig {
+    fn default() -> Self {
+        AccountConfig { reconcile_interval_s: 60, wallet: WalletType::GnosisSafe }
+    }
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        ShutdownConfig { open_positions: OnShutdown::Hold, sell_floor: Decimal::new(1, 2), timeout_s: 60, metrics_linger_s: 15 }
+    }
+}
+
+impl Default for PaperConfig {
+    fn default() -> Self {
+        PaperConfig { fee_rate_bps: 300.0 }
+    }
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        ClockConfig { ntp_server: "pool.ntp.org".to_string(), sync_interval_s: 300 }
+    }
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        TelemetryConfig { metrics_addr: "127.0.0.1:9184".to_string(), tui: false }
+    }
+}
+
+impl Config {
+    // Reads `path`, else CONFIG_PATH, else config.toml, then applies the .env overrides that
+    // predate the file: ENTRY_MODE, METRICS_ADDR and TUI.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let explicit = path.is_some() || std::env::var("CONFIG_PATH").is_ok();
+        let path = match path {
+            Some(path) => path.display().to_string(),
+            None => std::env::var("CONFIG_PATH").unwrap_or("config.toml".to_string()),
+        };
+        let mut config = if Path::new(&path).exists() {
+            let text = std::fs::read_to_string(&path)
+                .map_err(|e| ConfigError::File { path: path.clone(), reason: e.to_string() })?;
+            toml::from_str(&text).map_err(|e| ConfigError::File { path: path.clone(), reason: e.to_string() })?
+        } else if explicit {
+            return Err(ConfigError::File { path, reason: "no such file".to_string() }.into());
+        } else {
+            Config::default()
+        };
+
+        if let Ok(mode) = std::env::var("ENTRY_MODE") {
+            config.entry_mode = match mode.trim().to_lowercase().as_str() {
+                "maker" => EntryMode::Maker,
+                "taker" | "" => EntryMode::Taker,
+                other => return Err(ConfigError::Invalid { name: "ENTRY_MODE", reason: format!("{other:?} is not taker or maker") }.into()),
+            };
+        }
+        if let Ok(addr) = std::env::var("METRICS_ADDR") {
+            config.telemetry.metrics_addr = addr.trim().to_string();
+        }
+        if let Ok(tui) = std::env::var("TUI") {
+            config.telemetry.tui = matches!(tui.trim().to_lowercase().as_str(), "1" | "true" | "yes");
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |name: &'static str, reason: String| Err(ConfigError::Invalid { name, reason });
+
+        if self.window.open_after_s >= self.window.close_at_s || self.window.close_at_s > 900 {
+            return invalid("window", format!("need open_after_s < close_at_s <= 900, got {} and {}",
+                self.window.open_after_s, self.window.close_at_s));
+        }
+        if self.feeds.vwap_window_ms == 0 || self.feeds.coinbase_vwap_window_ms == 0 || self.feeds.trend_window_ms == 0 {
+            return invalid("feeds", "vwap and trend windows must be above zero".to_string());
+        }
+        if self.feeds.horizons_ms.contains(&0) {
+            return invalid("feeds.horizons_ms", format!("must all be above zero, got {:?}", self.feeds.horizons_ms));
+        }
+        if !(self.feeds.vol_half_life_s.is_finite() && self.feeds.vol_half_life_s > 0.0) {
+            return invalid("feeds.vol_half_life_s", format!("must be above zero, got {}", self.feeds.vol_half_life_s));
+        }
+        if self.feeds.vol_windows_ms.contains(&0) {
+            return invalid("feeds.vol_windows_ms", format!("must all be above zero, got {:?}", self.feeds.vol_windows_ms));
+        }
+        if self.feeds.flow_windows_ms.contains(&0) {
+            return invalid("feeds.flow_windows_ms", format!("must all be above zero, got {:?}", self.feeds.flow_windows_ms));
+        }
+        if self.feeds.min_live_venues == 0 || self.feeds.min_live_venues > VENUES.len() {
+            return invalid("feeds.min_live_venues", format!("must be between 1 and {}, got {}",
+                VENUES.len(), self.feeds.min_live_venues));
+        }
+        let weights = self.feeds.weights.as_array();
+        if weights.iter().any(|w| !w.is_finite() || *w < 0.0) || weights.iter().sum::<f64>() <= 0.0 {
+            return invalid("feeds.weights", format!("must be non-negative with a positive sum, got {weights:?}"));
+        }
+        if !(self.feeds.weights_half_life_s.is_finite() && self.feeds.weights_half_life_s > 0.0) || self.feeds.lead_lag_ms == 0 {
+            return invalid("feeds", format!("need weights_half_life_s > 0 and lead_lag_ms > 0, got {} and {}",
+                self.feeds.weights_half_life_s, self.feeds.lead_lag_ms));
+        }
+        if !(self.feeds.max_jump_bps > 0.0 && self.feeds.max_deviation_bps > 0.0) {
+            return invalid("feeds", format!("need max_jump_bps > 0 and max_deviation_bps > 0, got {} and {}",
+                self.feeds.max_jump_bps, self.feeds.max_deviation_bps));
+        }
+        if !(self.feeds.basis_half_life_s.is_finite() && self.feeds.basis_half_life_s > 0.0) {
+            return invalid("feeds.basis_half_life_s", format!("must be above zero, got {}", self.feeds.basis_half_life_s));
+        }
+        let kalman = &self.feeds.kalman;
+        let positive = |x: f64| x.is_finite() && x > 0.0;
+        if !(positive(kalman.velocity_noise) && positive(kalman.venue_noise) && kalman.learning_rate > 0.0 && kalman.learning_rate <= 1.0) {
+            return invalid("feeds.kalman", format!("need velocity_noise > 0, venue_noise > 0 and 0 < learning_rate <= 1, got {}, {} and {}",
+                kalman.velocity_noise, kalman.venue_noise, kalman.learning_rate));
+        }
+        if !(self.strategy.trend_limit.is_finite() && self.strategy.trend_limit > 0.0) {
+            return invalid("strategy.trend_limit", format!("must be above zero, got {}", self.strategy.trend_limit));
+        }
+        if [self.strategy.z_limit_open, self.strategy.z_limit_close].iter().any(|z| !(z.is_finite() && *z > 0.0)) {
+            return invalid("strategy", format!("z_limit_open and z_limit_close must be above zero, got {} and {}",
+                self.strategy.z_limit_open, self.strategy.z_limit_close));
+        }
+        if !(0.0..=1.0).contains(&self.strategy.min_r_squared) || self.strategy.min_points < 2 {
+            return invalid("strategy", format!("need 0 <= min_r_squared <= 1 and min_points >= 2, got {} and {}",
+                self.strategy.min_r_squared, self.strategy.min_points));
+        }
+        if self.strategy.min_agreeing_horizons > self.feeds.horizons_ms.len() {
+            return invalid("strategy.min_agreeing_horizons", format!("is {} but feeds.horizons_ms only has {}",
+                self.strategy.min_agreeing_horizons, self.feeds.horizons_ms.len()));
+        }
+        if !(0.0 <= self.strategy.min_price && self.strategy.min_price < self.strategy.max_price && self.strategy.max_price <= 1.0) {
+            return invalid("strategy", format!("need 0 <= min_price < max_price <= 1, got {} and {}",
+                self.strategy.min_price, self.strategy.max_price));
+        }
+        if self.health.max_clock_skew_ms < 0 {
+            return invalid("health.max_clock_skew_ms", "must not be negative".to_string());
+        }
+        if self.taker.size_usdc <= Decimal::ZERO {
+            return invalid("taker.size_usdc", format!("must be above zero, got {}", self.taker.size_usdc));
+        }
+        if self.exit.floor_below_entry < Decimal::ZERO || self.exit.deadline_lead_s >= 900 || self.exit.retry_ms == 0
+            || self.exit.max_empty_attempts == 0 {
+            return invalid("exit", "need floor_below_entry >= 0, deadline_lead_s < 900, retry_ms > 0 and max_empty_attempts > 0".to_string());
+        }
+        if self.maker.size_shares <= Decimal::ZERO || self.maker.cancel_lead_s >= 900 {
+            return invalid("maker", "need size_shares > 0 and cancel_lead_s < 900".to_string());
+        }
+        if !(self.paper.fee_rate_bps.is_finite() && self.paper.fee_rate_bps >= 0.0) {
+            return invalid("paper.fee_rate_bps", format!("must not be negative, got {}", self.paper.fee_rate_bps));
+        }
+        if !(Decimal::ZERO < self.shutdown.sell_floor && self.shutdown.sell_floor < Decimal::ONE) || self.shutdown.timeout_s == 0 {
+            return invalid("shutdown", "need 0 < sell_floor < 1 and timeout_s > 0".to_string());
+        }
+        if self.account.reconcile_interval_s == 0 {
+            return invalid("account.reconcile_interval_s", "must be above zero".to_string());
+        }
+        if self.clock.sync_interval_s == 0 {
+            return invalid("clock.sync_interval_s", "must be above zero".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn ntp_server(&self) -> Option<&str> {
+        Some(self.clock.ntp_server.as_str()).filter(|server| !server.is_empty())
+    }
+
+    pub fn metrics_addr(&self) -> Option<&str> {
+        Some(self.telemetry.metrics_addr.as_str()).filter(|addr| !addr.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_example_is_the_defaults() {
+        let example: Config = toml::from_str(include_str!("../config.example.toml")).unwrap();
+        assert_eq!(example, Config::default());
+        assert!(example.validate().is_ok());
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(toml::from_str::<Config>("[strategy]\ntrend_limt = 4.0\n").is_err());
+        let partial: Config = toml::from_str("[strategy]\ntrend_limit = 6.0\n").unwrap();
+        assert_eq!(partial.strategy.trend_limit, 6.0);
+        assert_eq!(partial.feeds, FeedConfig::default());
+    }
+
+    // the rule's name, and a change to the defaults that breaks it
+    type Case = (&'static str, fn(&mut Config));
+
+    #[test]
+    fn each_rule_rejects_a_bad_value() {
+        let cases: Vec<Case> = vec![
+            ("window", |c| c.window.open_after_s = c.window.close_at_s),
+            ("window", |c| c.window.close_at_s = 901),
+            ("feeds", |c| c.feeds.trend_window_ms = 0),
+            ("feeds.horizons_ms", |c| c.feeds.horizons_ms = vec![0]),
+            ("feeds.vol_half_life_s", |c| c.feeds.vol_half_life_s = f64::NAN),
+            ("feeds.vol_windows_ms", |c| c.feeds.vol_windows_ms = vec![0]),
+            ("feeds.flow_windows_ms", |c| c.feeds.flow_windows_ms = vec![0]),
+            ("feeds.min_live_venues", |c| c.feeds.min_live_venues = 0),
+            ("feeds.min_live_venues", |c| c.feeds.min_live_venues = VENUES.len() + 1),
+            ("feeds.weights", |c| c.feeds.weights.binance = -1.0),
+            ("feeds", |c| c.feeds.lead_lag_ms = 0),
+            ("feeds", |c| c.feeds.max_jump_bps = 0.0),
+            ("feeds.basis_half_life_s", |c| c.feeds.basis_half_life_s = 0.0),
+            ("feeds.kalman", |c| c.feeds.kalman.learning_rate = 1.5),
+            ("strategy.trend_limit", |c| c.strategy.trend_limit = 0.0),
+            ("strategy", |c| c.strategy.z_limit_close = -1.0),
+            ("strategy", |c| c.strategy.min_r_squared = 1.1),
+            ("strategy", |c| c.strategy.min_points = 1),
+            ("strategy.min_agreeing_horizons", |c| c.strategy.min_agreeing_horizons = c.feeds.horizons_ms.len() + 1),
+            ("strategy", |c| c.strategy.min_price = c.strategy.max_price),
+            ("health.max_clock_skew_ms", |c| c.health.max_clock_skew_ms = -1),
+            ("taker.size_usdc", |c| c.taker.size_usdc = Decimal::ZERO),
+            ("exit", |c| c.exit.deadline_lead_s = 900),
+            ("exit", |c| c.exit.max_empty_attempts = 0),
+            ("maker", |c| c.maker.cancel_lead_s = 900),
+            ("paper.fee_rate_bps", |c| c.paper.fee_rate_bps = -1.0),
+            ("shutdown", |c| c.shutdown.sell_floor = Decimal::ONE),
+            ("account.reconcile_interval_s", |c| c.account.reconcile_interval_s = 0),
+            ("clock.sync_interval_s", |c| c.clock.sync_interval_s = 0),
+        ];
+        for (rule, break_it) in cases {
+            let mut config = Config::default();
+            break_it(&mut config);
+            match config.validate() {
+                Err(ConfigError::Invalid { name, .. }) => assert_eq!(name, rule),
+                other => panic!("{rule}: expected it to be rejected, got {other:?}"),
+            }
+        }
+    }
+}
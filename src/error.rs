@@ -0,0 +1,302 @@
+use polymarket_client_sdk::error::{Error as SdkError, Kind, Status, StatusCode};
+use thiserror::Error;
+use tracing::warn;
+
+use crate::metrics;
+use tokio_tungstenite::tungstenite;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("feed: {0}")]
+    Feed(#[from] FeedError),
+    #[error("market discovery: {0}")]
+    Market(#[from] MarketError),
+    #[error("order: {0}")]
+    Order(#[from] OrderError),
+    #[error("signing: {0}")]
+    Signing(#[from] SigningError),
+    #[error("config: {0}")]
+    Config(#[from] ConfigError),
+    #[error("account: {0}")]
+    Account(#[from] AccountError),
+    #[error("io: {0}")]
+    Io(#[from] IoError),
+}
+
+#[derive(Debug, Error)]
+pub enum FeedError {
+    #[error("{venue}: could not connect: {source}")]
+    Connect { venue: &'static str, source: Box<tungstenite::Error> },
+    #[error("{venue}: websocket error: {source}")]
+    WebSocket { venue: &'static str, source: Box<tungstenite::Error> },
+    #[error("{venue}: bad {field} {value:?}")]
+    Parse { venue: &'static str, field: &'static str, value: String },
+    #[error("{venue}: subscription failed: {source}")]
+    Subscribe { venue: &'static str, source: SdkError },
+    #[error("{venue}: stream error: {source}")]
+    Stream { venue: &'static str, source: SdkError },
+    #[error("ntp {server}: {reason}")]
+    Ntp { server: String, reason: String },
+    #[error("{venue}: book snapshot failed: {source}")]
+    Snapshot { venue: &'static str, source: reqwest::Error },
+    #[error("{venue}: book sequence gap, expected {expected} got {got}")]
+    SequenceGap { venue: &'static str, expected: i64, got: i64 },
+    #[error("{venue}: book checksum {got} doesn't match the venue's {expected}")]
+    Checksum { venue: &'static str, expected: u32, got: u32 },
+}
+
+#[derive(Debug, Error)]
+pub enum MarketError {
+    #[error("request for {slug} failed: {source}")]
+    Http { slug: String, source: reqwest::Error },
+    #[error("no markets or clobTokenIds found for {slug}")]
+    NotFound { slug: String },
+    #[error("asked for {slug} but got {found}")]
+    SlugMismatch { slug: String, found: String },
+    #[error("bad clobTokenIds for {slug}: {source}")]
+    TokenIds { slug: String, source: serde_json::Error },
+    #[error("tick size lookup for {token_id} failed: {source}")]
+    TickSize { token_id: String, source: SdkError },
+    #[error("book lookup for {token_id} failed: {source}")]
+    Book { token_id: String, source: SdkError },
+}
+
+#[derive(Debug, Error)]
+pub enum OrderError {
+    #[error("building order on {token_id} failed: {source}")]
+    Build { token_id: String, source: SdkError },
+    #[error("posting order on {token_id} failed: {source}")]
+    Post { token_id: String, source: SdkError },
+    #[error("order on {token_id} rejected: {reason}")]
+    Rejected { token_id: String, reason: String },
+    #[error("cancelling {order_id} failed: {source}")]
+    Cancel { order_id: String, source: SdkError },
+    #[error("looking up {order_id} failed: {source}")]
+    Lookup { order_id: String, source: SdkError },
+    #[error("invalid {what} {value}")]
+    Invalid { what: &'static str, value: String },
+}
+
+// why a sell failed, classified from the CLOB's response to decide whether to retry
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum SellError {
+    #[error("insufficient balance: {0}")]
+    InsufficientBalance(String),
+    #[error("no liquidity above the floor: {0}")]
+    NoLiquidity(String),
+    #[error("rate limited: {0}")]
+    RateLimited(String),
+    #[error("network: {0}")]
+    Network(String),
+    #[error("rejected: {0}")]
+    Rejected(String),
+}
+
+impl SellError {
+    pub fn classify_message(message: &str) -> Self {
+        let lower = message.to_lowercase();
+        if lower.contains("balance") || lower.contains("allowance") {
+            SellError::InsufficientBalance(message.to_string())
+        } else if lower.contains("liquidity") || lower.contains("no opposing orders") || lower.contains("no match")
+            || lower.contains("fully filled") || lower.contains("killed") {
+            SellError::NoLiquidity(message.to_string())
+        } else if lower.contains("rate limit") || lower.contains("too many requests") {
+            SellError::RateLimited(message.to_string())
+        } else {
+            SellError::Rejected(message.to_string())
+        }
+    }
+
+    pub fn classify(error: &SdkError) -> Self {
+        let message = error.to_string();
+        match error.kind() {
+            Kind::Status => match error.downcast_ref::<Status>() {
+                Some(status) if status.status_code == StatusCode::TOO_MANY_REQUESTS => SellError::RateLimited(message),
+                Some(status) if status.status_code.is_server_error() => SellError::Network(message),
+                Some(status) => SellError::classify_message(&status.message),
+                None => SellError::classify_message(&message),
+            },
+            Kind::Internal | Kind::WebSocket => SellError::Network(message),
+            _ => SellError::classify_message(&message),
+        }
+    }
+
+    pub fn retryable(&self) -> bool {
+        !matches!(self, SellError::Rejected(_))
+    }
+
+    pub fn reason(&self) -> &'static str {
+        match self {
+            SellError::InsufficientBalance(_) => "insufficient_balance",
+            SellError::NoLiquidity(_) => "no_liquidity",
+            SellError::RateLimited(_) => "rate_limited",
+            SellError::Network(_) => "network",
+            SellError::Rejected(_) => "rejected",
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SigningError {
+    #[error("signing order on {token_id} failed: {source}")]
+    Sign { token_id: String, source: SdkError },
+    #[error("deriving api credentials failed: {0}")]
+    Credentials(SdkError),
+    #[error("authenticating failed: {0}")]
+    Auth(SdkError),
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("{0} is not set, add it to the .env file in the project root")]
+    MissingEnv(&'static str),
+    #[error("{name} is not valid: {reason}")]
+    Invalid { name: &'static str, reason: String },
+    #[error("could not read {path}: {reason}")]
+    File { path: String, reason: String },
+}
+
+// recordings, logs and anything else on disk that isn't the config
+#[derive(Debug, Error)]
+pub enum IoError {
+    #[error("could not read {path}: {reason}")]
+    Read { path: String, reason: String },
+    #[error("could not write {path}: {reason}")]
+    Write { path: String, reason: String },
+}
+
+#[derive(Debug, Error)]
+pub enum AccountError {
+    #[error("CLOB balance for {token_id} failed: {source}")]
+    ClobBalance { token_id: String, source: SdkError },
+    #[error("CLOB collateral balance failed: {0}")]
+    Collateral(SdkError),
+    #[error("on-chain balance for {token_id} failed: {reason}")]
+    OnChain { token_id: String, reason: String },
+    #[error("on-chain share approval lookup failed: {0}")]
+    Approval(String),
+    #[error("refusing to trade:\n  - {}", .0.join("\n  - "))]
+    Preflight(Vec<String>),
+    #[error("data api positions failed: {0}")]
+    DataApi(reqwest::Error),
+}
+
+impl OrderError {
+    // label for orders_rejected_total, None for errors that aren't a failed placement
+    pub fn reject_reason(&self) -> Option<&'static str> {
+        match self {
+            OrderError::Build { source, .. } | OrderError::Post { source, .. } => Some(SellError::classify(source).reason()),
+            OrderError::Rejected { reason, .. } => Some(SellError::classify_message(reason).reason()),
+            OrderError::Invalid { .. } => Some("invalid"),
+            OrderError::Cancel { .. } | OrderError::Lookup { .. } => None,
+        }
+    }
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Feed(_) => ErrorKind::Feed,
+            Error::Market(_) => ErrorKind::Market,
+            Error::Order(_) => ErrorKind::Order,
+            Error::Signing(_) => ErrorKind::Signing,
+            Error::Config(_) => ErrorKind::Config,
+            Error::Account(_) => ErrorKind::Account,
+            Error::Io(_) => ErrorKind::Io,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Feed,
+    Market,
+    Order,
+    Signing,
+    Config,
+    Account,
+    Io,
+}
+
+impl ErrorKind {
+    pub const ALL: [ErrorKind; 7] = [
+        ErrorKind::Feed,
+        ErrorKind::Market,
+        ErrorKind::Order,
+        ErrorKind::Signing,
+        ErrorKind::Config,
+        ErrorKind::Account,
+        ErrorKind::Io,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorKind::Feed => "feed",
+            ErrorKind::Market => "market",
+            ErrorKind::Order => "order",
+            ErrorKind::Signing => "signing",
+            ErrorKind::Config => "config",
+            ErrorKind::Account => "account",
+            ErrorKind::Io => "io",
+        }
+    }
+}
+
+// for errors the bot carries on from: log them with what we were doing and count them by kind
+pub fn handled(context: &str, err: &Error) {
+    warn!(kind = err.kind().as_str(), error = %err, "{context}");
+    count(err);
+}
+
+// counts without logging, for errors that are expected in bulk (e.g. unparseable feed messages)
+pub fn count(err: &Error) {
+    metrics::ERRORS_HANDLED.with_label_values(&[err.kind().as_str()]).inc();
+    if let Error::Order(e) = err
+        && let Some(reason) = e.reject_reason() {
+        metrics::ORDERS_REJECTED.with_label_values(&[reason]).inc();
+    }
+}
+
+pub fn handled_counts() -> [(ErrorKind, u64); 7] {
+    ErrorKind::ALL.map(|kind| (kind, metrics::ERRORS_HANDLED.with_label_values(&[kind.as_str()]).get()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polymarket_client_sdk::error::Method;
+
+    #[test]
+    fn classifies_the_clob_messages() {
+        let classify = SellError::classify_message;
+        assert!(matches!(classify("not enough balance / allowance"), SellError::InsufficientBalance(_)));
+        assert!(matches!(classify("no orders found to match with FAK order. FAK orders are partially filled or killed if no match is found."),
+            SellError::NoLiquidity(_)));
+        assert!(matches!(classify("order couldn't be fully filled. FOK orders are fully filled or killed."), SellError::NoLiquidity(_)));
+        assert!(matches!(classify("Too Many Requests"), SellError::RateLimited(_)));
+        assert!(matches!(classify("invalid amount for a marketable SELL order"), SellError::Rejected(_)));
+    }
+
+    #[test]
+    fn classifies_the_http_status_first() {
+        let status = |code: u16, message: &str| {
+            SdkError::status(StatusCode::from_u16(code).unwrap(), Method::POST, "/order".to_string(), message)
+        };
+        assert!(matches!(SellError::classify(&status(429, "slow down")), SellError::RateLimited(_)));
+        assert!(matches!(SellError::classify(&status(503, "not enough balance")), SellError::Network(_)));
+        assert!(matches!(SellError::classify(&status(400, "not enough balance / allowance")), SellError::InsufficientBalance(_)));
+        assert!(matches!(SellError::classify(&status(400, "order is invalid")), SellError::Rejected(_)));
+        assert!(matches!(SellError::classify(&SdkError::validation("bad price")), SellError::Rejected(_)));
+    }
+
+    #[test]
+    fn only_rejections_are_final() {
+        assert!(!SellError::Rejected(String::new()).retryable());
+        for e in [SellError::InsufficientBalance(String::new()), SellError::NoLiquidity(String::new()),
+            SellError::RateLimited(String::new()), SellError::Network(String::new())] {
+            assert!(e.retryable(), "{e}");
+        }
+    }
+}
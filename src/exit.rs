@@ -0,0 +1,342 @@
+use polymarket_client_sdk::auth::Signer;
+use polymarket_client_sdk::clob::types::{Amount, OrderType, Side};
+use polymarket_client_sdk::types::Decimal;
+use std::fmt;
+use tokio::time::{sleep, Duration};
+use tracing::{Span, field, info, instrument, warn};
+
+use crate::clock::Clock;
+use crate::config::ExitConfig;
+use crate::error::{self, SellError};
+use crate::metrics;
+use crate::maker::ClobClient;
+use crate::orders::SharedTracker;
+use crate::positions::{self, SharedPositions};
+use crate::shutdown::{self, Shutdown};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExitOutcome {
+    FullyExited { sold: Decimal },
+    Partial { sold: Decimal, remaining: Decimal, reason: String },
+    Stranded { remaining: Decimal, reason: String },
+}
+
+impl fmt::Display for ExitOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExitOutcome::FullyExited { sold } => write!(f, "fully exited, sold {sold} shares"),
+            ExitOutcome::Partial { sold, remaining, reason } => {
+                write!(f, "partial exit, sold {sold} shares, {remaining} left ({reason})")
+            }
+            ExitOutcome::Stranded { remaining, reason } => write!(f, "stranded with {remaining} shares ({reason})"),
+        }
+    }
+}
+
+// Sells the exact CLOB balance of a token with FAK orders that won't fill below `floor`,
+// retrying retryable errors until `deadline` (unix seconds), or until a shutdown when given one.
+pub struct ExitExecutor<'a, S: Signer> {
+    client: &'a ClobClient,
+    signer: &'a S,
+    positions: &'a SharedPositions,
+    tracker: &'a SharedTracker,
+    clock: &'a dyn Clock,
+    retry: Duration,
+    max_empty_attempts: u32,
+    shutdown: Option<Shutdown>,
+}
+
+// the largest rate limit backoff
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+// What an exit knows between attempts, kept apart from the client. Shares are counted from the
+// balance read before each attempt, so fills behind a lost response still count as sold.
+struct Attempts {
+    retry: Duration,
+    backoff: Duration,
+    max_empty: u32,
+    empty: u32, // attempts in a row that sold nothing
+    initial: Option<Decimal>,
+    remaining: Decimal,
+    last_error: String,
+}
+
+enum Step {
+    Sell(Decimal), // shares, to 2dp
+    Done(ExitOutcome),
+}
+
+impl Attempts {
+    fn new(retry: Duration, max_empty: u32) -> Self {
+        Attempts {
+            retry,
+            backoff: retry,
+            max_empty,
+            empty: 0,
+            initial: None,
+            remaining: Decimal::ZERO,
+            last_error: String::from("deadline passed"),
+        }
+    }
+
+    // what to do with `held` shares at now_s
+    fn next(&mut self, held: Decimal, now_s: u64, deadline: u64, shutdown: bool) -> Step {
+        // the CLOB takes share amounts to 2dp, what's below a lot can't be sold
+        self.remaining = held.trunc_with_scale(2);
+        let sold = *self.initial.get_or_insert(self.remaining) - self.remaining;
+
+        if self.remaining < Decimal::new(1, 2) {
+            return Step::Done(ExitOutcome::FullyExited { sold });
+        }
+        if now_s >= deadline {
+            return Step::Done(self.stop(self.last_error.clone()));
+        }
+        if shutdown {
+            return Step::Done(self.stop("shutdown requested".to_string()));
+        }
+        if self.empty >= self.max_empty {
+            return Step::Done(self.stop(format!("{} attempts found nothing to take above the floor: {}", self.empty, self.last_error)));
+        }
+        Step::Sell(self.remaining)
+    }
+
+    // how long to wait before the next attempt, or the outcome if there's no point in one
+    fn record(&mut self, result: &Result<Decimal, SellError>) -> Result<Duration, ExitOutcome> {
+        match result {
+            Ok(filled) => {
+                self.backoff = self.retry;
+                if filled.is_zero() {
+                    self.empty += 1;
+                    self.last_error = "nothing filled above the floor".to_string();
+                } else {
+                    self.empty = 0;
+                }
+                Ok(self.retry)
+            }
+            Err(e) if e.retryable() => {
+                self.last_error = e.to_string();
+                match e {
+                    SellError::RateLimited(_) => {
+                        self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+                        Ok(self.backoff)
+                    }
+                    SellError::NoLiquidity(_) => {
+                        self.empty += 1;
+                        Ok(self.retry)
+                    }
+                    _ => Ok(self.retry),
+                }
+            }
+            Err(e) => Err(self.stop(e.to_string())),
+        }
+    }
+
+    fn stop(&self, reason: String) -> ExitOutcome {
+        let sold = self.initial.unwrap_or_default() - self.remaining;
+        if sold.is_zero() {
+            ExitOutcome::Stranded { remaining: self.remaining, reason }
+        } else {
+            ExitOutcome::Partial { sold, remaining: self.remaining, reason }
+        }
+    }
+}
+
+impl<'a, S: Signer> ExitExecutor<'a, S> {
+    pub fn new(
+        client: &'a ClobClient,
+        signer: &'a S,
+        positions: &'a SharedPositions,
+        tracker: &'a SharedTracker,
+        clock: &'a dyn Clock,
+        config: &ExitConfig,
+    ) -> Self {
+        ExitExecutor {
+            client, signer, positions, tracker, clock,
+            retry: Duration::from_millis(config.retry_ms),
+            max_empty_attempts: config.max_empty_attempts,
+            shutdown: None,
+        }
+    }
+
+    // gives up between attempts once a shutdown is asked for, leaving the rest to the wind-down
+    pub fn until_shutdown(mut self, shutdown: &Shutdown) -> Self {
+        self.shutdown = Some(shutdown.clone());
+        self
+    }
+
+    // sleeps unless a shutdown comes first, true if it did
+    async fn wait(&self, duration: Duration) -> bool {
+        match self.shutdown.clone() {
+            Some(mut shutdown) => shutdown::pause(&mut shutdown, duration).await,
+            None => {
+                sleep(duration).await;
+                false
+            }
+        }
+    }
+
+    pub async fn exit(&self, token_id: &str, floor: Decimal, deadline: u64) -> ExitOutcome {
+        let mut attempts = Attempts::new(self.retry, self.max_empty_attempts);
+
+        loop {
+            let held = match positions::clob_balance(self.client, token_id).await {
+                Ok(held) => {
+                    // right after a fill the balance can lag the ledger, trust the ledger until it settles
+                    let mut positions = self.positions.lock().unwrap();
+                    if positions.settling(token_id) && held < positions.held(token_id) {
+                        positions.held(token_id)
+                    } else {
+                        positions.set(token_id, held);
+                        held
+                    }
+                }
+                Err(e) => {
+                    // fall back to the ledger, it is kept up to date by the user channel
+                    attempts.last_error = format!("balance lookup failed: {e}");
+                    error::count(&e);
+                    self.positions.lock().unwrap().held(token_id)
+                }
+            };
+            let shutdown = self.shutdown.as_ref().is_some_and(|s| *s.borrow());
+            let remaining = match attempts.next(held, self.clock.now_s(), deadline, shutdown) {
+                Step::Sell(remaining) => remaining,
+                Step::Done(outcome) => return outcome,
+            };
+
+            let result = self.sell(token_id, remaining, floor).await;
+            match &result {
+                Ok(filled) => info!(token_id, %filled, %remaining, %floor, "sold"),
+                Err(e) => {
+                    metrics::ORDERS_REJECTED.with_label_values(&[e.reason()]).inc();
+                    if e.retryable() {
+                        warn!(token_id, %remaining, error = %e, "sell failed, retrying");
+                    }
+                }
+            }
+            // after a sale the balance endpoint needs a moment to see the trade, it's read again even on a shutdown
+            match attempts.record(&result) {
+                Ok(wait) => _ = self.wait(wait).await,
+                Err(outcome) => return outcome,
+            }
+        }
+    }
+
+    #[instrument(name = "order", skip_all, fields(order_id = field::Empty, side = "sell", size = %shares, token_id))]
+    async fn sell(&self, token_id: &str, shares: Decimal, floor: Decimal) -> Result<Decimal, SellError> {
+        let amount = Amount::shares(shares).map_err(|e| SellError::Rejected(e.to_string()))?;
+        let order = self.client.market_order().token_id(token_id)
+            .amount(amount).price(floor).side(Side::Sell).order_type(OrderType::FAK)
+            .build().await
+            .map_err(|e| SellError::classify(&e))?;
+        let signed_order = self.client.sign(self.signer, order).await.map_err(|e| SellError::Rejected(e.to_string()))?;
+        let response = self.client.post_order(signed_order).await.map_err(|e| SellError::classify(&e))?;
+
+        Span::current().record("order_id", response.order_id.as_str());
+        if !response.success {
+            return Err(SellError::classify_message(&response.error_msg.unwrap_or_default()));
+        }
+        self.tracker.lock().unwrap().record_post(&response.order_id, token_id, Side::Sell, floor, response.making_amount);
+        Ok(response.making_amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const RETRY: Duration = Duration::from_millis(500);
+    const NOW: u64 = 1_700_000_000;
+    const DEADLINE: u64 = NOW + 60;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    fn sells(step: Step) -> Decimal {
+        match step {
+            Step::Sell(shares) => shares,
+            Step::Done(outcome) => panic!("stopped: {outcome}"),
+        }
+    }
+
+    fn done(step: Step) -> ExitOutcome {
+        match step {
+            Step::Sell(shares) => panic!("still selling {shares}"),
+            Step::Done(outcome) => outcome,
+        }
+    }
+
+    #[test]
+    fn sells_the_balance_to_two_decimals() {
+        let mut attempts = Attempts::new(RETRY, 10);
+        assert_eq!(sells(attempts.next(dec("12.3456"), NOW, DEADLINE, false)), dec("12.34"));
+        assert_eq!(attempts.record(&Ok(dec("10"))), Ok(RETRY));
+        assert_eq!(sells(attempts.next(dec("2.349"), NOW, DEADLINE, false)), dec("2.34"));
+        // dust under a lot is as good as sold
+        assert_eq!(done(attempts.next(dec("0.009"), NOW, DEADLINE, false)), ExitOutcome::FullyExited { sold: dec("12.34") });
+    }
+
+    #[test]
+    fn stops_at_the_deadline_and_on_shutdown() {
+        let mut attempts = Attempts::new(RETRY, 10);
+        assert_eq!(done(attempts.next(dec("10"), DEADLINE, DEADLINE, false)),
+            ExitOutcome::Stranded { remaining: dec("10"), reason: "deadline passed".to_string() });
+
+        let mut attempts = Attempts::new(RETRY, 10);
+        sells(attempts.next(dec("10"), NOW, DEADLINE, false));
+        attempts.record(&Err(SellError::Network("reset".to_string()))).unwrap();
+        assert_eq!(done(attempts.next(dec("4"), DEADLINE + 1, DEADLINE, false)),
+            ExitOutcome::Partial { sold: dec("6"), remaining: dec("4"), reason: "network: reset".to_string() });
+
+        let mut attempts = Attempts::new(RETRY, 10);
+        assert!(matches!(done(attempts.next(dec("10"), NOW, DEADLINE, true)), ExitOutcome::Stranded { reason, .. } if reason == "shutdown requested"));
+    }
+
+    #[test]
+    fn gives_up_when_nothing_fills_above_the_floor() {
+        let mut attempts = Attempts::new(RETRY, 3);
+        for _ in 0..2 {
+            sells(attempts.next(dec("10"), NOW, DEADLINE, false));
+            assert_eq!(attempts.record(&Err(SellError::NoLiquidity("no match".to_string()))), Ok(RETRY));
+        }
+        sells(attempts.next(dec("10"), NOW, DEADLINE, false));
+        assert_eq!(attempts.record(&Ok(Decimal::ZERO)), Ok(RETRY));
+        let ExitOutcome::Stranded { remaining, reason } = done(attempts.next(dec("10"), NOW, DEADLINE, false)) else {
+            panic!("expected stranded");
+        };
+        assert_eq!(remaining, dec("10"));
+        assert!(reason.starts_with("3 attempts found nothing"), "{reason}");
+
+        // a sale in between starts the count again
+        let mut attempts = Attempts::new(RETRY, 2);
+        sells(attempts.next(dec("10"), NOW, DEADLINE, false));
+        attempts.record(&Err(SellError::NoLiquidity(String::new()))).unwrap();
+        sells(attempts.next(dec("10"), NOW, DEADLINE, false));
+        attempts.record(&Ok(dec("5"))).unwrap();
+        sells(attempts.next(dec("5"), NOW, DEADLINE, false));
+        attempts.record(&Err(SellError::NoLiquidity(String::new()))).unwrap();
+        sells(attempts.next(dec("5"), NOW, DEADLINE, false));
+    }
+
+    #[test]
+    fn backs_off_while_rate_limited() {
+        let mut attempts = Attempts::new(RETRY, 10);
+        let limited = Err(SellError::RateLimited("429".to_string()));
+        let waits: Vec<Duration> = (0..6).map(|_| attempts.record(&limited).unwrap()).collect();
+        // doubling from the retry, up to MAX_BACKOFF
+        assert_eq!(waits, [1, 2, 4, 8, 10, 10].map(Duration::from_secs));
+        // other errors wait the usual retry, a sale resets the backoff
+        assert_eq!(attempts.record(&Err(SellError::Network(String::new()))), Ok(RETRY));
+        assert_eq!(attempts.record(&Ok(dec("1"))), Ok(RETRY));
+        assert_eq!(attempts.record(&limited), Ok(RETRY * 2));
+    }
+
+    #[test]
+    fn a_rejection_stops_the_exit() {
+        let mut attempts = Attempts::new(RETRY, 10);
+        sells(attempts.next(dec("10"), NOW, DEADLINE, false));
+        assert_eq!(attempts.record(&Err(SellError::Rejected("invalid amount".to_string()))),
+            Err(ExitOutcome::Stranded { remaining: dec("10"), reason: "rejected: invalid amount".to_string() }));
+    }
+}
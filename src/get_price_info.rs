@@ -1,31 +1,59 @@
 use futures::StreamExt as _;
 use polymarket_client_sdk::clob::ws::Client;
 use tokio::sync::watch::Sender;
+use tokio::time::Instant;
+use tracing::{info, trace};
+
+use crate::clock::SharedClock;
+use crate::error::{FeedError, Result};
+use crate::metrics;
+
+const VENUE: &str = "polymarket";
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BookTop {
+    pub bid: Option<f64>,
+    pub ask: Option<f64>,
+    pub received_at: Option<Instant>,
+    pub server_ts_ms: i64, // the book timestamp polymarket stamped the update with
+}
 
 pub async fn connect(
-    tx: Sender<(Option<f64>, Option<f64>)>,
+    tx: Sender<BookTop>,
     asset_id: &String,
-) -> Result<(), Box<dyn std::error::Error>> {
+    clock: SharedClock,
+) -> Result<()> {
     let client = Client::default();
     
     let asset_ids = vec![asset_id.clone()];
-    let stream = client.subscribe_orderbook(asset_ids)?;
+    let stream = client.subscribe_orderbook(asset_ids)
+        .map_err(|source| FeedError::Subscribe { venue: VENUE, source })?;
     let mut stream = Box::pin(stream);
 
-    println!("WebSocket connected for asset: {}", asset_id);
+    info!(asset_id = %asset_id, "book feed connected");
 
     while let Some(book_result) = stream.next().await {
-        let book = book_result?;
+        let book = book_result.map_err(|source| FeedError::Stream { venue: VENUE, source })?;
+        metrics::feed_message(VENUE, book.timestamp.max(0) as u64, &*clock);
         
         if let (Some(best_bid), Some(best_ask)) = 
             (book.bids.last(), book.asks.last()) {
                 let (best_bid, best_ask) =
                 (best_bid.price.to_string().parse::<f64>().ok(), best_ask.price.to_string().parse::<f64>().ok());
 
-                let _ = tx.send((best_bid, best_ask));
+                trace!(bid = ?best_bid, ask = ?best_ask, "book tick");
+                if let (Some(bid), Some(ask)) = (best_bid, best_ask) {
+                    metrics::BOOK_SPREAD.set(ask - bid);
+                }
+                let _ = tx.send(BookTop {
+                    bid: best_bid,
+                    ask: best_ask,
+                    received_at: Some(clock.instant()),
+                    server_ts_ms: book.timestamp,
+                });
             }
 
     }
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,155 +1,566 @@
+use serde::{Deserialize, Serialize};
 use tokio::sync::watch;
 use tokio::sync::mpsc;
 use std::collections::VecDeque;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::fmt;
 use tokio::time::{Instant, Duration};
+use tracing::{debug, info, trace};
+use std::sync::Arc;
 pub mod binance;
 pub mod coinbase;
 pub mod kraken;
 pub mod bitget;
 pub mod okx;
-use crate::config;
+pub mod bybit;
+pub mod bitstamp;
+pub mod gemini;
+pub mod usdt;
+pub mod depth;
+use depth::BookQuote;
+use crate::basis::BasisEstimator;
+use crate::clock::SharedClock;
+use crate::config::{Basis, Config, Fuser};
+use crate::error::{FeedError, Result};
+use crate::kalman::KalmanFuser;
+use crate::metrics;
+use crate::order_flow::{FlowWindow, OrderFlow};
+use crate::outliers::OutlierFilter;
+use crate::supervisor::{Scope, Supervisor};
+use crate::volatility::{Volatility, VolatilityEstimator};
+use crate::weights::VenueWeighting;
 
+pub const VENUES: [&str; 9] = ["binance", "coinbase", "kraken", "bitget", "okx", "bybit", "bybit-perp", "bitstamp", "gemini"];
+pub const VENUE_URLS: [&str; 9] = [binance::URL, coinbase::URL, kraken::URL, bitget::URL, okx::URL, bybit::URL, bybit::PERP_URL, bitstamp::URL, gemini::URL];
 
-fn trend_slope(data: &VecDeque<(u64, f64)>) -> f64 {
-    let n = data.len() as f64;
-    if n < 2.0 {
-        return 0.0;
-    }
+// what each venue's BTC pair is quoted in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quote {
+    Usd,
+    Usdt,
+    UsdtPerp, // a USDT perpetual, converted like spot but its funding basis kept out of the USDT/USD estimate
+}
+
+pub const QUOTES: [Quote; 9] = [Quote::Usdt, Quote::Usd, Quote::Usd, Quote::Usdt, Quote::Usdt, Quote::Usdt, Quote::UsdtPerp, Quote::Usd, Quote::Usd];
 
-    let t0 = data.front().unwrap().0;
+// latest state of every venue, published on each tick
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VenueSnapshot {
+    pub prices: [f64; VENUES.len()], // each venue's latest VWAP, in USD
+    pub updated: [Option<Instant>; VENUES.len()],
+    pub excluded: [bool; VENUES.len()], // left out of the aggregate for disagreeing with the other venues
+    pub aggregate: Option<f64>, // None while fewer than feeds.min_live_venues are live
+    pub usdt_usd: f64, // the USDT venues were converted at
+    pub books: [Option<BookQuote>; VENUES.len()], // the latest top of each venue's book, with feeds.depth on
+}
+
+// one trade on a venue, with the venue's VWAP after it
+#[derive(Debug, Clone, Copy)]
+pub struct VenueTrade {
+    pub vwap: f64,
+    pub notional: f64, // $ the trade was for
+    pub aggressor: Aggressor,
+}
 
-    let mut sum_t = 0.0f64;
-    let mut sum_p = 0.0f64;
-    let mut sum_tt = 0.0f64;
-    let mut sum_tp = 0.0f64;
+// what a venue feed sends the aggregator
+enum VenueInput {
+    Trade(VenueTrade),
+    Book(BookQuote),
+}
+
+// the side that crossed the spread, the taker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggressor {
+    Buy,
+    Sell,
+}
+
+// numeric fields arrive as strings on every venue
+pub fn parse_field(venue: &'static str, field: &'static str, value: &str) -> Result<f64, FeedError> {
+    value.parse::<f64>().map_err(|_| FeedError::Parse { venue, field, value: value.to_string() })
+}
 
-    for &(t, p) in data {
-        let t = (t - t0) as f64 * 0.0001; // normalize + convert
-        sum_t += t;
-        sum_p += p;
-        sum_tt += t * t;
-        sum_tp += t * p;
+// a venue's side field, given how the venue spells the taker buying and selling
+pub fn parse_side(venue: &'static str, field: &'static str, value: &str, buy: &str, sell: &str) -> Result<Aggressor, FeedError> {
+    match value {
+        v if v == buy => Ok(Aggressor::Buy),
+        v if v == sell => Ok(Aggressor::Sell),
+        _ => Err(FeedError::Parse { venue, field, value: value.to_string() }),
     }
+}
+
+// The line of best fit through the aggregate over trend_window_ms, published on every tick after
+// the warmup. A slope from a handful of points or a noisy window is weaker evidence of a move than
+// the same slope from a clean one, the fit quality lets the entry rule tell them apart. The
+// horizons are the same fit over feeds.horizons_ms, to compare short and long term moves. The
+// z-score scales the slope by the recent volatility, so one threshold means the same in a quiet
+// market as in a busy one. With the Kalman fuser the slope is the filter's velocity and the
+// z-score its ratio to the velocity's standard error, the fit fields still describe the line
+// through the filtered price.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrendSignal {
+    pub slope: f64, // $/s
+    pub intercept: f64, // the fitted price at the window's first point
+    pub r_squared: f64, // share of the price variance the line explains, 0 for a flat window
+    pub residual_se: f64, // $, how far prices scatter around the line
+    pub points: usize,
+    pub span_ms: u64, // from the window's first point to its last
+    pub venues: usize, // live venues in the latest aggregate
+    #[serde(default)]
+    pub horizons: Vec<HorizonTrend>,
+    #[serde(default)]
+    pub z_score: f64, // slope in standard deviations of a random walk's, 0 until the volatility is known
+    #[serde(default)]
+    pub volatility: Volatility,
+    #[serde(default)]
+    pub order_flow: Vec<OrderFlow>, // over each of feeds.flow_windows_ms
+    #[serde(default)]
+    pub book_imbalance: f64, // top of book, weighted over the venues with a live book, 0 without any
+}
 
-    let denom = n * sum_tt - sum_t * sum_t;
-    if denom == 0.0 {
-        return 0.0;
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct HorizonTrend {
+    pub horizon_ms: u64,
+    pub slope: f64, // $/s
+    pub r_squared: f64,
+    pub points: usize,
+    pub span_ms: u64, // short of horizon_ms until the feed has run that long
+    #[serde(default)]
+    pub z_score: f64,
+}
+
+impl fmt::Display for TrendSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:+.3}$/s z {:+.2} (r² {:.2}, se {:.2}$, {} points over {}ms, {} venues, vol {:.2e}/√s)",
+            self.slope, self.z_score, self.r_squared, self.residual_se, self.points, self.span_ms, self.venues, self.volatility.per_second())?;
+        for h in &self.horizons {
+            write!(f, ", {}ms {:+.3}$/s z {:+.2} r² {:.2}", h.horizon_ms, h.slope, h.z_score, h.r_squared)?;
+        }
+        for flow in &self.order_flow {
+            write!(f, ", flow {}ms {:+.2}", flow.window_ms, flow.volume_imbalance)?;
+        }
+        if self.book_imbalance != 0.0 {
+            write!(f, ", book {:+.2}", self.book_imbalance)?;
+        }
+        Ok(())
     }
+}
 
-    (n * sum_tp - sum_t * sum_p) / denom
+// the origin moves up to the window this often, the sums lose precision far from it
+const REBASE_MS: u64 = 600_000;
+
+// Ordinary least squares over the last `horizon_ms` of the aggregate, in seconds and dollars.
+// The sums are updated as points enter and leave the window, so each tick costs O(1) whatever
+// the horizon. They're taken relative to an origin near the window, raw btc prices and unix
+// times would cancel out most of their precision.
+struct RollingFit {
+    horizon_ms: u64,
+    window: VecDeque<(u64, f64)>,
+    origin: (u64, f64),
+    n: f64,
+    sum_t: f64,
+    sum_p: f64,
+    sum_tt: f64,
+    sum_tp: f64,
+    sum_pp: f64,
 }
 
+impl RollingFit {
+    fn new(horizon_ms: u64) -> Self {
+        RollingFit {
+            horizon_ms,
+            window: VecDeque::new(),
+            origin: (0, 0.0),
+            n: 0.0,
+            sum_t: 0.0,
+            sum_p: 0.0,
+            sum_tt: 0.0,
+            sum_tp: 0.0,
+            sum_pp: 0.0,
+        }
+    }
 
-pub async fn connect(tx_out: watch::Sender<f64>) -> Result<(), Box<dyn std::error::Error>> {
-    let (tx_binance, mut rx_binance) = mpsc::channel::<f64>(1024);
-    let (tx_coinbase, mut rx_coinbase) = mpsc::channel::<f64>(1024);
-    let (tx_kraken, mut rx_kraken) = mpsc::channel::<f64>(1024);
-    let (tx_bitget, mut rx_bitget) = mpsc::channel::<f64>(1024);
-    let (tx_okx, mut rx_okx) = mpsc::channel::<f64>(1024);
+    fn push(&mut self, time_ms: u64, price: f64) {
+        if self.window.is_empty() {
+            self.origin = (time_ms, price);
+        }
+        self.window.push_back((time_ms, price));
+        self.add(time_ms, price, 1.0);
 
-    let mut warmup_start: Option<Instant> = None;
+        let start = time_ms.saturating_sub(self.horizon_ms);
+        while let Some(&(t, p)) = self.window.front() && t < start {
+            self.window.pop_front();
+            self.add(t, p, -1.0);
+        }
 
-    let mut prices = [0.0,0.0,0.0,0.0,0.0];
+        if time_ms.saturating_sub(self.origin.0) > REBASE_MS {
+            self.origin = self.window[0];
+            (self.n, self.sum_t, self.sum_p, self.sum_tt, self.sum_tp, self.sum_pp) = (0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+            for (t, p) in self.window.clone() {
+                self.add(t, p, 1.0);
+            }
+        }
+    }
 
-    let weights: [f64; 5] = [0.354, 0.308, 0.059, 0.104, 0.175]; // using 24hr trading volume
+    fn add(&mut self, time_ms: u64, price: f64, weight: f64) {
+        let t = (time_ms as f64 - self.origin.0 as f64) / 1000.0;
+        let p = price - self.origin.1;
+        self.n += weight;
+        self.sum_t += weight * t;
+        self.sum_p += weight * p;
+        self.sum_tt += weight * t * t;
+        self.sum_tp += weight * t * p;
+        self.sum_pp += weight * p * p;
+    }
 
-    let mut price_times: VecDeque<(u64, f64)> = VecDeque::new();
+    fn signal(&self, venues: usize) -> TrendSignal {
+        let (Some(&(first_ms, first_price)), Some(&(last_ms, _))) = (self.window.front(), self.window.back()) else {
+            return TrendSignal { venues, ..TrendSignal::default() };
+        };
+        let points = self.window.len();
+        let mut signal = TrendSignal { intercept: first_price, points, span_ms: last_ms.saturating_sub(first_ms), venues, ..TrendSignal::default() };
 
-    tokio::spawn(async move {
-        let _ = binance::connect(tx_binance).await;
-    });
-    tokio::spawn(async move {
-        let _ = coinbase::connect(tx_coinbase).await;
-    });
-    tokio::spawn(async move {
-        let _ = kraken::connect(tx_kraken).await;
-    });
-    tokio::spawn(async move {
-        let _ = bitget::connect(tx_bitget).await;
-    });
-    tokio::spawn(async move {
-        let _ = okx::connect(tx_okx).await;
-    });
+        let n = points as f64;
+        let (mean_t, mean_p) = (self.sum_t / n, self.sum_p / n);
+        let s_tt = self.sum_tt - self.sum_t * mean_t;
+        let s_tp = self.sum_tp - self.sum_t * mean_p;
+        let s_pp = (self.sum_pp - self.sum_p * mean_p).max(0.0);
+        // a window within a millisecond has no slope, and the rounding in the sums is bigger than it
+        if points < 2 || s_tt < 1e-9 {
+            signal.intercept = mean_p + self.origin.1;
+            return signal;
+        }
 
-    let mut price_updates: [Option<Instant>; 5] = [None; 5];
+        signal.slope = s_tp / s_tt;
+        let first_t = (first_ms as f64 - self.origin.0 as f64) / 1000.0;
+        signal.intercept = mean_p + signal.slope * (first_t - mean_t) + self.origin.1;
+        let sse = (s_pp - signal.slope * s_tp).max(0.0);
+        if s_pp > 0.0 {
+            signal.r_squared = (1.0 - sse / s_pp).clamp(0.0, 1.0);
+        }
+        if points > 2 {
+            signal.residual_se = (sse / (n - 2.0)).sqrt();
+        }
+        signal
+    }
 
-    loop {
+    fn horizon(&self, volatility: &Volatility, price: f64) -> HorizonTrend {
+        let fit = self.signal(0);
+        HorizonTrend {
+            horizon_ms: self.horizon_ms,
+            slope: fit.slope,
+            r_squared: fit.r_squared,
+            points: fit.points,
+            span_ms: fit.span_ms,
+            z_score: volatility.z_score(fit.slope, fit.span_ms, price),
+        }
+    }
+}
+
+// No trend is published until warmup_s after the first aggregate, the fits need the points.
+struct Warmup {
+    duration: Duration,
+    start: Option<Instant>,
+}
 
-        let now = Instant::now();
+impl Warmup {
+    fn new(warmup_s: u64) -> Self {
+        Warmup { duration: Duration::from_secs(warmup_s), start: None }
+    }
 
-        tokio::select! {
-            Some(binance_vwap) = rx_binance.recv() => {
-                prices[0] = binance_vwap;
-                price_updates[0] = Some(now);
-            }
-            Some(coinbase_vwap) = rx_coinbase.recv() => {
-                prices[1] = coinbase_vwap;
-                price_updates[1] = Some(now);
-            }
-            Some(kraken_vwap) = rx_kraken.recv() => {
-                prices[2] = kraken_vwap;
-                price_updates[2] = Some(now);
+    // called on each aggregate, the first one starts the warmup
+    fn done(&mut self, now: Instant) -> bool {
+        let Some(start) = self.start else {
+            info!("Waiting {} seconds to get mean", self.duration.as_secs());
+            self.start = Some(now);
+            return false;
+        };
+        now.duration_since(start) >= self.duration
+    }
+}
+
+pub async fn connect(
+    config: Arc<Config>,
+    clock: SharedClock,
+    supervisor: Supervisor,
+    tx_out: watch::Sender<TrendSignal>,
+    tx_venues: watch::Sender<VenueSnapshot>,
+) -> Result<()> {
+    let (tx_binance, mut rx_binance) = mpsc::channel::<VenueTrade>(1024);
+    let (tx_coinbase, mut rx_coinbase) = mpsc::channel::<VenueTrade>(1024);
+    let (tx_kraken, mut rx_kraken) = mpsc::channel::<VenueTrade>(1024);
+    let (tx_bitget, mut rx_bitget) = mpsc::channel::<VenueTrade>(1024);
+    let (tx_okx, mut rx_okx) = mpsc::channel::<VenueTrade>(1024);
+    let (tx_bybit, mut rx_bybit) = mpsc::channel::<VenueTrade>(1024);
+    let (tx_bybit_perp, mut rx_bybit_perp) = mpsc::channel::<VenueTrade>(1024);
+    let (tx_bitstamp, mut rx_bitstamp) = mpsc::channel::<VenueTrade>(1024);
+    let (tx_gemini, mut rx_gemini) = mpsc::channel::<VenueTrade>(1024);
+    let (tx_usdt, mut rx_usdt) = mpsc::channel::<VenueTrade>(1024);
+    let (tx_book, mut rx_book) = mpsc::channel::<BookQuote>(1024);
+
+    let mut prices = [0.0; VENUES.len()];
+
+    let feeds = &config.feeds;
+    let mut warmup = Warmup::new(feeds.warmup_s);
+    let mut weighting = VenueWeighting::new(feeds);
+    let mut outliers = OutlierFilter::new(feeds);
+    let mut basis = BasisEstimator::new(feeds);
+    let mut flows: Vec<FlowWindow> = feeds.flow_windows_ms.iter().map(|ms| FlowWindow::new(*ms)).collect();
+
+    // the trend the entry rule reads, plus the extra horizons published alongside it
+    let mut trend_fit = RollingFit::new(feeds.trend_window_ms);
+    let mut horizon_fits: Vec<RollingFit> = feeds.horizons_ms.iter().map(|ms| RollingFit::new(*ms)).collect();
+    let mut volatility = VolatilityEstimator::new(feeds.vol_half_life_s, &feeds.vol_windows_ms);
+    let mut kalman = (feeds.fuser == Fuser::Kalman).then(|| KalmanFuser::new(&feeds.kalman, VENUES.len()));
+
+    // the venues reconnect after reconnect_ms whenever they drop, spawned again (replacing the old
+    // ones) if this task restarts
+    let (vwap_ms, coinbase_vwap_ms) = (feeds.vwap_window_ms, feeds.coinbase_vwap_window_ms);
+    let reconnect = Duration::from_millis(feeds.reconnect_ms);
+    let [clock_binance, clock_coinbase, clock_kraken, clock_bitget, clock_okx, clock_bybit, clock_bybit_perp, clock_bitstamp, clock_gemini] =
+        [(); VENUES.len()].map(|_| clock.clone());
+    supervisor.spawn("binance feed", Scope::Run, reconnect, move || binance::connect(tx_binance.clone(), vwap_ms, clock_binance.clone()));
+    supervisor.spawn("coinbase feed", Scope::Run, reconnect, move || coinbase::connect(tx_coinbase.clone(), coinbase_vwap_ms, clock_coinbase.clone()));
+    supervisor.spawn("kraken feed", Scope::Run, reconnect, move || kraken::connect(tx_kraken.clone(), vwap_ms, clock_kraken.clone()));
+    supervisor.spawn("bitget feed", Scope::Run, reconnect, move || bitget::connect(tx_bitget.clone(), vwap_ms, clock_bitget.clone()));
+    supervisor.spawn("okx feed", Scope::Run, reconnect, move || okx::connect(tx_okx.clone(), vwap_ms, clock_okx.clone()));
+    supervisor.spawn("bybit feed", Scope::Run, reconnect, move || bybit::connect(tx_bybit.clone(), vwap_ms, clock_bybit.clone()));
+    supervisor.spawn("bybit perp feed", Scope::Run, reconnect, move || bybit::connect_perp(tx_bybit_perp.clone(), vwap_ms, clock_bybit_perp.clone()));
+    supervisor.spawn("bitstamp feed", Scope::Run, reconnect, move || bitstamp::connect(tx_bitstamp.clone(), vwap_ms, clock_bitstamp.clone()));
+    supervisor.spawn("gemini feed", Scope::Run, reconnect, move || gemini::connect(tx_gemini.clone(), vwap_ms, clock_gemini.clone()));
+    if feeds.basis == Basis::Feed {
+        let clock_usdt = clock.clone();
+        supervisor.spawn("usdt feed", Scope::Run, reconnect, move || usdt::connect(tx_usdt.clone(), vwap_ms, clock_usdt.clone()));
+    } else {
+        drop(tx_usdt);
+    }
+    // while a venue's book is live its microprice stands in for its trade VWAP
+    if feeds.depth {
+        let [tx_binance_book, tx_coinbase_book, tx_kraken_book, tx_okx_book, tx_bybit_book] = [(); 5].map(|_| tx_book.clone());
+        let [clock_binance, clock_coinbase, clock_kraken, clock_okx, clock_bybit] = [(); 5].map(|_| clock.clone());
+        supervisor.spawn("binance depth", Scope::Run, reconnect, move || binance::connect_depth(tx_binance_book.clone(), clock_binance.clone()));
+        supervisor.spawn("coinbase depth", Scope::Run, reconnect, move || coinbase::connect_depth(tx_coinbase_book.clone(), clock_coinbase.clone()));
+        supervisor.spawn("kraken depth", Scope::Run, reconnect, move || kraken::connect_depth(tx_kraken_book.clone(), clock_kraken.clone()));
+        supervisor.spawn("okx depth", Scope::Run, reconnect, move || okx::connect_depth(tx_okx_book.clone(), clock_okx.clone()));
+        supervisor.spawn("bybit depth", Scope::Run, reconnect, move || bybit::connect_depth(tx_bybit_book.clone(), clock_bybit.clone()));
+    }
+    drop(tx_book);
+
+    let mut price_updates = [None; VENUES.len()];
+    let mut book_updates: [Option<Instant>; VENUES.len()] = [None; VENUES.len()];
+    let mut books = [None; VENUES.len()];
+
+    loop {
+        let (venue, input) = tokio::select! {
+            Some(trade) = rx_binance.recv() => (0, VenueInput::Trade(trade)),
+            Some(trade) = rx_coinbase.recv() => (1, VenueInput::Trade(trade)),
+            Some(trade) = rx_kraken.recv() => (2, VenueInput::Trade(trade)),
+            Some(trade) = rx_bitget.recv() => (3, VenueInput::Trade(trade)),
+            Some(trade) = rx_okx.recv() => (4, VenueInput::Trade(trade)),
+            Some(trade) = rx_bybit.recv() => (5, VenueInput::Trade(trade)),
+            Some(trade) = rx_bybit_perp.recv() => (6, VenueInput::Trade(trade)),
+            Some(trade) = rx_bitstamp.recv() => (7, VenueInput::Trade(trade)),
+            Some(trade) = rx_gemini.recv() => (8, VenueInput::Trade(trade)),
+            Some(quote) = rx_book.recv() => (quote.venue, VenueInput::Book(quote)),
+            Some(trade) = rx_usdt.recv() => {
+                basis.observe_feed(clock.now_ms(), trade.vwap);
+                continue;
             }
-            Some(bitget_vwap) = rx_bitget.recv() => {
-                prices[3] = bitget_vwap;
-                price_updates[3] = Some(now);
+            else => break,
+        };
+        let now = clock.instant();
+        let timestamp = clock.now_ms();
+        let max_age = Duration::from_millis(feeds.max_venue_age_ms);
+        let mut live = price_updates.map(|t| t.is_some_and(|t| now.duration_since(t) < max_age));
+        let booked = book_updates[venue].is_some_and(|t| now.duration_since(t) < max_age);
+        let (quoted, notional) = match input {
+            VenueInput::Trade(trade) => {
+                for flow in &mut flows {
+                    flow.push(timestamp, venue, trade.notional, trade.aggressor);
+                }
+                // the book already prices the venue, the trade only counts for its volume
+                if booked {
+                    weighting.trade(venue, timestamp, prices[venue], trade.notional);
+                    continue;
+                }
+                (trade.vwap, trade.notional)
             }
-            Some(okx_vwap) = rx_okx.recv() => {
-                prices[4] = okx_vwap;
-                price_updates[4] = Some(now);
+            VenueInput::Book(quote) => {
+                book_updates[venue] = Some(now);
+                books[venue] = Some(quote);
+                metrics::BOOK_IMBALANCE.with_label_values(&[VENUES[venue]]).set(quote.imbalance());
+                (quote.microprice(), 0.0)
             }
-            else => break,
+        };
+        // checked at the current basis, a dropped tick mustn't move the basis either
+        if let Err(rejection) = outliers.check_tick(venue, basis.to_usd(venue, quoted), &prices, &live) {
+            metrics::TICKS_REJECTED.with_label_values(&[VENUES[venue]]).inc();
+            debug!(venue = VENUES[venue], %rejection, "dropping tick");
+            continue;
+        }
+        let price = basis.observe(venue, timestamp, quoted, &live);
+
+        prices[venue] = price;
+        price_updates[venue] = Some(now);
+        live[venue] = true;
+        outliers.update(timestamp, &prices, &live);
+        let excluded: [bool; VENUES.len()] = std::array::from_fn(|i| outliers.is_excluded(i));
+
+        weighting.trade(venue, timestamp, price, notional);
+        let weights = weighting.weights();
+        if let Some(kalman) = kalman.as_mut()
+            && !excluded[venue] {
+            let price_sd = volatility.snapshot(timestamp).per_second() * price;
+            kalman.observe(venue, timestamp, price, price_sd * price_sd, weights);
+            metrics::VENUE_BIAS.with_label_values(&[VENUES[venue]]).set(kalman.bias(venue));
         }
 
         let mut weighted_sum = 0.0;
         let mut weight_sum = 0.0;
         let mut count = 0;
+        let (mut imbalance_sum, mut book_weight) = (0.0, 0.0);
 
-        for i in 0..5 {
-            if let Some(update_time) = price_updates[i] {
-                if now.duration_since(update_time).as_secs() < 5 {
-                    let w = weights[i];
-                    weighted_sum += prices[i] * w;
-                    weight_sum += w;
-                    count += 1;
+        for i in 0..VENUES.len() {
+            if live[i] && !excluded[i] {
+                let w = weights[i];
+                weighted_sum += prices[i] * w;
+                weight_sum += w;
+                count += 1;
+                if let Some(quote) = books[i]
+                    && book_updates[i].is_some_and(|t| now.duration_since(t) < max_age) {
+                    imbalance_sum += quote.imbalance() * w;
+                    book_weight += w;
                 }
             }
         }
+        let book_imbalance = if book_weight > 0.0 { imbalance_sum / book_weight } else { 0.0 };
 
-        if count < 4 {
+        if count < feeds.min_live_venues {
+            let _ = tx_venues.send(VenueSnapshot { prices, updated: price_updates, excluded, aggregate: None, usdt_usd: basis.usdt_usd(), books });
             continue;
         }
 
-        let new_price = weighted_sum / weight_sum as f64;
+        let new_price = kalman.as_ref().and_then(KalmanFuser::price).unwrap_or(weighted_sum / weight_sum);
+        let _ = tx_venues.send(VenueSnapshot { prices, updated: price_updates, excluded, aggregate: Some(new_price), usdt_usd: basis.usdt_usd(), books });
+        metrics::AGGREGATE_PRICE.set(new_price);
 
-        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
-        price_times.push_back((timestamp, new_price));
-        
+        trend_fit.push(timestamp, new_price);
+        for fit in &mut horizon_fits {
+            fit.push(timestamp, new_price);
+        }
+        volatility.push(timestamp, new_price);
 
-        let window_start = timestamp.saturating_sub(config::LIN_BEST_FIT_MS);
+        if !warmup.done(now) {
+            continue;
+        }
 
-        while let Some((time, _)) = price_times.front() {
-            if *time < window_start {
-                price_times.pop_front(); 
-            } else {
-                break;
-            }
+        let vol = volatility.snapshot(timestamp);
+        let fit = trend_fit.signal(count);
+        let mut trend = TrendSignal {
+            horizons: horizon_fits.iter().map(|h| h.horizon(&vol, new_price)).collect(),
+            z_score: vol.z_score(fit.slope, fit.span_ms, new_price),
+            volatility: vol,
+            order_flow: flows.iter().map(FlowWindow::flow).collect(),
+            book_imbalance,
+            ..fit
+        };
+        if let Some(kalman) = &kalman {
+            trend.slope = kalman.velocity();
+            trend.z_score = if kalman.velocity_se() > 0.0 { trend.slope / kalman.velocity_se() } else { 0.0 };
+        }
+        trace!(price = new_price, %trend, "trend tick");
+        metrics::TREND_SLOPE.set(trend.slope);
+        metrics::TREND_Z_SCORE.set(trend.z_score);
+        metrics::TREND_R_SQUARED.set(trend.r_squared);
+        metrics::VOLATILITY.set(trend.volatility.per_second());
+        for flow in &trend.order_flow {
+            metrics::ORDER_FLOW_IMBALANCE.with_label_values(&[&flow.window_ms.to_string()]).set(flow.volume_imbalance);
+        }
+        for h in &trend.horizons {
+            metrics::TREND_HORIZON_SLOPE.with_label_values(&[&h.horizon_ms.to_string()]).set(h.slope);
         }
+        let _ = tx_out.send(trend);
+    }
+    Ok(())
+}
 
-        let trend = trend_slope(&price_times);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, SimulatedClock};
+    use crate::testing::{Rng, ols};
 
-        if warmup_start.is_none() {
-            println!("Waiting 5 seconds to get mean");
-            warmup_start = Some(Instant::now());
-            continue;
+    #[test]
+    fn warmup_runs_from_the_first_aggregate() {
+        let clock = SimulatedClock::new(1_700_000_000_000);
+        let mut warmup = Warmup::new(5);
+
+        assert!(!warmup.done(clock.instant()), "the first aggregate only starts it");
+        clock.set(1_700_000_004_999);
+        assert!(!warmup.done(clock.instant()));
+        clock.set(1_700_000_005_000);
+        assert!(warmup.done(clock.instant()));
+        clock.set(1_700_000_060_000);
+        assert!(warmup.done(clock.instant()), "it stays done");
+    }
+
+    #[test]
+    fn no_warmup_still_skips_the_first_aggregate() {
+        let clock = SimulatedClock::new(0);
+        let mut warmup = Warmup::new(0);
+        assert!(!warmup.done(clock.instant()));
+        assert!(warmup.done(clock.instant()));
+    }
+
+    #[test]
+    fn rolling_fit_matches_a_naive_fit_of_its_window() {
+        let mut rng = Rng::new(5);
+        let horizon_ms = 30_000;
+        let mut fit = RollingFit::new(horizon_ms);
+        let (mut t, mut price) = (1_700_000_000_000u64, 60_000.0);
+        let mut points = Vec::new();
+
+        // irregular ticks, some in the same millisecond, for long enough to rebase a few times
+        for i in 0..4000 {
+            t += rng.next_u64() % 700;
+            price += 0.3 * rng.normal() + 0.02;
+            fit.push(t, price);
+            points.push((t, price));
+            points.retain(|(pt, _)| *pt >= t - horizon_ms);
+
+            let signal = fit.signal(3);
+            assert_eq!(signal.points, points.len());
+            assert_eq!(signal.span_ms, t - points[0].0);
+            if i % 97 != 0 || points.len() < 3 {
+                continue;
+            }
+            let (slope, intercept, r_squared, residual_se) = ols(&points);
+            assert!((signal.slope - slope).abs() < 1e-6, "slope {} vs {slope}", signal.slope);
+            assert!((signal.intercept - intercept).abs() < 1e-5, "intercept {} vs {intercept}", signal.intercept);
+            assert!((signal.r_squared - r_squared).abs() < 1e-6, "r² {} vs {r_squared}", signal.r_squared);
+            assert!((signal.residual_se - residual_se).abs() < 1e-6, "se {} vs {residual_se}", signal.residual_se);
         }
+    }
 
-        if warmup_start.unwrap().elapsed() < Duration::from_secs(5) {
-            continue;
+    #[test]
+    fn rolling_fit_of_a_line_is_exact() {
+        let mut fit = RollingFit::new(60_000);
+        for i in 0..200u64 {
+            fit.push(i * 500, 60_000.0 + 2.5 * i as f64 * 0.5);
         }
+        let signal = fit.signal(5);
+        assert!((signal.slope - 2.5).abs() < 1e-9);
+        assert!((signal.r_squared - 1.0).abs() < 1e-9);
+        assert!(signal.residual_se < 1e-6);
+        assert_eq!(signal.venues, 5);
+    }
 
-        let _ = tx_out.send(trend);
+    #[test]
+    fn rolling_fit_without_a_span_has_no_slope() {
+        let mut fit = RollingFit::new(60_000);
+        fit.push(1_000, 100.0);
+        fit.push(1_000, 102.0);
+        let signal = fit.signal(1);
+        assert_eq!((signal.slope, signal.points, signal.span_ms), (0.0, 2, 0));
+        assert_eq!(signal.intercept, 101.0);
     }
-    Ok(())
 }
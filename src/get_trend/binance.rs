@@ -2,19 +2,34 @@ use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
 use std::collections::VecDeque;
 use futures_util::{StreamExt, SinkExt};
 use tokio::sync::mpsc::Sender;
-use crate::config::VWAP_WINDOW_MS;
+use crate::clock::SharedClock;
+use crate::error::{self, FeedError, Result};
+use crate::get_trend::{Aggressor, VenueTrade, parse_field};
+use crate::get_trend::depth::{BookQuote, BookSide, LocalBook};
+use crate::metrics;
 
 #[derive(serde::Deserialize)]
 struct BinanceTrade {
     #[serde(rename = "p")] price: String,
     #[serde(rename = "q")] quantity: String,
     #[serde(rename = "E")] time: u64,
+    #[serde(rename = "m")] buyer_is_maker: bool,
 }
 
-pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>> {
-    let url = "wss://stream.binance.com:9443/ws/btcusdt@trade";
-    let (ws_stream, _) = connect_async(url).await.unwrap();
+const VENUE: &str = "binance";
+const DEPTH_VENUE: &str = "binance-depth";
+pub const URL: &str = "wss://stream.binance.com:9443/ws/btcusdt@trade";
+
+// the buyer making means the seller took
+fn parse(t: &BinanceTrade) -> Result<(f64, f64, u64, Aggressor), FeedError> {
+    let aggressor = if t.buyer_is_maker { Aggressor::Sell } else { Aggressor::Buy };
+    Ok((parse_field(VENUE, "p", &t.price)?, parse_field(VENUE, "q", &t.quantity)?, t.time, aggressor))
+}
+
+pub async fn connect(tx: Sender<VenueTrade>, window_ms: u64, clock: SharedClock) -> Result<()> {
+    let (ws_stream, _) = connect_async(URL).await.map_err(|e| FeedError::Connect { venue: VENUE, source: Box::new(e) })?;
     let (mut write, mut read) = ws_stream.split();
+    metrics::feed_connected(VENUE);
 
     let mut trades: VecDeque<(u64, f64, f64)> = VecDeque::new();
 
@@ -33,13 +48,20 @@ pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>>
                     }
                 };
 
-                let (price, quantity, timestamp) = (t.price.parse().unwrap(), t.quantity.parse().unwrap(), t.time);
+                let (price, quantity, timestamp, aggressor) = match parse(&t) {
+                    Ok(t) => t,
+                    Err(e) => {
+                        error::handled("skipping trade", &e.into());
+                        continue
+                    }
+                };
+                metrics::feed_message(VENUE, timestamp, &*clock);
 
                 total_volume += quantity;
                 price_vol += price * quantity;
                 trades.push_back((timestamp, price, quantity));
 
-                let window_start = timestamp.saturating_sub(VWAP_WINDOW_MS);
+                let window_start = timestamp.saturating_sub(window_ms);
 
                 while let Some((time, price, quantity)) = trades.front() {
                     if *time < window_start {
@@ -52,20 +74,139 @@ pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>>
                 }
                 if total_volume > 0.0 {
                     let vwap = price_vol / total_volume;
-                    if tx.send(vwap).await.is_err() {
+                    if tx.send(VenueTrade { vwap, notional: price * quantity, aggressor }).await.is_err() {
                         break;
                     }
                 }
             }
             Ok(Message::Ping(data)) => {
-                write.send(Message::Pong(data)).await?;
+                write.send(Message::Pong(data)).await
+                    .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
             }
             Err(e) => {
-                println!("FAILED: {e}");
+                error::handled("feed read failed", &FeedError::WebSocket { venue: VENUE, source: Box::new(e) }.into());
             }
             _ => {
             }
         }
     }
     Ok(())
-}
\ No newline at end of file
+}
+#[derive(serde::Deserialize)]
+struct BinanceDepthSnapshot {
+    #[serde(rename = "lastUpdateId")] last_update_id: i64,
+    bids: Vec<(String, String)>,
+    asks: Vec<(String, String)>,
+}
+
+#[derive(serde::Deserialize)]
+struct BinanceDepthUpdate {
+    #[serde(rename = "E")] time: u64,
+    #[serde(rename = "U")] first_id: i64,
+    #[serde(rename = "u")] last_id: i64,
+    #[serde(rename = "b")] bids: Vec<(String, String)>,
+    #[serde(rename = "a")] asks: Vec<(String, String)>,
+}
+
+pub const DEPTH_URL: &str = "wss://stream.binance.com:9443/ws/btcusdt@depth@100ms";
+const DEPTH_SNAPSHOT_URL: &str = "https://api.binance.com/api/v3/depth?symbol=BTCUSDT&limit=1000";
+
+// whether the update with ids first_id..=last_id applies, false for one the snapshot already has
+fn check_sequence(snapshot_id: i64, applied_id: Option<i64>, first_id: i64, last_id: i64) -> Result<bool, FeedError> {
+    if last_id <= snapshot_id {
+        return Ok(false);
+    }
+    let expected = applied_id.map_or(snapshot_id + 1, |id| id + 1);
+    let in_sequence = match applied_id {
+        Some(_) => first_id == expected,
+        None => first_id <= expected && expected <= last_id,
+    };
+    if !in_sequence {
+        return Err(FeedError::SequenceGap { venue: VENUE, expected, got: first_id });
+    }
+    Ok(true)
+}
+
+// Diffs on the stream, applied over a REST snapshot as binance documents: updates the snapshot
+// already has are skipped, the first one applied must straddle it and each after that must
+// start right where the last one ended. A gap ends the connection, the restart resyncs.
+pub async fn connect_depth(tx: Sender<BookQuote>, clock: SharedClock) -> Result<()> {
+    let (ws_stream, _) = connect_async(DEPTH_URL).await.map_err(|e| FeedError::Connect { venue: VENUE, source: Box::new(e) })?;
+    let (mut write, mut read) = ws_stream.split();
+    metrics::feed_connected(DEPTH_VENUE);
+
+    let snapshot: BinanceDepthSnapshot = async { reqwest::get(DEPTH_SNAPSHOT_URL).await?.error_for_status()?.json().await }.await
+        .map_err(|source| FeedError::Snapshot { venue: VENUE, source })?;
+    let mut book = LocalBook::new(VENUE);
+    for (price, size) in &snapshot.bids {
+        book.set(VENUE, BookSide::Bid, price, size)?;
+    }
+    for (price, size) in &snapshot.asks {
+        book.set(VENUE, BookSide::Ask, price, size)?;
+    }
+    let mut last_id: Option<i64> = None;
+
+    while let Some(msg) = read.next().await {
+        match msg {
+            Ok(Message::Text(text)) => {
+                let Ok(update) = serde_json::from_str::<BinanceDepthUpdate>(&text) else {
+                    continue
+                };
+                match check_sequence(snapshot.last_update_id, last_id, update.first_id, update.last_id) {
+                    Ok(false) => continue,
+                    Ok(true) => {}
+                    Err(e) => {
+                        metrics::BOOK_GAPS.with_label_values(&[VENUE]).inc();
+                        return Err(e.into());
+                    }
+                }
+                last_id = Some(update.last_id);
+
+                for (price, size) in &update.bids {
+                    book.set(VENUE, BookSide::Bid, price, size)?;
+                }
+                for (price, size) in &update.asks {
+                    book.set(VENUE, BookSide::Ask, price, size)?;
+                }
+                metrics::feed_message(DEPTH_VENUE, update.time, &*clock);
+                if let Some(quote) = book.quote()
+                    && tx.send(quote).await.is_err() {
+                    break;
+                }
+            }
+            Ok(Message::Ping(data)) => {
+                write.send(Message::Pong(data)).await
+                    .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
+            }
+            Err(e) => {
+                error::handled("depth feed dropped", &FeedError::WebSocket { venue: VENUE, source: Box::new(e) }.into());
+                break;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_first_update_straddles_the_snapshot() {
+        // snapshot at 100: updates it already has are skipped
+        assert!(!check_sequence(100, None, 90, 100).unwrap());
+        // the first applied has to contain 101
+        assert!(check_sequence(100, None, 95, 105).unwrap());
+        assert!(check_sequence(100, None, 101, 105).unwrap());
+        assert!(matches!(check_sequence(100, None, 102, 105), Err(FeedError::SequenceGap { expected: 101, got: 102, .. })));
+    }
+
+    #[test]
+    fn each_update_starts_where_the_last_ended() {
+        assert!(check_sequence(100, Some(105), 106, 110).unwrap());
+        assert!(matches!(check_sequence(100, Some(105), 108, 110), Err(FeedError::SequenceGap { expected: 106, got: 108, .. })));
+        // straddling only counts for the first
+        assert!(check_sequence(100, Some(105), 104, 110).is_err());
+    }
+}
@@ -2,8 +2,11 @@ use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
 use serde_json::json;
 use futures_util::{StreamExt, SinkExt};
 use tokio::sync::mpsc::Sender;
+use crate::clock::SharedClock;
+use crate::error::{self, FeedError, Result};
+use crate::get_trend::{Aggressor, VenueTrade, parse_field, parse_side};
+use crate::metrics;
 use std::collections::VecDeque;
-use crate::config::VWAP_WINDOW_MS;
 
 #[derive(Debug, serde::Deserialize)]
 struct BitgetTradeMessage {
@@ -15,13 +18,21 @@ struct BitgetTrade {
     #[serde(rename = "price")] price: String,
     #[serde(rename = "size")] size: String,
     #[serde(rename = "ts")] time: String,
+    #[serde(rename = "side")] side: String,
 }
 
-pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>> {
-    let url = "wss://ws.bitget.com/v2/ws/public";
+const VENUE: &str = "bitget";
+pub const URL: &str = "wss://ws.bitget.com/v2/ws/public";
 
-    let (ws_stream, _) = connect_async(url).await?;
+fn parse(trade: &BitgetTrade) -> Result<(f64, f64, u64, Aggressor), FeedError> {
+    Ok((parse_field(VENUE, "price", &trade.price)?, parse_field(VENUE, "size", &trade.size)?, parse_field(VENUE, "ts", &trade.time)? as u64,
+        parse_side(VENUE, "side", &trade.side, "buy", "sell")?))
+}
+
+pub async fn connect(tx: Sender<VenueTrade>, window_ms: u64, clock: SharedClock) -> Result<()> {
+    let (ws_stream, _) = connect_async(URL).await.map_err(|e| FeedError::Connect { venue: VENUE, source: Box::new(e) })?;
     let (mut write, mut read) = ws_stream.split();
+    metrics::feed_connected(VENUE);
     
     let subscribe_msg = json!({
         "op": "subscribe",
@@ -36,7 +47,8 @@ pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>>
     
     write
         .send(Message::Text(subscribe_msg.to_string().into()))
-        .await?;
+        .await
+        .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
 
     let mut trades: VecDeque<(u64, f64, f64)> = VecDeque::new();
 
@@ -54,13 +66,20 @@ pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>>
                 };
                 for trade in &t.data {
 
-                    let (price, quantity, timestamp) = (trade.price.parse::<f64>().unwrap(), trade.size.parse::<f64>().unwrap(), (trade.time.parse::<f64>().unwrap()) as u64);                
+                    let (price, quantity, timestamp, aggressor) = match parse(trade) {
+                        Ok(t) => t,
+                        Err(e) => {
+                            error::handled("skipping trade", &e.into());
+                            continue
+                        }
+                    };
+                    metrics::feed_message(VENUE, timestamp, &*clock);
                     
                     total_volume += quantity;
                     price_vol += price * quantity;
                     trades.push_back((timestamp, price, quantity));
 
-                    let window_start = timestamp.saturating_sub(VWAP_WINDOW_MS);
+                    let window_start = timestamp.saturating_sub(window_ms);
 
                     while let Some((time, price, quantity)) = trades.front() {
                         if *time < window_start {
@@ -73,7 +92,7 @@ pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>>
                     }
                     if total_volume > 0.0 {
                         let vwap = price_vol / total_volume;
-                        if tx.send(vwap).await.is_err() {
+                        if tx.send(VenueTrade { vwap, notional: price * quantity, aggressor }).await.is_err() {
                             break;
                         }
                     }
@@ -81,10 +100,11 @@ pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>>
                 }
             }
             Ok(Message::Ping(data)) => {
-                write.send(Message::Pong(data)).await?;
+                write.send(Message::Pong(data)).await
+                    .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
             }
             Err(e) => {
-                eprintln!("WebSocket error: {e}");
+                error::handled("feed dropped", &FeedError::WebSocket { venue: VENUE, source: Box::new(e) }.into());
                 break;
             }
             _ => {}
@@ -0,0 +1,132 @@
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+use serde_json::json;
+use futures_util::{StreamExt, SinkExt};
+use tokio::sync::mpsc::Sender;
+use tracing::info;
+use crate::clock::SharedClock;
+use crate::error::{self, FeedError, Result};
+use crate::get_trend::{Aggressor, VenueTrade, parse_field, parse_side};
+use crate::metrics;
+use std::collections::VecDeque;
+
+// every message is an event with its data, the trade fields only on "trade"
+#[derive(Debug, serde::Deserialize)]
+struct BitstampMessage {
+    event: String,
+    #[serde(default)] data: serde_json::Value,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct BitstampTrade {
+    price_str: String,
+    amount_str: String,
+    microtimestamp: String,
+    #[serde(rename = "type")] side: u8, // the taker's, 0 buy and 1 sell
+}
+
+const VENUE: &str = "bitstamp";
+pub const URL: &str = "wss://ws.bitstamp.net";
+
+fn parse(trade: &BitstampTrade) -> Result<(f64, f64, u64, Aggressor), FeedError> {
+    Ok((parse_field(VENUE, "price_str", &trade.price_str)?, parse_field(VENUE, "amount_str", &trade.amount_str)?,
+        (parse_field(VENUE, "microtimestamp", &trade.microtimestamp)? / 1000.0) as u64,
+        parse_side(VENUE, "type", &trade.side.to_string(), "0", "1")?))
+}
+
+pub async fn connect(tx: Sender<VenueTrade>, window_ms: u64, clock: SharedClock) -> Result<()> {
+    let (ws_stream, _) = connect_async(URL).await.map_err(|e| FeedError::Connect { venue: VENUE, source: Box::new(e) })?;
+    let (mut write, mut read) = ws_stream.split();
+    metrics::feed_connected(VENUE);
+
+    let subscribe_msg = json!({
+        "event": "bts:subscribe",
+        "data": {
+            "channel": "live_trades_btcusd"
+        }
+    });
+    write
+        .send(Message::Text(subscribe_msg.to_string().into()))
+        .await
+        .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
+
+    let mut trades: VecDeque<(u64, f64, f64)> = VecDeque::new();
+    let mut total_volume = 0.0;
+    let mut price_vol = 0.0;
+
+    while let Some(msg) = read.next().await {
+        match msg {
+            Ok(Message::Text(text)) => {
+                let Ok(message) = serde_json::from_str::<BitstampMessage>(&text) else {
+                    continue
+                };
+                // sent ahead of maintenance, the restart reconnects to another server
+                if message.event == "bts:request_reconnect" {
+                    info!(venue = VENUE, "venue asked for a reconnect");
+                    break;
+                }
+                if message.event != "trade" {
+                    continue;
+                }
+                let parsed = serde_json::from_value::<BitstampTrade>(message.data.clone())
+                    .map_err(|_| FeedError::Parse { venue: VENUE, field: "data", value: message.data.to_string() })
+                    .and_then(|trade| parse(&trade));
+                let (price, quantity, timestamp, aggressor) = match parsed {
+                    Ok(t) => t,
+                    Err(e) => {
+                        error::handled("skipping trade", &e.into());
+                        continue
+                    }
+                };
+                metrics::feed_message(VENUE, timestamp, &*clock);
+
+                total_volume += quantity;
+                price_vol += price * quantity;
+                trades.push_back((timestamp, price, quantity));
+
+                let window_start = timestamp.saturating_sub(window_ms);
+                while let Some((time, price, quantity)) = trades.front() {
+                    if *time < window_start {
+                        price_vol -= price * quantity;
+                        total_volume -= quantity;
+                        trades.pop_front();
+                    } else {
+                        break;
+                    }
+                }
+                if total_volume > 0.0 {
+                    let vwap = price_vol / total_volume;
+                    if tx.send(VenueTrade { vwap, notional: price * quantity, aggressor }).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            Ok(Message::Ping(data)) => {
+                write.send(Message::Pong(data)).await
+                    .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
+            }
+            Err(e) => {
+                error::handled("feed dropped", &FeedError::WebSocket { venue: VENUE, source: Box::new(e) }.into());
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(side: u8) -> BitstampTrade {
+        BitstampTrade { price_str: "60000.5".into(), amount_str: "0.25".into(), microtimestamp: "1700000000123456".into(), side }
+    }
+
+    #[test]
+    fn parses_the_taker_side_and_microseconds() {
+        assert_eq!(parse(&trade(0)).unwrap(), (60_000.5, 0.25, 1_700_000_000_123, Aggressor::Buy));
+        assert_eq!(parse(&trade(1)).unwrap().3, Aggressor::Sell);
+        assert!(matches!(parse(&trade(2)), Err(FeedError::Parse { field: "type", .. })));
+    }
+}
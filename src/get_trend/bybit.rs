@@ -0,0 +1,248 @@
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+use serde_json::json;
+use futures_util::{StreamExt, SinkExt};
+use tokio::sync::mpsc::Sender;
+use tokio::time::{Duration, interval};
+use crate::clock::SharedClock;
+use crate::error::{self, FeedError, Result};
+use crate::get_trend::{Aggressor, VenueTrade, parse_field, parse_side};
+use crate::get_trend::depth::{BookQuote, BookSide, LocalBook};
+use crate::metrics;
+use std::collections::VecDeque;
+
+#[derive(Debug, serde::Deserialize)]
+struct BybitTradeMessage {
+    data: Vec<BybitTrade>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct BybitTrade {
+    #[serde(rename = "p")] price: String,
+    #[serde(rename = "v")] size: String,
+    #[serde(rename = "T")] time: u64,
+    #[serde(rename = "S")] side: String, // the taker's
+}
+
+const VENUE: &str = "bybit";
+const PERP_VENUE: &str = "bybit-perp";
+pub const URL: &str = "wss://stream.bybit.com/v5/public/spot";
+pub const PERP_URL: &str = "wss://stream.bybit.com/v5/public/linear";
+
+// bybit drops connections that go quiet, it asks for a ping every 20s
+const HEARTBEAT: Duration = Duration::from_secs(20);
+
+fn parse(venue: &'static str, trade: &BybitTrade) -> Result<(f64, f64, u64, Aggressor), FeedError> {
+    Ok((parse_field(venue, "p", &trade.price)?, parse_field(venue, "v", &trade.size)?, trade.time,
+        parse_side(venue, "S", &trade.side, "Buy", "Sell")?))
+}
+
+// BTCUSDT spot
+pub async fn connect(tx: Sender<VenueTrade>, window_ms: u64, clock: SharedClock) -> Result<()> {
+    stream(URL, VENUE, tx, window_ms, clock).await
+}
+
+// the USDT margined perpetual, sized in BTC like the spot pair. It trades a few bps off spot with
+// the funding rate, well inside what the outlier filter allows.
+pub async fn connect_perp(tx: Sender<VenueTrade>, window_ms: u64, clock: SharedClock) -> Result<()> {
+    stream(PERP_URL, PERP_VENUE, tx, window_ms, clock).await
+}
+
+// spot and linear share the v5 public trade stream, on their own URLs
+async fn stream(url: &str, venue: &'static str, tx: Sender<VenueTrade>, window_ms: u64, clock: SharedClock) -> Result<()> {
+    let (ws_stream, _) = connect_async(url).await.map_err(|e| FeedError::Connect { venue, source: Box::new(e) })?;
+    let (mut write, mut read) = ws_stream.split();
+    metrics::feed_connected(venue);
+
+    let subscribe_msg = json!({
+        "op": "subscribe",
+        "args": ["publicTrade.BTCUSDT"]
+    });
+    write
+        .send(Message::Text(subscribe_msg.to_string().into()))
+        .await
+        .map_err(|e| FeedError::WebSocket { venue, source: Box::new(e) })?;
+
+    let mut trades: VecDeque<(u64, f64, f64)> = VecDeque::new();
+    let mut total_volume = 0.0;
+    let mut price_vol = 0.0;
+    let mut heartbeat = interval(HEARTBEAT);
+
+    loop {
+        let msg = tokio::select! {
+            msg = read.next() => match msg {
+                Some(msg) => msg,
+                None => break,
+            },
+            _ = heartbeat.tick() => {
+                write.send(Message::Text(json!({ "op": "ping" }).to_string().into())).await
+                    .map_err(|e| FeedError::WebSocket { venue, source: Box::new(e) })?;
+                continue;
+            }
+        };
+        match msg {
+            Ok(Message::Text(text)) => {
+                // subscription acks and pongs don't carry data
+                let Ok(t) = serde_json::from_str::<BybitTradeMessage>(&text) else {
+                    continue
+                };
+                for trade in &t.data {
+                    let (price, quantity, timestamp, aggressor) = match parse(venue, trade) {
+                        Ok(t) => t,
+                        Err(e) => {
+                            error::handled("skipping trade", &e.into());
+                            continue
+                        }
+                    };
+                    metrics::feed_message(venue, timestamp, &*clock);
+
+                    total_volume += quantity;
+                    price_vol += price * quantity;
+                    trades.push_back((timestamp, price, quantity));
+
+                    let window_start = timestamp.saturating_sub(window_ms);
+                    while let Some((time, price, quantity)) = trades.front() {
+                        if *time < window_start {
+                            price_vol -= price * quantity;
+                            total_volume -= quantity;
+                            trades.pop_front();
+                        } else {
+                            break;
+                        }
+                    }
+                    if total_volume > 0.0 {
+                        let vwap = price_vol / total_volume;
+                        if tx.send(VenueTrade { vwap, notional: price * quantity, aggressor }).await.is_err() {
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+            Ok(Message::Ping(data)) => {
+                write.send(Message::Pong(data)).await
+                    .map_err(|e| FeedError::WebSocket { venue, source: Box::new(e) })?;
+            }
+            Err(e) => {
+                error::handled("feed dropped", &FeedError::WebSocket { venue, source: Box::new(e) }.into());
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct BybitDepthMessage {
+    #[serde(rename = "type")] kind: String,
+    ts: u64,
+    data: BybitDepth,
+}
+
+// levels are [price, size], a size of zero removes the level
+#[derive(Debug, serde::Deserialize)]
+struct BybitDepth {
+    #[serde(rename = "b")] bids: Vec<(String, String)>,
+    #[serde(rename = "a")] asks: Vec<(String, String)>,
+    #[serde(rename = "u")] update_id: i64,
+}
+
+const DEPTH_VENUE: &str = "bybit-depth";
+
+// A snapshot then deltas on the spot orderbook.50 channel, each delta's u one on from the last.
+// A snapshot replaces the book whenever it comes, u of 1 on one is the venue restarting.
+fn check_sequence(snapshot: bool, last_id: Option<i64>, update_id: i64) -> Result<(), FeedError> {
+    if snapshot || last_id.is_some_and(|last| update_id == last + 1) {
+        return Ok(());
+    }
+    Err(FeedError::SequenceGap { venue: VENUE, expected: last_id.map_or(-1, |id| id + 1), got: update_id })
+}
+
+// the spot book, the perp's would price the perp. A gap ends the connection so the restart
+// gets a fresh snapshot.
+pub async fn connect_depth(tx: Sender<BookQuote>, clock: SharedClock) -> Result<()> {
+    let (ws_stream, _) = connect_async(URL).await.map_err(|e| FeedError::Connect { venue: VENUE, source: Box::new(e) })?;
+    let (mut write, mut read) = ws_stream.split();
+    metrics::feed_connected(DEPTH_VENUE);
+
+    let subscribe_msg = json!({
+        "op": "subscribe",
+        "args": ["orderbook.50.BTCUSDT"]
+    });
+    write
+        .send(Message::Text(subscribe_msg.to_string().into()))
+        .await
+        .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
+
+    let mut book = LocalBook::new(VENUE);
+    let mut last_id: Option<i64> = None;
+    let mut heartbeat = interval(HEARTBEAT);
+
+    loop {
+        let msg = tokio::select! {
+            msg = read.next() => match msg {
+                Some(msg) => msg,
+                None => break,
+            },
+            _ = heartbeat.tick() => {
+                write.send(Message::Text(json!({ "op": "ping" }).to_string().into())).await
+                    .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
+                continue;
+            }
+        };
+        match msg {
+            Ok(Message::Text(text)) => {
+                let Ok(message) = serde_json::from_str::<BybitDepthMessage>(&text) else {
+                    continue
+                };
+                let snapshot = message.kind == "snapshot";
+                if let Err(e) = check_sequence(snapshot, last_id, message.data.update_id) {
+                    metrics::BOOK_GAPS.with_label_values(&[VENUE]).inc();
+                    return Err(e.into());
+                }
+                last_id = Some(message.data.update_id);
+                if snapshot {
+                    book.clear();
+                }
+
+                for (side, levels) in [(BookSide::Bid, &message.data.bids), (BookSide::Ask, &message.data.asks)] {
+                    for (price, size) in levels {
+                        book.set(VENUE, side, price, size)?;
+                    }
+                }
+                metrics::feed_message(DEPTH_VENUE, message.ts, &*clock);
+                if let Some(quote) = book.quote()
+                    && tx.send(quote).await.is_err() {
+                    break;
+                }
+            }
+            Ok(Message::Ping(data)) => {
+                write.send(Message::Pong(data)).await
+                    .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
+            }
+            Err(e) => {
+                error::handled("depth feed dropped", &FeedError::WebSocket { venue: VENUE, source: Box::new(e) }.into());
+                break;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deltas_must_follow_on() {
+        assert!(check_sequence(true, None, 100).is_ok());
+        assert!(check_sequence(false, Some(100), 101).is_ok());
+        assert!(matches!(check_sequence(false, Some(100), 103), Err(FeedError::SequenceGap { expected: 101, got: 103, .. })));
+        assert!(check_sequence(false, Some(100), 100).is_err());
+        // a delta before any snapshot has nothing to apply to
+        assert!(matches!(check_sequence(false, None, 5), Err(FeedError::SequenceGap { expected: -1, got: 5, .. })));
+        // a snapshot resets, the restart's u of 1 included
+        assert!(check_sequence(true, Some(100), 1).is_ok());
+    }
+}
@@ -2,6 +2,11 @@ use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
 use serde_json::json;
 use futures_util::{StreamExt, SinkExt};
 use tokio::sync::mpsc::Sender;
+use crate::clock::SharedClock;
+use crate::error::{self, FeedError, Result};
+use crate::get_trend::{Aggressor, VenueTrade, parse_field, parse_side};
+use crate::get_trend::depth::{BookQuote, BookSide, LocalBook};
+use crate::metrics;
 use chrono::{DateTime, Utc};
 use std::collections::VecDeque;
 
@@ -9,14 +14,23 @@ use std::collections::VecDeque;
 struct CoinbaseTrade {
     #[serde(rename = "price")] price: String,
     #[serde(rename = "size")] quantity: String,
-    #[serde(rename = "time")] time: DateTime<Utc>
+    #[serde(rename = "time")] time: DateTime<Utc>,
+    #[serde(rename = "side")] side: String,
 }
 
-pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>> {
-    let url = "wss://ws-feed.exchange.coinbase.com";
+const VENUE: &str = "coinbase";
+pub const URL: &str = "wss://ws-feed.exchange.coinbase.com";
 
-    let (ws_stream, _) = connect_async(url).await?;
+// side is the maker's, a resting sell filled means a buyer took it
+fn parse(t: &CoinbaseTrade) -> Result<(f64, f64, u64, Aggressor), FeedError> {
+    Ok((parse_field(VENUE, "price", &t.price)?, parse_field(VENUE, "size", &t.quantity)?, t.time.timestamp_millis() as u64,
+        parse_side(VENUE, "side", &t.side, "sell", "buy")?))
+}
+
+pub async fn connect(tx: Sender<VenueTrade>, window_ms: u64, clock: SharedClock) -> Result<()> {
+    let (ws_stream, _) = connect_async(URL).await.map_err(|e| FeedError::Connect { venue: VENUE, source: Box::new(e) })?;
     let (mut write, mut read) = ws_stream.split();
+    metrics::feed_connected(VENUE);
 
     let subscribe_msg = json!({
         "type": "subscribe",
@@ -26,11 +40,11 @@ pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>>
     
     write
         .send(Message::Text(subscribe_msg.to_string().into()))
-        .await?;
+        .await
+        .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
 
     let mut trades: VecDeque<(u64, f64, f64)> = VecDeque::new();
 
-    let window_ms = 1000 as u64;
     let mut total_volume = 0.0;
     let mut price_vol = 0.0;
 
@@ -43,7 +57,14 @@ pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>>
                         continue
                     }
                 };
-                let (price, quantity, timestamp) = (t.price.parse().unwrap(), t.quantity.parse().unwrap(), (t.time.timestamp_millis()) as u64);
+                let (price, quantity, timestamp, aggressor) = match parse(&t) {
+                    Ok(t) => t,
+                    Err(e) => {
+                        error::handled("skipping trade", &e.into());
+                        continue
+                    }
+                };
+                metrics::feed_message(VENUE, timestamp, &*clock);
                 // println!("{}, {}, {}", price, quantity, timestamp);
 
                 total_volume += quantity;
@@ -63,16 +84,17 @@ pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>>
                 }
                 if total_volume > 0.0 {
                     let vwap = price_vol / total_volume;
-                    if tx.send(vwap).await.is_err() {
+                    if tx.send(VenueTrade { vwap, notional: price * quantity, aggressor }).await.is_err() {
                         break;
                     }
                 }
             }
             Ok(Message::Ping(data)) => {
-                write.send(Message::Pong(data)).await?;
+                write.send(Message::Pong(data)).await
+                    .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
             }
             Err(e) => {
-                eprintln!("WebSocket error: {e}");
+                error::handled("feed dropped", &FeedError::WebSocket { venue: VENUE, source: Box::new(e) }.into());
                 break;
             }
             _ => {}
@@ -80,4 +102,117 @@ pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>>
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[derive(serde::Deserialize)]
+struct CoinbaseDepthMessage {
+    sequence_num: i64,
+    #[serde(default)] channel: String,
+    timestamp: Option<DateTime<Utc>>,
+    #[serde(default)] events: Vec<CoinbaseDepthEvent>,
+}
+
+#[derive(serde::Deserialize)]
+struct CoinbaseDepthEvent {
+    #[serde(default, rename = "type")] kind: String, // absent on the subscriptions message
+    #[serde(default)] updates: Vec<CoinbaseDepthLevel>,
+}
+
+#[derive(serde::Deserialize)]
+struct CoinbaseDepthLevel {
+    side: String,
+    price_level: String,
+    new_quantity: String,
+}
+
+// the exchange feed's level2 needs an API key, advanced trade's is public
+pub const DEPTH_URL: &str = "wss://advanced-trade-ws.coinbase.com";
+const DEPTH_VENUE: &str = "coinbase-depth";
+
+// the first message on a connection can have any number, every one after follows the last
+fn check_sequence(last: Option<i64>, sequence: i64) -> Result<(), FeedError> {
+    match last {
+        Some(last) if sequence != last + 1 => Err(FeedError::SequenceGap { venue: VENUE, expected: last + 1, got: sequence }),
+        _ => Ok(()),
+    }
+}
+
+// A snapshot then level updates on advanced trade's level2 channel. Every message on the
+// connection is numbered, one that doesn't follow the last means updates were lost, which ends
+// the connection so the restart starts from a fresh snapshot.
+pub async fn connect_depth(tx: Sender<BookQuote>, clock: SharedClock) -> Result<()> {
+    let (ws_stream, _) = connect_async(DEPTH_URL).await.map_err(|e| FeedError::Connect { venue: VENUE, source: Box::new(e) })?;
+    let (mut write, mut read) = ws_stream.split();
+    metrics::feed_connected(DEPTH_VENUE);
+
+    let subscribe_msg = json!({
+        "type": "subscribe",
+        "product_ids": ["BTC-USD"],
+        "channel": "level2"
+    });
+    write
+        .send(Message::Text(subscribe_msg.to_string().into()))
+        .await
+        .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
+
+    let mut book = LocalBook::new(VENUE);
+    let mut last_sequence: Option<i64> = None;
+
+    while let Some(msg) = read.next().await {
+        match msg {
+            Ok(Message::Text(text)) => {
+                let Ok(message) = serde_json::from_str::<CoinbaseDepthMessage>(&text) else {
+                    continue
+                };
+                if let Err(e) = check_sequence(last_sequence, message.sequence_num) {
+                    metrics::BOOK_GAPS.with_label_values(&[VENUE]).inc();
+                    return Err(e.into());
+                }
+                last_sequence = Some(message.sequence_num);
+                if message.channel != "l2_data" {
+                    continue;
+                }
+
+                for event in &message.events {
+                    if event.kind == "snapshot" {
+                        book.clear();
+                    }
+                    for level in &event.updates {
+                        let side = if level.side == "bid" { BookSide::Bid } else { BookSide::Ask };
+                        book.set(VENUE, side, &level.price_level, &level.new_quantity)?;
+                    }
+                }
+                if let Some(time) = message.timestamp {
+                    metrics::feed_message(DEPTH_VENUE, time.timestamp_millis() as u64, &*clock);
+                }
+                if let Some(quote) = book.quote()
+                    && tx.send(quote).await.is_err() {
+                    break;
+                }
+            }
+            Ok(Message::Ping(data)) => {
+                write.send(Message::Pong(data)).await
+                    .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
+            }
+            Err(e) => {
+                error::handled("depth feed dropped", &FeedError::WebSocket { venue: VENUE, source: Box::new(e) }.into());
+                break;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_are_numbered_in_order() {
+        assert!(check_sequence(None, 0).is_ok());
+        assert!(check_sequence(Some(0), 1).is_ok());
+        assert!(matches!(check_sequence(Some(1), 3), Err(FeedError::SequenceGap { expected: 2, got: 3, .. })));
+        assert!(check_sequence(Some(1), 1).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+
+use crate::error::FeedError;
+use crate::get_trend::{VENUES, parse_field};
+
+// A venue's order book, kept from its snapshot and the updates after it. Levels are keyed by
+// price in 1e-8 steps so they sort exactly, and keep the venue's own strings for checksums.
+pub struct LocalBook {
+    venue: usize,
+    bids: BTreeMap<u64, Level>,
+    asks: BTreeMap<u64, Level>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Level {
+    pub price: f64,
+    pub size: f64,
+    pub raw_price: String,
+    pub raw_size: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookSide {
+    Bid,
+    Ask,
+}
+
+// the top of a venue's book and what's read from it, sent on every update
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BookQuote {
+    pub venue: usize, // in get_trend::VENUES
+    pub bid: f64,
+    pub ask: f64,
+    pub bid_size: f64,
+    pub ask_size: f64,
+}
+
+impl BookQuote {
+    // the mid leaned toward the thinner side, where the next trade is likelier to move the price
+    pub fn microprice(&self) -> f64 {
+        (self.bid * self.ask_size + self.ask * self.bid_size) / (self.bid_size + self.ask_size)
+    }
+
+    // (bid size - ask size) / both, in -1..=1, positive when buyers are queued deeper
+    pub fn imbalance(&self) -> f64 {
+        (self.bid_size - self.ask_size) / (self.bid_size + self.ask_size)
+    }
+}
+
+impl LocalBook {
+    pub fn new(venue: &str) -> Self {
+        LocalBook {
+            venue: VENUES.iter().position(|v| *v == venue).expect("a known venue"),
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.bids.clear();
+        self.asks.clear();
+    }
+
+    // sets a level, a size of zero removes it
+    pub fn set(&mut self, venue: &'static str, side: BookSide, price: &str, size: &str) -> Result<(), FeedError> {
+        let level = Level {
+            price: parse_field(venue, "price", price)?,
+            size: parse_field(venue, "size", size)?,
+            raw_price: price.to_string(),
+            raw_size: size.to_string(),
+        };
+        let key = (level.price * 1e8).round() as u64;
+        let levels = match side {
+            BookSide::Bid => &mut self.bids,
+            BookSide::Ask => &mut self.asks,
+        };
+        if level.size > 0.0 {
+            levels.insert(key, level);
+        } else {
+            levels.remove(&key);
+        }
+        Ok(())
+    }
+
+    // drops all but the best `depth` levels on each side, for venues that only maintain that many
+    pub fn truncate(&mut self, depth: usize) {
+        while self.bids.len() > depth {
+            self.bids.pop_first();
+        }
+        while self.asks.len() > depth {
+            self.asks.pop_last();
+        }
+    }
+
+    pub fn bids(&self) -> impl Iterator<Item = &Level> {
+        self.bids.values().rev()
+    }
+
+    pub fn asks(&self) -> impl Iterator<Item = &Level> {
+        self.asks.values()
+    }
+
+    // None while a side is empty or the book is crossed, which a missed update can leave it
+    pub fn quote(&self) -> Option<BookQuote> {
+        let (bid, ask) = (self.bids().next()?, self.asks().next()?);
+        (bid.price < ask.price).then_some(BookQuote {
+            venue: self.venue,
+            bid: bid.price,
+            ask: ask.price,
+            bid_size: bid.size,
+            ask_size: ask.size,
+        })
+    }
+}
+
+// CRC-32 (IEEE), which kraken checksums its books with
+pub fn crc32(data: &[u8]) -> u32 {
+    static TABLE: LazyLock<[u32; 256]> = LazyLock::new(|| {
+        std::array::from_fn(|i| (0..8).fold(i as u32, |c, _| if c & 1 == 1 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 }))
+    });
+    !data.iter().fold(!0u32, |crc, b| TABLE[((crc ^ *b as u32) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book(bids: &[(&str, &str)], asks: &[(&str, &str)]) -> LocalBook {
+        let mut book = LocalBook::new("binance");
+        for (price, size) in bids {
+            book.set("binance", BookSide::Bid, price, size).unwrap();
+        }
+        for (price, size) in asks {
+            book.set("binance", BookSide::Ask, price, size).unwrap();
+        }
+        book
+    }
+
+    #[test]
+    fn keeps_the_best_levels_first() {
+        let mut book = book(&[("59999.5", "1"), ("60000.0", "2"), ("59990", "3")], &[("60001.0", "1"), ("60000.5", "0.5")]);
+        let bids: Vec<f64> = book.bids().map(|l| l.price).collect();
+        let asks: Vec<f64> = book.asks().map(|l| l.price).collect();
+        assert_eq!(bids, [60_000.0, 59_999.5, 59_990.0]);
+        assert_eq!(asks, [60_000.5, 60_001.0]);
+        assert_eq!(book.quote(), Some(BookQuote { venue: 0, bid: 60_000.0, ask: 60_000.5, bid_size: 2.0, ask_size: 0.5 }));
+
+        // a size of zero removes the level, a new size replaces it and the venue's strings are kept
+        book.set("binance", BookSide::Bid, "60000.00", "0").unwrap();
+        book.set("binance", BookSide::Ask, "60000.5", "0.75000").unwrap();
+        let quote = book.quote().unwrap();
+        assert_eq!((quote.bid, quote.ask_size), (59_999.5, 0.75));
+        assert_eq!(book.asks().next().unwrap().raw_size, "0.75000");
+
+        book.truncate(1);
+        assert_eq!(book.bids().count(), 1);
+        assert_eq!(book.bids().next().unwrap().price, 59_999.5);
+        assert_eq!(book.asks().next().unwrap().price, 60_000.5);
+
+        book.clear();
+        assert_eq!(book.quote(), None);
+    }
+
+    #[test]
+    fn no_quote_from_a_crossed_or_one_sided_book() {
+        assert_eq!(book(&[("60000", "1")], &[]).quote(), None);
+        assert_eq!(book(&[("60001", "1")], &[("60000", "1")]).quote(), None);
+        // a level that doesn't parse is an error and leaves the book as it was
+        let mut book = book(&[], &[]);
+        assert!(matches!(book.set("binance", BookSide::Bid, "60000", "x"), Err(FeedError::Parse { field: "size", .. })));
+        assert!(book.bids().next().is_none());
+    }
+
+    #[test]
+    fn microprice_leans_to_the_thin_side() {
+        let quote = BookQuote { venue: 0, bid: 100.0, ask: 101.0, bid_size: 3.0, ask_size: 1.0 };
+        assert_eq!(quote.microprice(), 100.75);
+        assert_eq!(quote.imbalance(), 0.5);
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+}
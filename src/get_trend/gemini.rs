@@ -0,0 +1,112 @@
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+use futures_util::{StreamExt, SinkExt};
+use tokio::sync::mpsc::Sender;
+use crate::clock::SharedClock;
+use crate::error::{self, FeedError, Result};
+use crate::get_trend::{Aggressor, VenueTrade, parse_field, parse_side};
+use crate::metrics;
+use std::collections::VecDeque;
+
+// the v1 market data stream, its trades in the events of each update and heartbeats in between
+#[derive(Debug, serde::Deserialize)]
+struct GeminiUpdate {
+    #[serde(rename = "timestampms")] time: u64,
+    #[serde(default)] events: Vec<GeminiEvent>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct GeminiEvent {
+    #[serde(rename = "type")] kind: String,
+    #[serde(default)] price: String,
+    #[serde(default)] amount: String,
+    #[serde(default, rename = "makerSide")] maker_side: String,
+}
+
+const VENUE: &str = "gemini";
+// subscribed by the URL, trades only
+pub const URL: &str = "wss://api.gemini.com/v1/marketdata/BTCUSD?trades=true";
+
+// the side is the maker's, the taker bought from an ask or sold to a bid
+fn parse(event: &GeminiEvent, time: u64) -> Result<(f64, f64, u64, Aggressor), FeedError> {
+    Ok((parse_field(VENUE, "price", &event.price)?, parse_field(VENUE, "amount", &event.amount)?, time,
+        parse_side(VENUE, "makerSide", &event.maker_side, "ask", "bid")?))
+}
+
+pub async fn connect(tx: Sender<VenueTrade>, window_ms: u64, clock: SharedClock) -> Result<()> {
+    let (ws_stream, _) = connect_async(URL).await.map_err(|e| FeedError::Connect { venue: VENUE, source: Box::new(e) })?;
+    let (mut write, mut read) = ws_stream.split();
+    metrics::feed_connected(VENUE);
+
+    let mut trades: VecDeque<(u64, f64, f64)> = VecDeque::new();
+    let mut total_volume = 0.0;
+    let mut price_vol = 0.0;
+
+    while let Some(msg) = read.next().await {
+        match msg {
+            Ok(Message::Text(text)) => {
+                let Ok(update) = serde_json::from_str::<GeminiUpdate>(&text) else {
+                    continue
+                };
+                // auction fills have no taker and print at the auction price, not the market's
+                for event in update.events.iter().filter(|e| e.kind == "trade" && e.maker_side != "auction") {
+                    let (price, quantity, timestamp, aggressor) = match parse(event, update.time) {
+                        Ok(t) => t,
+                        Err(e) => {
+                            error::handled("skipping trade", &e.into());
+                            continue
+                        }
+                    };
+                    metrics::feed_message(VENUE, timestamp, &*clock);
+
+                    total_volume += quantity;
+                    price_vol += price * quantity;
+                    trades.push_back((timestamp, price, quantity));
+
+                    let window_start = timestamp.saturating_sub(window_ms);
+                    while let Some((time, price, quantity)) = trades.front() {
+                        if *time < window_start {
+                            price_vol -= price * quantity;
+                            total_volume -= quantity;
+                            trades.pop_front();
+                        } else {
+                            break;
+                        }
+                    }
+                    if total_volume > 0.0 {
+                        let vwap = price_vol / total_volume;
+                        if tx.send(VenueTrade { vwap, notional: price * quantity, aggressor }).await.is_err() {
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+            Ok(Message::Ping(data)) => {
+                write.send(Message::Pong(data)).await
+                    .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
+            }
+            Err(e) => {
+                error::handled("feed dropped", &FeedError::WebSocket { venue: VENUE, source: Box::new(e) }.into());
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(maker_side: &str) -> GeminiEvent {
+        GeminiEvent { kind: "trade".into(), price: "60000.5".into(), amount: "0.25".into(), maker_side: maker_side.into() }
+    }
+
+    #[test]
+    fn the_taker_is_opposite_the_maker() {
+        assert_eq!(parse(&event("ask"), 1_700_000_000_123).unwrap(), (60_000.5, 0.25, 1_700_000_000_123, Aggressor::Buy));
+        assert_eq!(parse(&event("bid"), 1_700_000_000_123).unwrap().3, Aggressor::Sell);
+        assert!(matches!(parse(&event("auction"), 1_700_000_000_123), Err(FeedError::Parse { field: "makerSide", .. })));
+    }
+}
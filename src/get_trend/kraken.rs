@@ -2,8 +2,12 @@ use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
 use serde_json::json;
 use futures_util::{StreamExt, SinkExt};
 use tokio::sync::mpsc::Sender;
+use crate::clock::SharedClock;
+use crate::error::{self, FeedError, Result};
+use crate::get_trend::{Aggressor, VenueTrade, parse_field, parse_side};
+use crate::get_trend::depth::{BookQuote, BookSide, LocalBook, crc32};
+use crate::metrics;
 use std::collections::VecDeque;
-use crate::config::VWAP_WINDOW_MS;
 
 #[derive(Debug, serde::Deserialize)]
 #[allow(dead_code)]
@@ -25,11 +29,19 @@ struct KrakenTradeMessage(
     String
 );
 
-pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>> {
-    let url = "wss://ws.kraken.com";
+const VENUE: &str = "kraken";
+pub const URL: &str = "wss://ws.kraken.com";
 
-    let (ws_stream, _) = connect_async(url).await?;
+// the side is the taker's, b or s
+fn parse(trade: &KrakenTrade) -> Result<(f64, f64, u64, Aggressor), FeedError> {
+    Ok((parse_field(VENUE, "price", &trade.0)?, parse_field(VENUE, "volume", &trade.1)?, (parse_field(VENUE, "time", &trade.2)? * 1000.0) as u64,
+        parse_side(VENUE, "side", &trade.3, "b", "s")?))
+}
+
+pub async fn connect(tx: Sender<VenueTrade>, window_ms: u64, clock: SharedClock) -> Result<()> {
+    let (ws_stream, _) = connect_async(URL).await.map_err(|e| FeedError::Connect { venue: VENUE, source: Box::new(e) })?;
     let (mut write, mut read) = ws_stream.split();
+    metrics::feed_connected(VENUE);
     
     let subscribe_msg = json!({
         "event": "subscribe",
@@ -41,7 +53,8 @@ pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>>
     
     write
         .send(Message::Text(subscribe_msg.to_string().into()))
-        .await?;
+        .await
+        .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
 
     let mut trades: VecDeque<(u64, f64, f64)> = VecDeque::new();
 
@@ -63,13 +76,20 @@ pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>>
                 // println!(" SUCCESS {:?}", t);
                 for trade in &t.1 {
 
-                    let (price, quantity, timestamp) = (trade.0.parse::<f64>().unwrap(), trade.1.parse::<f64>().unwrap(), (trade.2.parse::<f64>().unwrap() * 1000.0) as u64);                
+                    let (price, quantity, timestamp, aggressor) = match parse(trade) {
+                        Ok(t) => t,
+                        Err(e) => {
+                            error::handled("skipping trade", &e.into());
+                            continue
+                        }
+                    };
+                    metrics::feed_message(VENUE, timestamp, &*clock);
                     
                     total_volume += quantity;
                     price_vol += price * quantity;
                     trades.push_back((timestamp, price, quantity));
 
-                    let window_start = timestamp.saturating_sub(VWAP_WINDOW_MS);
+                    let window_start = timestamp.saturating_sub(window_ms);
 
                     while let Some((time, price, quantity)) = trades.front() {
                         if *time < window_start {
@@ -83,7 +103,7 @@ pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>>
                     if total_volume > 0.0 {
                         // println!("{price_vol}");
                         let vwap = price_vol / total_volume;
-                        if tx.send(vwap).await.is_err() {
+                        if tx.send(VenueTrade { vwap, notional: price * quantity, aggressor }).await.is_err() {
                             break;
                         }
                     }
@@ -95,10 +115,11 @@ pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>>
             }
             
             Ok(Message::Ping(data)) => {
-                write.send(Message::Pong(data)).await?;
+                write.send(Message::Pong(data)).await
+                    .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
             }
             Err(e) => {
-                eprintln!("WebSocket error: {e}");
+                error::handled("feed dropped", &FeedError::WebSocket { venue: VENUE, source: Box::new(e) }.into());
                 break;
             }
             _ => {}
@@ -106,4 +127,132 @@ pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>>
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+const DEPTH_VENUE: &str = "kraken-depth";
+const BOOK_DEPTH: usize = 10;
+
+// The top of the book over the book channel: [channel, {"as", "bs"}, "book-10", pair] to start
+// and [channel, {"a"}, {"b", "c"}, "book-10", pair] with either side after that. Kraken has no
+// sequence numbers, it checksums the top ten levels instead, and a mismatch means the local
+// book has drifted, which ends the connection so the restart gets a fresh snapshot.
+pub async fn connect_depth(tx: Sender<BookQuote>, clock: SharedClock) -> Result<()> {
+    let (ws_stream, _) = connect_async(URL).await.map_err(|e| FeedError::Connect { venue: VENUE, source: Box::new(e) })?;
+    let (mut write, mut read) = ws_stream.split();
+    metrics::feed_connected(DEPTH_VENUE);
+
+    let subscribe_msg = json!({
+        "event": "subscribe",
+        "pair": ["XBT/USD"],
+        "subscription": {
+            "name": "book",
+            "depth": BOOK_DEPTH
+        }
+    });
+    write
+        .send(Message::Text(subscribe_msg.to_string().into()))
+        .await
+        .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
+
+    let mut book = LocalBook::new(VENUE);
+
+    while let Some(msg) = read.next().await {
+        match msg {
+            Ok(Message::Text(text)) => {
+                let Ok(serde_json::Value::Array(parts)) = serde_json::from_str::<serde_json::Value>(&text) else {
+                    continue
+                };
+                let mut checksum = None;
+                let mut latest_ms = 0;
+                for part in parts.iter().filter_map(|p| p.as_object()) {
+                    if part.contains_key("as") || part.contains_key("bs") {
+                        book.clear();
+                    }
+                    for (key, side) in [("as", BookSide::Ask), ("bs", BookSide::Bid), ("a", BookSide::Ask), ("b", BookSide::Bid)] {
+                        for level in part.get(key).and_then(|l| l.as_array()).into_iter().flatten() {
+                            let field = |i: usize| level.get(i).and_then(|v| v.as_str()).unwrap_or_default();
+                            book.set(VENUE, side, field(0), field(1))?;
+                            latest_ms = latest_ms.max((parse_field(VENUE, "time", field(2))? * 1000.0) as u64);
+                        }
+                    }
+                    if let Some(c) = part.get("c").and_then(|c| c.as_str()) {
+                        checksum = Some(c.parse::<u32>().map_err(|_| FeedError::Parse { venue: VENUE, field: "c", value: c.to_string() })?);
+                    }
+                }
+                book.truncate(BOOK_DEPTH);
+
+                if let Some(expected) = checksum {
+                    let got = book_checksum(&book);
+                    if got != expected {
+                        metrics::BOOK_GAPS.with_label_values(&[VENUE]).inc();
+                        return Err(FeedError::Checksum { venue: VENUE, expected, got }.into());
+                    }
+                }
+                if latest_ms > 0 {
+                    metrics::feed_message(DEPTH_VENUE, latest_ms, &*clock);
+                }
+                if let Some(quote) = book.quote()
+                    && tx.send(quote).await.is_err() {
+                    break;
+                }
+            }
+            Ok(Message::Ping(data)) => {
+                write.send(Message::Pong(data)).await
+                    .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
+            }
+            Err(e) => {
+                error::handled("depth feed dropped", &FeedError::WebSocket { venue: VENUE, source: Box::new(e) }.into());
+                break;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// CRC-32 of the top ten asks then bids, each price and volume without its point or leading zeros
+fn book_checksum(book: &LocalBook) -> u32 {
+    let digits = |s: &str| s.replace('.', "").trim_start_matches('0').to_string();
+    let mut payload = String::new();
+    for level in book.asks().take(BOOK_DEPTH).chain(book.bids().take(BOOK_DEPTH)) {
+        payload.push_str(&digits(&level.raw_price));
+        payload.push_str(&digits(&level.raw_size));
+    }
+    crc32(payload.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the example in kraken's book checksum guide
+    fn documented_book() -> LocalBook {
+        let mut book = LocalBook::new(VENUE);
+        let asks = ["0.05005", "0.05010", "0.05015", "0.05020", "0.05025", "0.05030", "0.05035", "0.05040", "0.05045", "0.05050"];
+        let bids = ["0.05000", "0.04995", "0.04990", "0.04980", "0.04975", "0.04970", "0.04965", "0.04960", "0.04955", "0.04950"];
+        for price in asks {
+            book.set(VENUE, BookSide::Ask, price, "0.00000500").unwrap();
+        }
+        for price in bids {
+            book.set(VENUE, BookSide::Bid, price, "0.00000500").unwrap();
+        }
+        book
+    }
+
+    #[test]
+    fn checksums_the_documented_example() {
+        assert_eq!(book_checksum(&documented_book()), 974_947_235);
+    }
+
+    #[test]
+    fn a_drifted_book_fails_the_checksum() {
+        let mut book = documented_book();
+        book.set(VENUE, BookSide::Bid, "0.04950", "0.00000600").unwrap();
+        assert_ne!(book_checksum(&book), 974_947_235);
+
+        // levels past the top ten don't count, once truncated
+        let mut book = documented_book();
+        book.set(VENUE, BookSide::Bid, "0.04900", "1.00000000").unwrap();
+        book.truncate(BOOK_DEPTH);
+        assert_eq!(book_checksum(&book), 974_947_235);
+    }
+}
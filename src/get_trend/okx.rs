@@ -2,8 +2,12 @@ use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
 use serde_json::json;
 use futures_util::{StreamExt, SinkExt};
 use tokio::sync::mpsc::Sender;
+use crate::clock::SharedClock;
+use crate::error::{self, FeedError, Result};
+use crate::get_trend::{Aggressor, VenueTrade, parse_field, parse_side};
+use crate::get_trend::depth::{BookQuote, BookSide, LocalBook};
+use crate::metrics;
 use std::collections::VecDeque;
-use crate::config::VWAP_WINDOW_MS;
 
 
 #[derive(Debug, serde::Deserialize)]
@@ -16,13 +20,21 @@ struct OkxTrade {
     #[serde(rename = "px")] price: String,
     #[serde(rename = "sz")] size: String,
     #[serde(rename = "ts")] time: String,
+    #[serde(rename = "side")] side: String,
 }
 
-pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>> {
-    let url = "wss://ws.okx.com:8443/ws/v5/public";
+const VENUE: &str = "okx";
+pub const URL: &str = "wss://ws.okx.com:8443/ws/v5/public";
 
-    let (ws_stream, _) = connect_async(url).await?;
+fn parse(trade: &OkxTrade) -> Result<(f64, f64, u64, Aggressor), FeedError> {
+    Ok((parse_field(VENUE, "px", &trade.price)?, parse_field(VENUE, "sz", &trade.size)?, parse_field(VENUE, "ts", &trade.time)? as u64,
+        parse_side(VENUE, "side", &trade.side, "buy", "sell")?))
+}
+
+pub async fn connect(tx: Sender<VenueTrade>, window_ms: u64, clock: SharedClock) -> Result<()> {
+    let (ws_stream, _) = connect_async(URL).await.map_err(|e| FeedError::Connect { venue: VENUE, source: Box::new(e) })?;
     let (mut write, mut read) = ws_stream.split();
+    metrics::feed_connected(VENUE);
     
     let subscribe_msg = json!({
         "op": "subscribe",
@@ -36,7 +48,8 @@ pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>>
     
     write
         .send(Message::Text(subscribe_msg.to_string().into()))
-        .await?;
+        .await
+        .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
 
     let mut trades: VecDeque<(u64, f64, f64)> = VecDeque::new();
 
@@ -54,13 +67,20 @@ pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>>
                 };
                 for trade in &t.data {
 
-                    let (price, quantity, timestamp) = (trade.price.parse::<f64>().unwrap(), trade.size.parse::<f64>().unwrap(), (trade.time.parse::<f64>().unwrap()) as u64);                
+                    let (price, quantity, timestamp, aggressor) = match parse(trade) {
+                        Ok(t) => t,
+                        Err(e) => {
+                            error::handled("skipping trade", &e.into());
+                            continue
+                        }
+                    };
+                    metrics::feed_message(VENUE, timestamp, &*clock);
                     
                     total_volume += quantity;
                     price_vol += price * quantity;
                     trades.push_back((timestamp, price, quantity));
 
-                    let window_start = timestamp.saturating_sub(VWAP_WINDOW_MS);
+                    let window_start = timestamp.saturating_sub(window_ms);
 
                     while let Some((time, price, quantity)) = trades.front() {
                         if *time < window_start {
@@ -73,7 +93,7 @@ pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>>
                     }
                     if total_volume > 0.0 {
                         let vwap = price_vol / total_volume;
-                        if tx.send(vwap).await.is_err() {
+                        if tx.send(VenueTrade { vwap, notional: price * quantity, aggressor }).await.is_err() {
                             break;
                         }
                     }
@@ -81,10 +101,11 @@ pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>>
                 }
             }
             Ok(Message::Ping(data)) => {
-                write.send(Message::Pong(data)).await?;
+                write.send(Message::Pong(data)).await
+                    .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
             }
             Err(e) => {
-                eprintln!("WebSocket error: {e}");
+                error::handled("feed dropped", &FeedError::WebSocket { venue: VENUE, source: Box::new(e) }.into());
                 break;
             }
             _ => {}
@@ -92,4 +113,109 @@ pub async fn connect(tx: Sender<f64>) -> Result<(), Box<dyn std::error::Error>>
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+#[derive(Debug, serde::Deserialize)]
+struct OkxDepthMessage {
+    action: String,
+    data: Vec<OkxDepth>,
+}
+
+// levels are [price, size, "0", orders]
+#[derive(Debug, serde::Deserialize)]
+struct OkxDepth {
+    asks: Vec<Vec<String>>,
+    bids: Vec<Vec<String>>,
+    ts: String,
+    #[serde(rename = "seqId")] seq_id: i64,
+    #[serde(rename = "prevSeqId")] prev_seq_id: i64,
+}
+
+const DEPTH_VENUE: &str = "okx-depth";
+
+// an update has to name the last seqId, a snapshot starts over
+fn check_sequence(snapshot: bool, last_seq: Option<i64>, prev_seq_id: i64) -> Result<(), FeedError> {
+    if snapshot || last_seq == Some(prev_seq_id) {
+        return Ok(());
+    }
+    Err(FeedError::SequenceGap { venue: VENUE, expected: last_seq.unwrap_or(-1), got: prev_seq_id })
+}
+
+// The books channel: a snapshot, then updates that each name the seqId of the one before. One
+// naming anything else means updates were lost, which ends the connection so the restart gets
+// a fresh snapshot.
+pub async fn connect_depth(tx: Sender<BookQuote>, clock: SharedClock) -> Result<()> {
+    let (ws_stream, _) = connect_async(URL).await.map_err(|e| FeedError::Connect { venue: VENUE, source: Box::new(e) })?;
+    let (mut write, mut read) = ws_stream.split();
+    metrics::feed_connected(DEPTH_VENUE);
+
+    let subscribe_msg = json!({
+        "op": "subscribe",
+        "args": [{ "channel": "books", "instId": "BTC-USDT" }]
+    });
+    write
+        .send(Message::Text(subscribe_msg.to_string().into()))
+        .await
+        .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
+
+    let mut book = LocalBook::new(VENUE);
+    let mut last_seq: Option<i64> = None;
+
+    while let Some(msg) = read.next().await {
+        match msg {
+            Ok(Message::Text(text)) => {
+                let Ok(message) = serde_json::from_str::<OkxDepthMessage>(&text) else {
+                    continue
+                };
+                for depth in &message.data {
+                    let snapshot = message.action == "snapshot";
+                    if let Err(e) = check_sequence(snapshot, last_seq, depth.prev_seq_id) {
+                        metrics::BOOK_GAPS.with_label_values(&[VENUE]).inc();
+                        return Err(e.into());
+                    }
+                    if snapshot {
+                        book.clear();
+                    }
+                    last_seq = Some(depth.seq_id);
+
+                    for (side, levels) in [(BookSide::Bid, &depth.bids), (BookSide::Ask, &depth.asks)] {
+                        for level in levels {
+                            let [price, size, ..] = level.as_slice() else {
+                                return Err(FeedError::Parse { venue: VENUE, field: "level", value: format!("{level:?}") }.into());
+                            };
+                            book.set(VENUE, side, price, size)?;
+                        }
+                    }
+                    metrics::feed_message(DEPTH_VENUE, parse_field(VENUE, "ts", &depth.ts)? as u64, &*clock);
+                }
+                if let Some(quote) = book.quote()
+                    && tx.send(quote).await.is_err() {
+                    break;
+                }
+            }
+            Ok(Message::Ping(data)) => {
+                write.send(Message::Pong(data)).await
+                    .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
+            }
+            Err(e) => {
+                error::handled("depth feed dropped", &FeedError::WebSocket { venue: VENUE, source: Box::new(e) }.into());
+                break;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updates_name_the_last_seq_id() {
+        assert!(check_sequence(true, None, -1).is_ok());
+        assert!(check_sequence(false, Some(10), 10).is_ok());
+        assert!(matches!(check_sequence(false, Some(10), 12), Err(FeedError::SequenceGap { expected: 10, got: 12, .. })));
+        assert!(matches!(check_sequence(false, None, 3), Err(FeedError::SequenceGap { expected: -1, got: 3, .. })));
+        assert!(check_sequence(true, Some(10), 50).is_ok());
+    }
+}
@@ -0,0 +1,102 @@
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+use serde_json::json;
+use futures_util::{StreamExt, SinkExt};
+use tokio::sync::mpsc::Sender;
+use crate::clock::SharedClock;
+use crate::error::{self, FeedError, Result};
+use crate::get_trend::{Aggressor, VenueTrade, parse_field, parse_side};
+use crate::metrics;
+use std::collections::VecDeque;
+
+// Kraken's USDT/USD trades, for the basis between the USDT and USD quoted venues. Same protocol
+// as the BTC feed: [channel, [[price, volume, time, ...]], "trade", pair].
+#[derive(Debug, serde::Deserialize)]
+#[allow(dead_code)]
+struct KrakenTrade(String, String, String, String, String, String);
+
+#[derive(Debug, serde::Deserialize)]
+#[allow(dead_code)]
+struct KrakenTradeMessage(i64, Vec<KrakenTrade>, String, String);
+
+const VENUE: &str = "kraken-usdt";
+pub const URL: &str = "wss://ws.kraken.com";
+
+// the side is the taker's, b or s
+fn parse(trade: &KrakenTrade) -> Result<(f64, f64, u64, Aggressor), FeedError> {
+    Ok((parse_field(VENUE, "price", &trade.0)?, parse_field(VENUE, "volume", &trade.1)?, (parse_field(VENUE, "time", &trade.2)? * 1000.0) as u64,
+        parse_side(VENUE, "side", &trade.3, "b", "s")?))
+}
+
+pub async fn connect(tx: Sender<VenueTrade>, window_ms: u64, clock: SharedClock) -> Result<()> {
+    let (ws_stream, _) = connect_async(URL).await.map_err(|e| FeedError::Connect { venue: VENUE, source: Box::new(e) })?;
+    let (mut write, mut read) = ws_stream.split();
+    metrics::feed_connected(VENUE);
+
+    let subscribe_msg = json!({
+        "event": "subscribe",
+        "pair": ["USDT/USD"],
+        "subscription": {
+            "name": "trade"
+        }
+    });
+    write
+        .send(Message::Text(subscribe_msg.to_string().into()))
+        .await
+        .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
+
+    let mut trades: VecDeque<(u64, f64, f64)> = VecDeque::new();
+    let mut total_volume = 0.0;
+    let mut price_vol = 0.0;
+
+    while let Some(msg) = read.next().await {
+        match msg {
+            Ok(Message::Text(text)) => {
+                let Ok(t) = serde_json::from_str::<KrakenTradeMessage>(&text) else {
+                    continue
+                };
+                for trade in &t.1 {
+                    let (price, quantity, timestamp, aggressor) = match parse(trade) {
+                        Ok(t) => t,
+                        Err(e) => {
+                            error::handled("skipping trade", &e.into());
+                            continue
+                        }
+                    };
+                    metrics::feed_message(VENUE, timestamp, &*clock);
+
+                    total_volume += quantity;
+                    price_vol += price * quantity;
+                    trades.push_back((timestamp, price, quantity));
+
+                    let window_start = timestamp.saturating_sub(window_ms);
+                    while let Some((time, price, quantity)) = trades.front() {
+                        if *time < window_start {
+                            price_vol -= price * quantity;
+                            total_volume -= quantity;
+                            trades.pop_front();
+                        } else {
+                            break;
+                        }
+                    }
+                    if total_volume > 0.0 {
+                        let vwap = price_vol / total_volume;
+                        if tx.send(VenueTrade { vwap, notional: price * quantity, aggressor }).await.is_err() {
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+            Ok(Message::Ping(data)) => {
+                write.send(Message::Pong(data)).await
+                    .map_err(|e| FeedError::WebSocket { venue: VENUE, source: Box::new(e) })?;
+            }
+            Err(e) => {
+                error::handled("feed dropped", &FeedError::WebSocket { venue: VENUE, source: Box::new(e) }.into());
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,206 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::clock::Clock;
+use crate::config::Config;
+use crate::get_price_info::BookTop;
+use crate::get_trend::{VENUES, VenueSnapshot};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WsStatus {
+    Connected,
+    Closed,
+}
+
+#[derive(Debug, Clone)]
+pub struct MarketHealth {
+    pub live_venues: usize, // ticked within max_venue_age_ms and not excluded as an outlier
+    pub venue_ages: [Option<Duration>; VENUES.len()], // None if the venue has never ticked
+    pub excluded: [bool; VENUES.len()],
+    pub book_age: Option<Duration>,
+    pub book_ws: WsStatus,
+    pub clock_skew_ms: Option<i64>, // local time minus the book's server timestamp, at receipt
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HealthRejection {
+    TooFewVenues { live: usize, required: usize },
+    NoBook,
+    StaleBook { age_ms: u128, max_ms: u64 },
+    BookWsClosed,
+    ClockSkew { skew_ms: i64, max_ms: i64 },
+}
+
+impl fmt::Display for HealthRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HealthRejection::TooFewVenues { live, required } => {
+                write!(f, "only {live} live venues, need {required}")
+            }
+            HealthRejection::NoBook => write!(f, "no order book received yet"),
+            HealthRejection::StaleBook { age_ms, max_ms } => {
+                write!(f, "order book is {age_ms}ms old, max {max_ms}ms")
+            }
+            HealthRejection::BookWsClosed => write!(f, "order book websocket closed"),
+            HealthRejection::ClockSkew { skew_ms, max_ms } => {
+                write!(f, "clock skew {skew_ms}ms vs polymarket, max {max_ms}ms")
+            }
+        }
+    }
+}
+
+impl MarketHealth {
+    pub fn snapshot(venues: &VenueSnapshot, book: &BookTop, book_ws: WsStatus, clock: &dyn Clock, config: &Config) -> Self {
+        let now = clock.instant();
+        let max_venue_age = Duration::from_millis(config.feeds.max_venue_age_ms);
+
+        let venue_ages = venues.updated.map(|t| t.map(|t| now.duration_since(t)));
+        let live_venues = venue_ages.iter().zip(venues.excluded)
+            .filter(|(age, excluded)| !excluded && age.is_some_and(|age| age < max_venue_age))
+            .count();
+
+        let book_age = book.received_at.map(|t| now.duration_since(t));
+
+        // the skew is measured against the moment the book arrived, not now
+        let clock_skew_ms = book.received_at.map(|t| {
+            let local_ms = clock.now_ms() as i64
+                - now.duration_since(t).as_millis() as i64;
+            local_ms - book.server_ts_ms
+        });
+
+        MarketHealth { live_venues, venue_ages, excluded: venues.excluded, book_age, book_ws, clock_skew_ms }
+    }
+
+    pub fn check(&self, config: &Config) -> Result<(), HealthRejection> {
+        let required = config.feeds.min_live_venues;
+        if self.live_venues < required {
+            return Err(HealthRejection::TooFewVenues { live: self.live_venues, required });
+        }
+        if self.book_ws == WsStatus::Closed {
+            return Err(HealthRejection::BookWsClosed);
+        }
+        let Some(book_age) = self.book_age else {
+            return Err(HealthRejection::NoBook);
+        };
+        let max_book_age_ms = config.health.max_book_age_ms;
+        if book_age > Duration::from_millis(max_book_age_ms) {
+            return Err(HealthRejection::StaleBook { age_ms: book_age.as_millis(), max_ms: max_book_age_ms });
+        }
+        let max_skew_ms = config.health.max_clock_skew_ms;
+        if let Some(skew_ms) = self.clock_skew_ms
+            && skew_ms.abs() > max_skew_ms {
+            return Err(HealthRejection::ClockSkew { skew_ms, max_ms: max_skew_ms });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MarketHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "venues live: {}/{} [", self.live_venues, VENUES.len())?;
+        for ((name, age), excluded) in VENUES.iter().zip(self.venue_ages.iter()).zip(self.excluded) {
+            match age {
+                Some(age) => write!(f, " {name}:{}ms", age.as_millis())?,
+                None => write!(f, " {name}:-")?,
+            }
+            if excluded {
+                write!(f, "(excluded)")?;
+            }
+        }
+        write!(f, " ], book ws: {:?}", self.book_ws)?;
+        if let Some(age) = self.book_age {
+            write!(f, ", book age: {}ms", age.as_millis())?;
+        }
+        if let Some(skew) = self.clock_skew_ms {
+            write!(f, ", clock skew: {skew}ms")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SimulatedClock;
+
+    const START_MS: u64 = 1_700_000_000_000;
+
+    // just enough venues and the book ticking at START_MS, the book stamped by a server in sync
+    fn ticked(clock: &SimulatedClock) -> (VenueSnapshot, BookTop) {
+        let mut venues = VenueSnapshot::default();
+        for updated in venues.updated.iter_mut().take(Config::default().feeds.min_live_venues) {
+            *updated = Some(clock.instant());
+        }
+        let book = BookTop { bid: Some(0.5), ask: Some(0.52), received_at: Some(clock.instant()), server_ts_ms: START_MS as i64 };
+        (venues, book)
+    }
+
+    #[test]
+    fn venues_go_stale_after_max_venue_age() {
+        let (config, clock) = (Config::default(), SimulatedClock::new(START_MS));
+        let (venues, book) = ticked(&clock);
+
+        let health = MarketHealth::snapshot(&venues, &book, WsStatus::Connected, &clock, &config);
+        let required = config.feeds.min_live_venues;
+        assert_eq!(health.live_venues, required);
+        assert_eq!(health.check(&config), Ok(()));
+
+        clock.set(START_MS + config.feeds.max_venue_age_ms);
+        let health = MarketHealth::snapshot(&venues, &book, WsStatus::Connected, &clock, &config);
+        assert_eq!(health.live_venues, 0);
+        assert_eq!(health.venue_ages[0], Some(Duration::from_millis(config.feeds.max_venue_age_ms)));
+        assert_eq!(health.check(&config), Err(HealthRejection::TooFewVenues { live: 0, required }));
+    }
+
+    #[test]
+    fn excluded_venues_are_not_live() {
+        let (config, clock) = (Config::default(), SimulatedClock::new(START_MS));
+        let (mut venues, book) = ticked(&clock);
+        venues.excluded[0] = true;
+
+        let health = MarketHealth::snapshot(&venues, &book, WsStatus::Connected, &clock, &config);
+        let required = config.feeds.min_live_venues;
+        assert_eq!(health.live_venues, required - 1);
+        assert_eq!(health.check(&config), Err(HealthRejection::TooFewVenues { live: required - 1, required }));
+    }
+
+    #[test]
+    fn book_goes_stale_after_max_book_age() {
+        let (config, clock) = (Config::default(), SimulatedClock::new(START_MS));
+        let (mut venues, book) = ticked(&clock);
+
+        // the venues keep ticking, only the book stops
+        clock.set(START_MS + config.health.max_book_age_ms + 1);
+        for updated in venues.updated.iter_mut().take(Config::default().feeds.min_live_venues) {
+            *updated = Some(clock.instant());
+        }
+        let health = MarketHealth::snapshot(&venues, &book, WsStatus::Connected, &clock, &config);
+        assert_eq!(health.check(&config), Err(HealthRejection::StaleBook {
+            age_ms: config.health.max_book_age_ms as u128 + 1,
+            max_ms: config.health.max_book_age_ms,
+        }));
+    }
+
+    #[test]
+    fn skew_is_measured_when_the_book_arrived() {
+        let (config, clock) = (Config::default(), SimulatedClock::new(START_MS));
+        let (venues, mut book) = ticked(&clock);
+        book.server_ts_ms -= 3000;
+
+        // time passing since doesn't add to it
+        clock.set(START_MS + 1000);
+        let health = MarketHealth::snapshot(&venues, &book, WsStatus::Connected, &clock, &config);
+        assert_eq!(health.clock_skew_ms, Some(3000));
+        assert_eq!(health.check(&config), Err(HealthRejection::ClockSkew { skew_ms: 3000, max_ms: 2000 }));
+    }
+
+    #[test]
+    fn no_book_until_one_arrives() {
+        let (config, clock) = (Config::default(), SimulatedClock::new(START_MS));
+        let (venues, _) = ticked(&clock);
+        let health = MarketHealth::snapshot(&venues, &BookTop::default(), WsStatus::Connected, &clock, &config);
+        assert_eq!(health.check(&config), Err(HealthRejection::NoBook));
+        let health = MarketHealth::snapshot(&venues, &BookTop::default(), WsStatus::Closed, &clock, &config);
+        assert_eq!(health.check(&config), Err(HealthRejection::BookWsClosed));
+    }
+}
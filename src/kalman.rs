@@ -0,0 +1,180 @@
+use crate::config::KalmanConfig;
+
+// Fuses the venues' VWAPs into one price with a constant-velocity Kalman filter. The state is
+// the latent price ($) and its velocity ($/s). Each venue reads the price plus a bias of its own
+// (its quote currency, its VWAP lag) and noise, and both are learned online from the venue's
+// innovations, so a noisy or offset venue pulls on the estimate less as the filter gets to know
+// it. The biases are pinned to a mean of zero under the fusion's current venue weights, they're
+// only known relative to each other.
+pub struct KalmanFuser {
+    velocity_noise: f64, // $/s per √s
+    learning_rate: f64,
+    state: Option<State>,
+    venues: Vec<VenueNoise>,
+}
+
+struct State {
+    time_ms: u64,
+    x: [f64; 2], // price, velocity
+    p: [[f64; 2]; 2], // its covariance
+}
+
+#[derive(Clone, Copy)]
+struct VenueNoise {
+    bias: f64, // $
+    variance: f64, // $²
+}
+
+// the filter's idea of a venue, before it has learned better
+const VELOCITY_VARIANCE_0: f64 = 100.0; // ($/s)², it starts with no idea which way the price is going
+const MIN_VARIANCE: f64 = 1e-4; // $², a venue never counts as exact
+
+impl KalmanFuser {
+    pub fn new(config: &KalmanConfig, venues: usize) -> Self {
+        KalmanFuser {
+            velocity_noise: config.velocity_noise,
+            learning_rate: config.learning_rate,
+            state: None,
+            venues: vec![VenueNoise { bias: 0.0, variance: config.venue_noise.powi(2) }; venues],
+        }
+    }
+
+    // One venue's VWAP at time_ms. price_variance is the latent price's own variance per second
+    // ($²/s), from the realised volatility, so the filter follows a busy market more closely.
+    // weights are the venues' weights in the fusion as they stand, the biases' anchor moves with them.
+    pub fn observe(&mut self, venue: usize, time_ms: u64, price: f64, price_variance: f64, weights: &[f64]) {
+        let noise = self.venues[venue];
+        let Some(state) = self.state.as_mut() else {
+            self.state = Some(State {
+                time_ms,
+                x: [price - noise.bias, 0.0],
+                p: [[noise.variance, 0.0], [0.0, VELOCITY_VARIANCE_0]],
+            });
+            return;
+        };
+        state.predict(time_ms, price_variance, self.velocity_noise.powi(2));
+
+        let innovation = price - noise.bias - state.x[0];
+        let s = state.p[0][0] + noise.variance;
+        let gain = [state.p[0][0] / s, state.p[1][0] / s];
+        state.x[0] += gain[0] * innovation;
+        state.x[1] += gain[1] * innovation;
+        let p = state.p;
+        state.p = [
+            [(1.0 - gain[0]) * p[0][0], (1.0 - gain[0]) * p[0][1]],
+            [p[1][0] - gain[1] * p[0][0], p[1][1] - gain[1] * p[0][1]],
+        ];
+
+        // the innovation's mean is the venue's bias and its spread, less the filter's own
+        // uncertainty, the venue's noise
+        let rate = self.learning_rate;
+        let venue_state = &mut self.venues[venue];
+        venue_state.bias += rate * innovation;
+        venue_state.variance += rate * ((innovation.powi(2) - p[0][0]).max(MIN_VARIANCE) - venue_state.variance);
+
+        let weight_sum: f64 = weights.iter().sum();
+        if weight_sum <= 0.0 {
+            return;
+        }
+        let mean_bias = self.venues.iter().zip(weights).map(|(v, w)| v.bias * w).sum::<f64>() / weight_sum;
+        for venue_state in &mut self.venues {
+            venue_state.bias -= mean_bias;
+        }
+    }
+
+    pub fn price(&self) -> Option<f64> {
+        self.state.as_ref().map(|s| s.x[0])
+    }
+
+    pub fn velocity(&self) -> f64 {
+        self.state.as_ref().map_or(0.0, |s| s.x[1])
+    }
+
+    // the velocity's standard error, $/s
+    pub fn velocity_se(&self) -> f64 {
+        self.state.as_ref().map_or(0.0, |s| s.p[1][1].max(0.0).sqrt())
+    }
+
+    pub fn bias(&self, venue: usize) -> f64 {
+        self.venues[venue].bias
+    }
+}
+
+impl State {
+    // moves the state on to time_ms, the price drifting at the velocity while both diffuse
+    fn predict(&mut self, time_ms: u64, price_variance: f64, velocity_variance: f64) {
+        let dt = time_ms.saturating_sub(self.time_ms) as f64 / 1000.0;
+        self.time_ms = self.time_ms.max(time_ms);
+        if dt <= 0.0 {
+            return;
+        }
+        let p = self.p;
+        self.x[0] += self.x[1] * dt;
+        // F P Fᵀ + Q with F = [[1, dt], [0, 1]], Q from a random walk in both plus the
+        // integrated velocity noise
+        let q = velocity_variance;
+        self.p = [
+            [
+                p[0][0] + dt * (p[0][1] + p[1][0]) + dt * dt * p[1][1] + price_variance * dt + q * dt.powi(3) / 3.0,
+                p[0][1] + dt * p[1][1] + q * dt * dt / 2.0,
+            ],
+            [
+                p[1][0] + dt * p[1][1] + q * dt * dt / 2.0,
+                p[1][1] + q * dt,
+            ],
+        ];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Rng;
+
+    // two venues ticking in turn every 250ms on a price climbing at velocity $/s, the second
+    // reading offset dollars high, both with $1 of noise
+    fn run(kalman: &mut KalmanFuser, velocity: f64, offset: f64, weights: &[f64]) -> f64 {
+        let mut rng = Rng::new(7);
+        let mut truth = 0.0;
+        for step in 0..4_000u64 {
+            let time_ms = 1_700_000_000_000 + step * 250;
+            truth = 60_000.0 + velocity * (step as f64) * 0.25;
+            let venue = (step % 2) as usize;
+            let read = truth + if venue == 1 { offset } else { 0.0 } + rng.normal();
+            kalman.observe(venue, time_ms, read, 0.01, weights);
+        }
+        truth
+    }
+
+    #[test]
+    fn converges_to_a_constant_velocity_path() {
+        let mut kalman = KalmanFuser::new(&KalmanConfig::default(), 2);
+        let truth = run(&mut kalman, 3.0, 0.0, &[1.0, 1.0]);
+
+        assert!((kalman.velocity() - 3.0).abs() < 0.3, "velocity {}", kalman.velocity());
+        assert!((kalman.price().unwrap() - truth).abs() < 2.0, "price {} against {truth}", kalman.price().unwrap());
+        assert!(kalman.velocity_se() > 0.0 && kalman.velocity_se() < 1.0);
+    }
+
+    #[test]
+    fn learns_a_venue_bias() {
+        let mut kalman = KalmanFuser::new(&KalmanConfig::default(), 2);
+        let truth = run(&mut kalman, 0.0, 10.0, &[1.0, 1.0]);
+
+        // only the gap is observable, split either side of the weighted mean
+        assert!((kalman.bias(1) - kalman.bias(0) - 10.0).abs() < 1.0, "biases {} {}", kalman.bias(0), kalman.bias(1));
+        assert!((kalman.bias(0) + kalman.bias(1)).abs() < 1e-9);
+        assert!((kalman.price().unwrap() - (truth + 5.0)).abs() < 2.0);
+    }
+
+    #[test]
+    fn anchors_the_biases_to_the_weights_given() {
+        // with all the weight on the first venue its bias is held at zero, the price is its own
+        let mut kalman = KalmanFuser::new(&KalmanConfig::default(), 2);
+        let truth = run(&mut kalman, 0.0, 10.0, &[1.0, 0.0]);
+
+        assert!(kalman.bias(0).abs() < 1e-9);
+        assert!((kalman.bias(1) - 10.0).abs() < 1.0, "bias {}", kalman.bias(1));
+        assert!((kalman.price().unwrap() - truth).abs() < 2.0);
+    }
+}
@@ -0,0 +1,507 @@
+use polymarket_client_sdk::auth::Signer;
+use polymarket_client_sdk::clob::ws;
+use polymarket_client_sdk::clob::types::{Amount, Side, OrderType, SignedOrder};
+use polymarket_client_sdk::types::Decimal;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::time::{sleep, sleep_until, Duration};
+use tracing::{Instrument, debug, field, info, info_span, warn};
+
+use crate::clock::{Clock, SharedClock};
+use crate::config::{Config, EntryMode, OnShutdown};
+use crate::error::{self, OrderError, SigningError};
+use crate::exit::ExitExecutor;
+use crate::get_price_info::{self, BookTop};
+use crate::get_trend::{self, TrendSignal, VenueSnapshot};
+use crate::health::{MarketHealth, WsStatus};
+use crate::maker::{ClobClient, MakerEntry};
+use crate::orders::{self, OrderTracker, SharedTracker};
+use crate::positions::{self, Positions, SharedPositions};
+use crate::recorder::Recorder;
+use crate::strategy::{self, Tick};
+use crate::util_functions::{WINDOW_S, get_token_ids, window_slug, window_start};
+use crate::shutdown;
+use crate::supervisor::{Scope, Supervisor};
+use crate::{tui, user_channel, wallet};
+
+// Trades the up/down windows for real, optionally recording every tick for replay and backtest.
+pub async fn run(config: Arc<Config>, clock: SharedClock, record: Option<PathBuf>, supervisor: Supervisor, stop: watch::Sender<bool>) -> anyhow::Result<()> {
+    let entry_mode = config.entry_mode;
+    info!(?entry_mode, "starting");
+    let mut shutdown = stop.subscribe();
+
+    let mut recorder = record.as_deref().map(Recorder::create).transpose()?;
+    let (tx_trend, mut rx_trend) = watch::channel(TrendSignal::default());
+    let (tx_venues, rx_venues) = watch::channel(VenueSnapshot::default());
+    let (config_for_trend, clock_for_trend) = (config.clone(), clock.clone());
+    let (tx_window, rx_window) = watch::channel(None);
+    let rx_trend_for_tui = rx_trend.clone();
+    let restart = Duration::from_millis(config.feeds.reconnect_ms);
+
+    let supervisor_for_trend = supervisor.clone();
+    supervisor.spawn("trend", Scope::Run, restart, move || {
+        get_trend::connect(config_for_trend.clone(), clock_for_trend.clone(), supervisor_for_trend.clone(), tx_trend.clone(), tx_venues.clone())
+    });
+
+    let (signer, funder) = wallet::keys()?;
+    let (client, credentials) = wallet::authenticate(&signer, funder, config.account.wallet).await?;
+    let preflight = wallet::preflight(&client, signer.address(), funder, &config).await?.ok()?;
+    info!(signer = %signer.address(), %funder, wallet = config.account.wallet.as_str(), balance = %preflight.balance,
+        needed = %preflight.needed, "preflight passed");
+
+    let tracker = OrderTracker::shared(credentials.key(), clock.clone());
+    let user_ws = ws::Client::default().authenticate(credentials, signer.address())?;
+    let positions = Positions::shared(clock.clone());
+    let tracker_for_ws = tracker.clone();
+    let positions_for_ws = positions.clone();
+
+    supervisor.spawn("user channel", Scope::Run, restart, move || {
+        user_channel::connect(user_ws.clone(), tracker_for_ws.clone(), positions_for_ws.clone())
+    });
+
+    let discrepancies = positions::reconcile(&client, &positions, funder, &[]).await
+        .map_err(|e| anyhow::anyhow!("startup position reconciliation failed: {e}"))?;
+    for d in discrepancies.iter().filter(|d| d.ledger.is_zero()) {
+        warn!(token_id = %d.token_id, shares = %d.clob, on_chain = %d.on_chain.map(|b| b.to_string()).unwrap_or("-".into()),
+            "found a position the bot did not open");
+    }
+
+    // started once setup can no longer fail, so an early exit doesn't leave the terminal in raw mode
+    let dashboard = config.telemetry.tui.then(|| tui::spawn(tui::Dashboard {
+        config: config.clone(),
+        clock: clock.clone(),
+        venues: rx_venues.clone(),
+        trend: rx_trend_for_tui,
+        window: rx_window,
+        positions: positions.clone(),
+        tracker: tracker.clone(),
+        supervisor: supervisor.clone(),
+        stop: stop.clone(),
+    }));
+
+    let client_for_reconcile = client.clone();
+    let positions_for_reconcile = positions.clone();
+    let reconcile_interval = Duration::from_secs(config.account.reconcile_interval_s);
+    supervisor.spawn("reconcile", Scope::Run, reconcile_interval, move || {
+        let (client, positions) = (client_for_reconcile.clone(), positions_for_reconcile.clone());
+        async move {
+            loop {
+                sleep(reconcile_interval).await;
+                if let Err(e) = positions::reconcile(&client, &positions, funder, &[]).await {
+                    error::handled("position reconciliation failed", &e);
+                }
+            }
+        }
+    });
+
+    // tokens the bot bought this run, the only ones a shutdown sells
+    let mut traded = HashSet::new();
+
+    loop {
+        if *shutdown.borrow() {
+            break;
+        }
+
+        let Some((time_15_min, now_900)) = trading_window(&*clock, &config) else {
+            debug!("market not ready yet, waiting 5s");
+            shutdown::pause(&mut shutdown, Duration::from_secs(5)).await;
+            continue
+        };
+
+        let event_slug = window_slug(time_15_min);
+
+        let raw_tokens = get_token_ids(&event_slug).await;
+
+        let tokens = match raw_tokens {
+            Ok(t) => t,
+            Err(e) => {
+                error::handled(&format!("skipping {event_slug}"), &e);
+                shutdown::pause(&mut shutdown, Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+
+        let yes_token = tokens[0].clone();
+        let no_token = tokens[1].clone();
+        let window_span = info_span!("window", slug = %event_slug, up = %yes_token, down = %no_token);
+        info!(parent: &window_span, "window opened");
+        let (yes_token_for_ws, clock_for_ws) = (yes_token.clone(), clock.clone());
+        let close_in = Duration::from_secs(config.window.close_at_s.saturating_sub(now_900));
+
+        let (tx_price_info, mut rx_price_info) = watch::channel(BookTop::default());
+        let _ = tx_window.send(Some(tui::WindowView {
+            slug: event_slug.clone(),
+            up_token: yes_token.clone(),
+            down_token: no_token.clone(),
+            end_s: time_15_min + WINDOW_S,
+            book: rx_price_info.clone(),
+        }));
+
+        window_span.in_scope(|| supervisor.spawn("book", Scope::Window, restart, move || {
+            let (tx, token, clock) = (tx_price_info.clone(), yes_token_for_ws.clone(), clock_for_ws.clone());
+            async move { get_price_info::connect(tx, &token, clock).await }
+        }));
+        let book_ws = || if supervisor.is_running("book") { WsStatus::Connected } else { WsStatus::Closed };
+
+        async {
+            // the book feed retries on its own, give up on the window if it never gets going
+            tokio::select! {
+                _ = rx_price_info.changed() => {}
+                _ = sleep(close_in) => {
+                    warn!("no book update before the window closed");
+                    return Ok(());
+                }
+                _ = shutdown::requested(&mut shutdown) => return Ok(()),
+            }
+
+            let mut maker = match entry_mode {
+                EntryMode::Maker => match MakerEntry::new(&client, tracker.clone(), clock.clone(), &yes_token, &no_token, time_15_min + WINDOW_S, &config).await {
+                    Ok(m) => {
+                        traded.extend([yes_token.clone(), no_token.clone()]);
+                        Some(m)
+                    }
+                    Err(e) => {
+                        error::handled("could not set up maker entries", &e);
+                        shutdown::pause(&mut shutdown, Duration::from_secs(5)).await;
+                        return Ok(());
+                    }
+                },
+                EntryMode::Taker => None,
+            };
+
+            // a quiet trend feed mustn't leave the maker order resting into the window's close
+            let cancel_in = (time_15_min + WINDOW_S).saturating_sub(clock.now_s() + config.maker.cancel_lead_s);
+            let cancel_at = clock.instant() + Duration::from_secs(cancel_in);
+
+            loop {
+                let changed = tokio::select! {
+                    biased;
+                    _ = shutdown::requested(&mut shutdown) => None,
+                    _ = sleep_until(cancel_at), if maker.as_ref().is_some_and(|m| m.resting.is_some()) => {
+                        if let Some(maker) = maker.as_mut() {
+                            info!(reason = "window closing", "cancelling maker order");
+                            match maker.cancel(&client).await {
+                                Ok(filled) if filled > Decimal::ZERO => info!(shares = %maker.filled, "holding until resolution"),
+                                Ok(_) => {}
+                                Err(e) => error::handled("failed to cancel maker order", &e),
+                            }
+                        }
+                        continue;
+                    }
+                    changed = rx_trend.changed() => Some(changed),
+                };
+                match changed {
+                    None => {
+                        if let Some(maker) = maker.as_mut()
+                            && let Err(e) = maker.cancel(&client).await {
+                            error::handled("failed to cancel maker order", &e);
+                        }
+                        break;
+                    }
+                    Some(Ok(_)) => {
+                        // a trend update late enough to land in the next window closes this one too
+                        if trading_window(&*clock, &config).is_none_or(|(start, _)| start != time_15_min) {
+                            if let Some(maker) = maker.as_mut()
+                                && let Err(e) = maker.cancel(&client).await {
+                                error::handled("failed to cancel maker order", &e);
+                            }
+                            let stray: Vec<_> = tracker.lock().unwrap().open_orders()
+                                .filter(|o| o.token_id == yes_token || o.token_id == no_token)
+                                .cloned().collect();
+                            for order in stray {
+                                info!(order_id = %order.order_id, side = ?order.side, size = %order.original_size, price = %order.price, "cancelling open order");
+                                if let Err(source) = client.cancel_order(&order.order_id).await {
+                                    error::handled("failed to cancel stray order", &OrderError::Cancel { order_id: order.order_id.clone(), source }.into());
+                                }
+                            }
+                            let handled = error::handled_counts().map(|(kind, n)| format!("{} {}", kind.as_str(), n));
+                            info!(up = %positions.lock().unwrap().held(&yes_token),
+                                down = %positions.lock().unwrap().held(&no_token),
+                                handled_errors = %handled.join(", "),
+                                "window closing, holding shares into resolution");
+                            break;
+                        }
+
+                        let trend = rx_trend.borrow().clone();
+                        let seconds_in = clock.now_s() % WINDOW_S;
+                        debug!(%trend, "trend");
+
+                        if let Some(recorder) = recorder.as_mut() {
+                            let book = *rx_price_info.borrow();
+                            let health = MarketHealth::snapshot(&rx_venues.borrow(), &book, book_ws(), &*clock, &config);
+                            let tick = Tick::observe(&*clock, &event_slug, time_15_min + WINDOW_S, trend.clone(), &health, &book, &config);
+                            if let Err(e) = recorder.record(&tick) {
+                                error::handled("recording tick failed", &e);
+                            }
+                        }
+
+                        if let Some(maker) = maker.as_mut() {
+                            let signal = strategy::signal(&trend, seconds_in, &config);
+                            let book = *rx_price_info.borrow();
+                            let health = MarketHealth::snapshot(&rx_venues.borrow(), &book, book_ws(), &*clock, &config);
+
+                            let result = match health.check(&config) {
+                                Ok(()) => maker.on_signal(&client, &signer, signal, &book).await,
+                                Err(rejection) if maker.resting.is_some() => {
+                                    info!(%rejection, %health, "pulling maker order, feed unhealthy");
+                                    maker.cancel(&client).await
+                                }
+                                Err(_) => Ok(Decimal::ZERO),
+                            };
+                            match result {
+                                Ok(filled) if filled > Decimal::ZERO => {
+                                    info!(shares = %maker.filled, "holding until resolution");
+                                }
+                                Ok(_) => {}
+                                Err(e) => error::handled("maker entry failed", &e),
+                            }
+                            continue;
+                        }
+
+                        if let Some(up) = strategy::signal(&trend, seconds_in, &config) {
+                            info!(%trend, limit = strategy::limit(seconds_in, &config), threshold = ?config.strategy.threshold, "trend over the limit");
+                            let token = if up { yes_token.clone() } else { no_token.clone() };
+                            let book = *rx_price_info.borrow();
+                            let health = MarketHealth::snapshot(&rx_venues.borrow(), &book, book_ws(), &*clock, &config);
+
+                            if let Err(rejection) = health.check(&config) {
+                                info!(%rejection, %health, "skipping entry, feed unhealthy");
+                                continue;
+                            }
+
+                            if let (Some(bid), Some(ask)) = (book.bid, book.ask) {
+                                let price = (bid + ask) / 2.0; 
+                                if strategy::in_band(price, &config) {
+
+// ------------------------------------------------------------------------------------------------------------------
+
+                                    let order_span = info_span!("order", order_id = field::Empty, side = "buy", size_usdc = %config.taker.size_usdc, token_id = %token);
+                                    let signed_order = match build_buy(&client, &signer, &token, config.taker.size_usdc).instrument(order_span.clone()).await {
+                                        Ok(o) => o,
+                                        Err(e) => {
+                                            error::handled("skipping entry", &e);
+                                            continue;
+                                        }
+                                    };
+
+                                    let held_before = positions.lock().unwrap().held(&token);
+                                    let taking_amount = match client.post_order(signed_order).instrument(order_span.clone()).await {
+                                        Ok(response) => {
+                                            order_span.record("order_id", response.order_id.as_str());
+                                            debug!(parent: &order_span, ?response, "buy response");
+                                            if response.success {
+                                                tracker.lock().unwrap().record_post(&response.order_id, &token, Side::Buy, Decimal::ZERO, response.taking_amount);
+                                                response.taking_amount.to_string().parse::<f64>().ok()
+                                            } else {
+                                                let reason = response.error_msg.unwrap_or_default();
+                                                error::handled("buy order rejected", &OrderError::Rejected { token_id: token.clone(), reason }.into());
+                                                None
+                                            }
+                                        }
+                                        Err(source) => {
+                                            let e = error::Error::from(OrderError::Post { token_id: token.clone(), source });
+                                            // the order may have matched even though the response was lost,
+                                            // the user channel has the final word
+                                            shutdown::pause(&mut shutdown, Duration::from_secs(2)).await;
+                                            let bought = positions.lock().unwrap().held(&token) - held_before;
+                                            if bought > Decimal::ZERO {
+                                                warn!(parent: &order_span, error = %e, shares = %bought, "buy response lost but the user channel saw a fill");
+                                                bought.to_string().parse::<f64>().ok()
+                                            } else {
+                                                error::handled("buy order failed", &e);
+                                                None
+                                            }
+                                        }
+                                    };
+
+                                    if let Some(taking_amount) = taking_amount {
+                                        traded.insert(token.clone());
+                                        info!(parent: &order_span, shares = taking_amount, hold_s = config.taker.hold_s, "bought");
+                                        // a shutdown while holding goes straight to the wind-down, which sells or keeps as configured
+                                        if shutdown::pause(&mut shutdown, Duration::from_secs(config.taker.hold_s)).await {
+                                            break;
+                                        }
+
+                                        // the book is the up token's, the down token trades at its complement
+                                        let entry = if token == yes_token { price } else { 1.0 - price };
+                                        // a NaN or infinite mid leaves no entry to put the floor under, so sell down to the minimum tick
+                                        let min_floor = Decimal::new(1, 2);
+                                        let floor = match Decimal::from_str(&format!("{:.2}", entry)) {
+                                            Ok(entry) => (entry - config.exit.floor_below_entry).max(min_floor),
+                                            Err(_) => {
+                                                error::handled("exiting without an entry floor",
+                                                    &OrderError::Invalid { what: "entry price", value: entry.to_string() }.into());
+                                                min_floor
+                                            }
+                                        };
+                                        let deadline = time_15_min + WINDOW_S - config.exit.deadline_lead_s;
+                                        let outcome = ExitExecutor::new(&client, &signer, &positions, &tracker, &*clock, &config.exit)
+                                            .until_shutdown(&shutdown)
+                                            .exit(&token, floor, deadline).await;
+                                        info!(parent: &order_span, %outcome, "exit");
+                                    }
+
+// ------------------------------------------------------------------------------------------------------------------
+                                }
+                            }
+
+                        }
+                    },
+                    Some(Err(_)) => {
+                        warn!("trend channel closed");
+                        break;
+                    }
+                }
+            }
+            Ok::<(), anyhow::Error>(())
+        }
+        .instrument(window_span)
+        .await?;
+        supervisor.cancel(Scope::Window);
+    }
+
+    if let Some(dashboard) = dashboard {
+        let _ = tokio::task::spawn_blocking(move || dashboard.join()).await;
+    }
+    wind_down(&config, &*clock, &client, &signer, &positions, &tracker, &traded).await;
+    if let Some(recorder) = recorder
+        && let Err(e) = recorder.finish() {
+        error::handled("flushing the recording failed", &e);
+    }
+    if config.metrics_addr().is_some() && config.shutdown.metrics_linger_s > 0 {
+        info!(linger_s = config.shutdown.metrics_linger_s, "serving metrics a little longer for a final scrape");
+        sleep(Duration::from_secs(config.shutdown.metrics_linger_s)).await;
+    }
+    supervisor.cancel_all();
+    Ok(())
+}
+
+// the window being traded and how many seconds into it, None outside open_after_s..=close_at_s
+fn trading_window(clock: &dyn Clock, config: &Config) -> Option<(u64, u64)> {
+    let now_s = clock.now_s();
+    let seconds_in = now_s % WINDOW_S;
+    strategy::in_window(seconds_in, config).then(|| (window_start(now_s), seconds_in))
+}
+
+// After a shutdown request: cancels whatever is still resting, sells or keeps what the bot bought
+// as configured, and reports what is left. Prints as well as logs, the dashboard has the console
+// logs turned off.
+async fn wind_down<S: Signer>(
+    config: &Config,
+    clock: &dyn Clock,
+    client: &ClobClient,
+    signer: &S,
+    positions: &SharedPositions,
+    tracker: &SharedTracker,
+    traded: &HashSet<String>,
+) {
+    let say = |line: String| {
+        info!("{line}");
+        if config.telemetry.tui {
+            println!("{line}");
+        }
+    };
+    say("stopped entering, cleaning up".to_string());
+
+    let open: Vec<_> = tracker.lock().unwrap().open_orders().cloned().collect();
+    for order in open {
+        say(format!("cancelling {} order {} on {}", orders::side_label(order.side), order.order_id, order.token_id));
+        if let Err(source) = client.cancel_order(&order.order_id).await {
+            error::handled("failed to cancel order on shutdown", &OrderError::Cancel { order_id: order.order_id.clone(), source }.into());
+        }
+    }
+
+    if config.shutdown.open_positions == OnShutdown::Sell {
+        let deadline = clock.now_s() + config.shutdown.timeout_s;
+        let mut tokens: Vec<_> = traded.iter().filter(|t| positions.lock().unwrap().held(t) > Decimal::ZERO).collect();
+        tokens.sort();
+        for token in tokens {
+            let outcome = ExitExecutor::new(client, signer, positions, tracker, clock, &config.exit)
+                .exit(token, config.shutdown.sell_floor, deadline).await;
+            say(format!("{token}: {outcome}"));
+        }
+    }
+
+    let positions = positions.lock().unwrap();
+    let held: Vec<_> = positions.tokens().into_iter()
+        .map(|token| { let shares = positions.held(&token); (token, shares) })
+        .filter(|(_, shares)| *shares > Decimal::ZERO)
+        .collect();
+    if held.is_empty() {
+        say("nothing held".to_string());
+    }
+    for (token, shares) in held {
+        let origin = if traded.contains(&token) { "bought this run" } else { "held before this run" };
+        say(format!("still holding {shares} of {token} ({origin}), it pays out at resolution"));
+    }
+    let handled = error::handled_counts().map(|(kind, n)| format!("{} {}", kind.as_str(), n));
+    say(format!("realised pnl {} before fees, fees {}, handled errors {}", positions.realised_pnl, positions.fees, handled.join(", ")));
+}
+
+// FOK market buy for size_usdc, built and signed
+async fn build_buy<S: Signer>(client: &ClobClient, signer: &S, token: &str, size_usdc: Decimal) -> error::Result<SignedOrder> {
+    let amount = Amount::usdc(size_usdc)
+        .map_err(|e| OrderError::Invalid { what: "amount", value: e.to_string() })?;
+    let order = client.market_order().token_id(token)
+        .amount(amount).side(Side::Buy).order_type(OrderType::FOK)
+        .build().await
+        .map_err(|source| OrderError::Build { token_id: token.to_string(), source })?;
+    let signed_order = client.sign(signer, order).await
+        .map_err(|source| SigningError::Sign { token_id: token.to_string(), source })?;
+    Ok(signed_order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SimulatedClock;
+
+    // a window boundary, 1_700_000_100 being a multiple of 900
+    const BOUNDARY_S: u64 = 1_700_000_100;
+
+    #[test]
+    fn trades_between_open_after_and_close_at() {
+        let config = Config::default();
+        let clock = SimulatedClock::new((BOUNDARY_S - WINDOW_S) * 1000);
+        let start = BOUNDARY_S - WINDOW_S;
+
+        assert_eq!(trading_window(&clock, &config), None, "the first open_after_s are skipped");
+        clock.set((start + config.window.open_after_s) * 1000);
+        assert_eq!(trading_window(&clock, &config), Some((start, config.window.open_after_s)));
+        clock.set((start + config.window.close_at_s) * 1000 + 999);
+        assert_eq!(trading_window(&clock, &config), Some((start, config.window.close_at_s)));
+        clock.set((start + config.window.close_at_s + 1) * 1000);
+        assert_eq!(trading_window(&clock, &config), None);
+    }
+
+    #[test]
+    fn rolls_over_to_the_next_window() {
+        let config = Config::default();
+        let clock = SimulatedClock::new(BOUNDARY_S * 1000 - 1);
+        assert_eq!(window_start(clock.now_s()), BOUNDARY_S - WINDOW_S);
+        assert_eq!(trading_window(&clock, &config), None);
+
+        clock.set(BOUNDARY_S * 1000);
+        assert_eq!(window_start(clock.now_s()), BOUNDARY_S);
+        assert_eq!(window_slug(window_start(clock.now_s())), "btc-updown-15m-1700000100");
+        assert_eq!(trading_window(&clock, &config), None);
+
+        clock.set((BOUNDARY_S + config.window.open_after_s) * 1000);
+        let (start, seconds_in) = trading_window(&clock, &config).unwrap();
+        assert_eq!((start, seconds_in), (BOUNDARY_S, config.window.open_after_s));
+    }
+
+    #[test]
+    fn a_late_update_does_not_trade_the_old_window() {
+        let config = Config::default();
+        let traded_start = BOUNDARY_S - WINDOW_S;
+        // the trend stalled across the boundary and came back inside the next window's trading part
+        let clock = SimulatedClock::new((BOUNDARY_S + 60) * 1000);
+        let window = trading_window(&clock, &config);
+        assert!(window.is_some());
+        assert!(window.is_none_or(|(start, _)| start != traded_start));
+    }
+}
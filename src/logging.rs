@@ -0,0 +1,47 @@
+use std::fs::OpenOptions;
+use std::sync::Mutex;
+use tracing_subscriber::{EnvFilter, fmt, prelude::*};
+
+use crate::error::{ConfigError, Result};
+
+// default levels: trade decisions, orders and fills are info, per-tick feed values are debug/trace
+const CONSOLE_FILTER: &str = "main0=info,warn";
+const FILE_FILTER: &str = "main0=debug,warn";
+
+// Human readable logs on the console (unless it belongs to the dashboard), filtered by RUST_LOG.
+// Setting LOG_FILE also appends every event as a JSON line to that file (filtered by
+// LOG_FILE_FILTER), with the fields of its spans.
+pub fn init(console: bool) -> Result<()> {
+    let console = if console {
+        Some(fmt::layer()
+            .with_target(false)
+            .with_filter(filter("RUST_LOG", CONSOLE_FILTER)?))
+    } else {
+        None
+    };
+
+    let file = match std::env::var("LOG_FILE") {
+        Ok(path) => {
+            let file = OpenOptions::new().create(true).append(true).open(&path)
+                .map_err(|e| ConfigError::Invalid { name: "LOG_FILE", reason: format!("{path}: {e}") })?;
+            Some(fmt::layer()
+                .json()
+                .with_current_span(true)
+                .with_span_list(true)
+                .with_writer(Mutex::new(file))
+                .with_filter(filter("LOG_FILE_FILTER", FILE_FILTER)?))
+        }
+        Err(_) => None,
+    };
+
+    tracing_subscriber::registry().with(console).with(file).init();
+    Ok(())
+}
+
+fn filter(var: &'static str, default: &str) -> Result<EnvFilter> {
+    match std::env::var(var) {
+        Ok(directives) => EnvFilter::try_new(&directives)
+            .map_err(|e| ConfigError::Invalid { name: var, reason: e.to_string() }.into()),
+        Err(_) => Ok(EnvFilter::new(default)),
+    }
+}
@@ -1,197 +1,88 @@
 mod get_trend;
-use polymarket_client_sdk::POLYGON;
-use polymarket_client_sdk::auth::{LocalSigner, Signer};
-use polymarket_client_sdk::clob::{Client, Config};
-use polymarket_client_sdk::clob::types::SignatureType;
-use polymarket_client_sdk::clob::types::{Amount, Side, OrderType};
-use polymarket_client_sdk::types::Decimal;
-use std::time::{SystemTime, UNIX_EPOCH};
-
-use tokio::time::{sleep, Duration};
 mod config;
-use std::str::FromStr;
 mod util_functions;
-use crate::util_functions::get_token_ids;
 mod get_price_info;
-
-use tokio::sync::watch;
+mod health;
+mod maker;
+mod orders;
+mod user_channel;
+mod positions;
+mod exit;
+mod error;
+mod logging;
+mod metrics;
+mod tui;
+mod strategy;
+mod recorder;
+mod paper;
+mod backtest;
+mod report;
+mod markets;
+mod check;
+mod wallet;
+mod live;
+mod cli;
+mod shutdown;
+mod supervisor;
+mod clock;
+mod volatility;
+mod kalman;
+mod weights;
+mod outliers;
+mod basis;
+mod order_flow;
+#[cfg(test)]
+mod testing;
+
+use clap::Parser;
+use std::sync::Arc;
+use tokio::time::Duration;
+
+use crate::cli::{Cli, Command};
+use crate::clock::{OffsetClock, SharedClock, SystemClock};
+use crate::config::Config;
+use crate::report::Report;
+use crate::supervisor::{Scope, Supervisor};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenvy::dotenv().ok();
-    let (tx_trend, mut rx_trend) = watch::channel(0.0);
-
-    tokio::spawn(async move {
-        match get_trend::connect(tx_trend).await {
-            Ok(_) => {println!("get_trend exited")},
-            Err(e) => eprintln!("get_trend failed: {e}"),
+    let cli = Cli::parse();
+    let config = Arc::new(Config::load(cli.config.as_deref())?);
+    let command = cli.command.unwrap_or(Command::Run { record: None });
+
+    // metrics only make sense for the long running commands, the dashboard only for live trading
+    let long_running = matches!(command, Command::Run { .. } | Command::Paper { .. });
+    logging::init(!(matches!(command, Command::Run { .. }) && config.telemetry.tui))?;
+    let supervisor = Supervisor::default();
+    let clock: SharedClock = if long_running {
+        metrics::init();
+        if let Some(addr) = config.metrics_addr().map(String::from) {
+            supervisor.spawn("metrics", Scope::Run, Duration::from_secs(5), move || metrics::serve(addr.clone()));
         }
-    });
-
-    loop {
-
-        let time_now = SystemTime::now().duration_since(UNIX_EPOCH).expect("msg").as_secs();
-        let now_900 = time_now % 900;
-        let time_15_min = time_now - now_900;
-
-        if now_900 > 870 || now_900 < 10 {
-            println!("Market not read yet, waiting 5...");
-            sleep(Duration::from_secs(5)).await;
-            continue
+        let clock = Arc::new(OffsetClock::default());
+        if let Some(server) = config.ntp_server().map(String::from) {
+            let (clock, interval) = (clock.clone(), Duration::from_secs(config.clock.sync_interval_s));
+            supervisor.spawn("clock sync", Scope::Run, interval, move || clock::sync(clock.clone(), server.clone(), interval));
         }
-
-        let private_key = std::env::var("PRIVATE_KEY")
-            .expect("You have not inputted your private key\n
-            Please in the ROOT folder (not in src) create a .env file and inside Write:\n\n
-            PRIVATE_KEY = 0x...\n
-            Note that if the 0x part is not at the start of your private key\n
-            You must add the 0x \n");
-        let funder_key = std::env::var("FUNDER_KEY")
-            .expect("The funder key should be the PUBLIC key\n
-            Of your polymarket proxy wallet found in your profile\n\n
-            FUNDER_KEY = x0...\n\n
-            Again starting with x0 if its not their already
-            ");
-        let signer = LocalSigner::from_str(&private_key)?.with_chain_id(Some(POLYGON));
-        let client = Client::new("https://clob.polymarket.com", Config::default())?
-            .authentication_builder(&signer)
-            .signature_type(SignatureType::GnosisSafe)
-            .funder(funder_key.parse()?)
-            .authenticate()
-            .await?;
-
-        let event_slug = format!("btc-updown-15m-{}", time_15_min);
-
-        let raw_tokens = get_token_ids(&event_slug).await;
-
-        let tokens = match raw_tokens {
-            Ok(t) => t,
-            Err(_) => {
-                eprintln!("ERROR");
-                continue;
-            }
-        };
-
-        let yes_token = tokens[0].clone();
-        let no_token = tokens[1].clone();
-        println!("yes: {}, no: {}", yes_token, no_token);
-        let yes_token_for_ws = yes_token.clone();
-
-        let (tx_price_info, mut rx_price_info) = watch::channel((None::<f64>, None::<f64>));
-
-        tokio::spawn(async move {
-            match get_price_info::connect(tx_price_info, &yes_token_for_ws).await {
-                Ok(_) => println!("WebSocket connection exited"),
-                Err(e) => eprintln!("WebSocket failed: {e}"),
-            }
-        });
-
-        rx_price_info.changed().await?;
-
-        loop {
-            match rx_trend.changed().await {
-                Ok(_) => {
-                    let trend = *rx_trend.borrow();
-
-                    let limit = 40.0;
-
-                    if trend > limit || trend < -limit {
-                        println!("!!! trend: {}", trend);
-                        let token = if trend > limit { yes_token.clone() } else { no_token.clone() };
-                        let price_info = *rx_price_info.borrow();
-
-                        if let (Some(bid), Some(ask)) = price_info {
-                            let price = (bid + ask) / 2.0; 
-                            if price > 0.05 && price < 0.95 {
-
-// ------------------------------------------------------------------------------------------------------------------
-
-                                let order = client.market_order().token_id(&token)
-                                .amount(Amount::usdc(Decimal::from(1))?).side(Side::Buy).order_type(OrderType::FOK)
-                                .build().await?;
-                                println!("order built");
-                                let signed_order = client.sign(&signer, order).await?;
-                                println!("order signed");
-
-                                let response = match client.post_order(signed_order).await {
-                                    Ok(resp) => resp,
-                                    Err(_) => {
-                                        // println!("Error buy posting order: {}", e);
-                                        continue
-                                    }
-                                };
-
-                                println!("Order response: {:?}", response);
-
-                                if response.success {
-                                    let taking_amount = response.taking_amount.to_string().parse::<f64>().ok().unwrap_or(0.0);
-                                    let mut amount_to_sell = (taking_amount * 100.0).floor() / 100.0;
-
-                                    sleep(Duration::from_secs(3)).await;
-
-                                    loop {
-                                        let amount_str = format!("{:.2}", amount_to_sell);
-                                        let amount_dec = Decimal::from_str(&amount_str).unwrap();
-
-                                        println!("{}", amount_dec);
-
-                                        let order = client.market_order().token_id(&token)
-                                        .amount(Amount::shares(amount_dec)?).side(Side::Sell).order_type(OrderType::FAK)
-                                        .build().await?;
-                                        println!("order built");
-                                    
-                                        let signed_order = client.sign(&signer, order).await?;
-                                        println!("order signed");
-
-                                        let response = match client.post_order(signed_order).await {
-                                            Ok(resp) => resp,
-                                            Err(_) => {
-                                                amount_to_sell -= 0.01;
-                                                if amount_to_sell <= 0.01 {
-                                                    break;
-                                                }
-                                                // println!("Error posting sell order: {}", e);
-                                                sleep(Duration::from_millis(500)).await;
-                                                continue;
-                                            }
-                                        };
-                                        println!("Order response: {:?}", response);
-
-                                        if response.success {
-                                            let making_amount = response.making_amount.to_string().parse::<f64>().ok().unwrap_or(0.0);
-                                            amount_to_sell -= (making_amount * 100.0).floor() / 100.0;
-                                            if amount_to_sell <= 0.01 {
-                                                println!("Full Sell Fill");
-                                                sleep(Duration::from_secs(3)).await;
-                                                break;
-                                            } else {
-                                                println!("Partial Sell Fill");
-                                            }
-                                        } else {
-                                            println!("Sell order failed");
-                                            break;
-                                        }
-                                    }
-                                }
-
-// ------------------------------------------------------------------------------------------------------------------
-                            }
-                        }
-
-                    }
-                    let time_now = SystemTime::now().duration_since(UNIX_EPOCH).expect("msg").as_secs();
-                    let now_900 = time_now % 900;
-
-                    if now_900 > 870 || now_900 < 10 {
-                        println!("Exiting current event");
-                        break;
-                    }
-                },
-                Err(_) => {
-                    println!("trend channel closed");
-                    break;
-                }
-            }
+        clock
+    } else {
+        Arc::new(SystemClock)
+    };
+
+    match command {
+        Command::Run { record } => live::run(config, clock, record, supervisor, shutdown::listen()).await,
+        Command::Paper { record } => Ok(paper::run(config, clock, record, supervisor, shutdown::listen().subscribe()).await?),
+        Command::Backtest { files } => Ok(backtest::backtest(config, &files)?),
+        Command::Replay { file, speed } => Ok(backtest::replay(config, &file, speed).await?),
+        Command::Report { log_file } => {
+            println!("{}", Report::read(&log_file)?);
+            Ok(())
+        }
+        Command::Markets => {
+            markets::list(&*clock).await;
+            Ok(())
         }
+        Command::Check => check::run(&config, &*clock).await,
     }
 }
@@ -0,0 +1,343 @@
+use polymarket_client_sdk::auth::{Normal, Signer};
+use polymarket_client_sdk::auth::state::Authenticated;
+use polymarket_client_sdk::clob::Client;
+use polymarket_client_sdk::clob::types::request::OrderBookSummaryRequest;
+use polymarket_client_sdk::clob::types::{OrderStatusType, OrderType, Side};
+use polymarket_client_sdk::types::{DateTime, Decimal, Utc};
+use std::str::FromStr;
+use tokio::time::{Duration, Instant};
+use tracing::{Span, field, info, instrument, warn};
+
+use crate::clock::SharedClock;
+use crate::config::{Config, MakerConfig};
+use crate::error::{MarketError, OrderError, Result, SigningError};
+use crate::get_price_info::BookTop;
+use crate::orders::SharedTracker;
+
+pub type ClobClient = Client<Authenticated<Normal>>;
+
+#[derive(Debug, Clone)]
+pub struct RestingOrder {
+    pub order_id: String,
+    pub token_id: String,
+    pub up: bool,
+    pub price: Decimal,
+    pub size: Decimal,
+    pub size_matched: Decimal,
+    pub placed_at: Instant,
+    pub span: Span, // the order's span, events about it after placement are logged under it
+}
+
+// one per 15 minute window, owns at most one resting limit buy meant to sit on the book as a maker
+pub struct MakerEntry {
+    tracker: SharedTracker,
+    clock: SharedClock,
+    yes_token: String,
+    no_token: String,
+    window_end: u64,
+    tick: Decimal,
+    config: MakerConfig,
+    band: (f64, f64), // the strategy's price band, on the side being bought
+    pub resting: Option<RestingOrder>,
+    pub filled: Decimal, // shares bought this window, held until resolution
+    last_poll: Instant,
+}
+
+impl MakerEntry {
+    pub async fn new(
+        client: &ClobClient,
+        tracker: SharedTracker,
+        clock: SharedClock,
+        yes_token: &str,
+        no_token: &str,
+        window_end: u64,
+        config: &Config,
+    ) -> Result<Self> {
+        let tick = client.tick_size(yes_token).await
+            .map_err(|source| MarketError::TickSize { token_id: yes_token.to_string(), source })?
+            .minimum_tick_size.as_decimal();
+        let last_poll = clock.instant();
+        Ok(MakerEntry {
+            tracker,
+            clock,
+            yes_token: yes_token.to_string(),
+            no_token: no_token.to_string(),
+            window_end,
+            tick,
+            config: config.maker.clone(),
+            band: (config.strategy.min_price, config.strategy.max_price),
+            resting: None,
+            filled: Decimal::ZERO,
+            last_poll,
+        })
+    }
+
+    // signal is Some(true) for up, Some(false) for down and None when the trend is inside the limit.
+    // Returns the shares newly filled since the last call.
+    pub async fn on_signal<S: Signer>(
+        &mut self,
+        client: &ClobClient,
+        signer: &S,
+        signal: Option<bool>,
+        book: &BookTop,
+    ) -> Result<Decimal> {
+        let now_s = self.clock.now_s();
+        let mut newly_filled = Decimal::ZERO;
+
+        if self.resting.is_some() {
+            newly_filled += self.poll(client, false).await?;
+        }
+
+        let Some(resting) = self.resting.clone() else {
+            // one fill per window, the shares are held to resolution
+            if self.filled.is_zero()
+                && now_s + self.config.cancel_lead_s < self.window_end
+                && let Some(up) = signal
+                && let Some(price) = self.target_price(up, book) {
+                self.place(client, signer, up, price, self.config.size_shares).await?;
+            }
+            return Ok(newly_filled);
+        };
+
+        if now_s + self.config.cancel_lead_s >= self.window_end {
+            info!(parent: &resting.span, reason = "window closing", "cancelling maker order");
+            newly_filled += self.cancel(client).await?;
+            return Ok(newly_filled);
+        }
+
+        if signal == Some(!resting.up) {
+            info!(parent: &resting.span, reason = "signal flipped", "cancelling maker order");
+            newly_filled += self.cancel(client).await?;
+            return Ok(newly_filled);
+        }
+
+        if self.clock.instant().duration_since(resting.placed_at) < Duration::from_millis(self.config.reprice_ms) {
+            return Ok(newly_filled);
+        }
+
+        match self.target_price(resting.up, book) {
+            Some(price) if price != resting.price => {
+                info!(parent: &resting.span, from = %resting.price, to = %price, "repricing maker order");
+                newly_filled += self.cancel(client).await?;
+                if self.filled.is_zero() {
+                    self.place(client, signer, resting.up, price, resting.size).await?;
+                }
+            }
+            Some(_) => {}
+            None => {
+                info!(parent: &resting.span, reason = "book outside the price band", "cancelling maker order");
+                newly_filled += self.cancel(client).await?;
+            }
+        }
+
+        Ok(newly_filled)
+    }
+
+    // best bid on our side, stepped inside by maker.improve_ticks if that still rests below the ask.
+    // The no token's book is the mirror of the yes book.
+    fn target_price(&self, up: bool, book: &BookTop) -> Option<Decimal> {
+        let (Some(bid), Some(ask)) = (book.bid, book.ask) else {
+            return None;
+        };
+        let (side_bid, side_ask) = if up { (bid, ask) } else { (1.0 - ask, 1.0 - bid) };
+
+        let mid = (side_bid + side_ask) / 2.0;
+        if !(self.band.0..=self.band.1).contains(&mid) {
+            return None;
+        }
+
+        let tick = self.tick.to_string().parse::<f64>().ok()?;
+        let mut price = side_bid + self.config.improve_ticks as f64 * tick;
+        if price > side_ask - tick / 2.0 {
+            price = side_bid;
+        }
+        if price > side_ask - tick / 2.0 || price < tick {
+            return None;
+        }
+
+        let ticks = (price / tick + 1e-9).floor();
+        Decimal::from_str(&format!("{:.*}", self.tick.scale() as usize, ticks * tick)).ok()
+    }
+
+    #[instrument(name = "order", skip_all, fields(order_id = field::Empty, side = "buy", size = %size, %price, up))]
+    async fn place<S: Signer>(
+        &mut self,
+        client: &ClobClient,
+        signer: &S,
+        up: bool,
+        price: Decimal,
+        size: Decimal,
+    ) -> Result<()> {
+        let token = if up { self.yes_token.clone() } else { self.no_token.clone() };
+
+        // polymarket has no post-only flag, a price at or through the ask fills as a taker and pays
+        // the fee maker entries avoid. The cached book can be stale, so check against the live one.
+        let request = OrderBookSummaryRequest::builder().token_id(token.clone()).build();
+        let live = client.order_book(&request).await
+            .map_err(|source| MarketError::Book { token_id: token.clone(), source })?;
+        if let Some(ask) = live.asks.iter().map(|level| level.price).min()
+            && price >= ask {
+            info!(token_id = %token, %ask, "maker price would cross the live ask, not placing");
+            return Ok(());
+        }
+
+        // GTD expiry is a backstop if the bot dies, polymarket applies it 60s early
+        let expiry = self.window_end - self.config.cancel_lead_s + 60;
+        let expiration = DateTime::<Utc>::from_timestamp(expiry as i64, 0)
+            .ok_or(OrderError::Invalid { what: "expiration", value: expiry.to_string() })?;
+
+        let order = client.limit_order().token_id(&token)
+            .price(price).size(size).side(Side::Buy).order_type(OrderType::GTD).expiration(expiration)
+            .build().await
+            .map_err(|source| OrderError::Build { token_id: token.clone(), source })?;
+        let signed_order = client.sign(signer, order).await
+            .map_err(|source| SigningError::Sign { token_id: token.clone(), source })?;
+        let response = client.post_order(signed_order).await
+            .map_err(|source| OrderError::Post { token_id: token.clone(), source })?;
+
+        if !response.success {
+            return Err(OrderError::Rejected { token_id: token, reason: response.error_msg.unwrap_or_default() }.into());
+        }
+
+        let span = Span::current();
+        span.record("order_id", response.order_id.as_str());
+        info!(token_id = %token, "maker order resting");
+        self.tracker.lock().unwrap().record_post(&response.order_id, &token, Side::Buy, price, size);
+        self.resting = Some(RestingOrder {
+            order_id: response.order_id,
+            token_id: token,
+            up,
+            price,
+            size,
+            size_matched: Decimal::ZERO,
+            placed_at: self.clock.instant(),
+            span,
+        });
+        Ok(())
+    }
+
+    // refreshes size_matched from the user channel. The channel can drop a message, so once it
+    // has been quiet on the order for maker.poll_ms (or when forced) the REST endpoint is asked
+    // too, at most every maker.poll_ms.
+    async fn poll(&mut self, client: &ClobClient, force: bool) -> Result<Decimal> {
+        let Some(resting) = self.resting.as_mut() else {
+            return Ok(Decimal::ZERO);
+        };
+
+        let now = self.clock.instant();
+        let poll_every = Duration::from_millis(self.config.poll_ms);
+        let tracked = self.tracker.lock().unwrap().order(&resting.order_id).cloned();
+        let fresh = tracked.as_ref().is_some_and(|order| now.duration_since(order.updated_at) < poll_every);
+        let (size_matched, open) = if force || (!fresh && now.duration_since(self.last_poll) >= poll_every) {
+            self.last_poll = now;
+            let order = client.order(&resting.order_id).await
+                .map_err(|source| OrderError::Lookup { order_id: resting.order_id.clone(), source })?;
+            let matched = tracked.as_ref().map_or(order.size_matched, |t| t.matched().max(order.size_matched));
+            (matched, matches!(order.status, OrderStatusType::Live | OrderStatusType::Delayed))
+        } else if let Some(order) = tracked {
+            (order.matched(), order.is_open())
+        } else {
+            return Ok(Decimal::ZERO);
+        };
+
+        let newly_filled = (size_matched - resting.size_matched).max(Decimal::ZERO);
+        resting.size_matched += newly_filled;
+        self.filled += newly_filled;
+
+        if newly_filled > Decimal::ZERO {
+            info!(parent: &resting.span, token_id = %resting.token_id, shares = %newly_filled, size_matched = %resting.size_matched, "maker fill");
+        }
+        if !open {
+            self.resting = None;
+        }
+        Ok(newly_filled)
+    }
+
+    pub async fn cancel(&mut self, client: &ClobClient) -> Result<Decimal> {
+        let Some(resting) = self.resting.as_ref() else {
+            return Ok(Decimal::ZERO);
+        };
+        let response = client.cancel_order(&resting.order_id).await
+            .map_err(|source| OrderError::Cancel { order_id: resting.order_id.clone(), source })?;
+        if let Some(reason) = response.not_canceled.get(&resting.order_id) {
+            warn!(parent: &resting.span, %reason, "maker order not cancelled");
+        }
+        // catch any fill that landed before the cancel
+        let newly_filled = self.poll(client, true).await?;
+        self.resting = None;
+        Ok(newly_filled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SimulatedClock;
+    use crate::orders::OrderTracker;
+    use std::sync::Arc;
+
+    // a cent tick, one tick of improvement and the default 0.05..=0.95 band
+    fn entry() -> MakerEntry {
+        let clock: SharedClock = Arc::new(SimulatedClock::new(1_700_000_000_000));
+        let config = Config::default();
+        MakerEntry {
+            tracker: OrderTracker::shared(Default::default(), clock.clone()),
+            last_poll: clock.instant(),
+            clock,
+            yes_token: "yes".to_string(),
+            no_token: "no".to_string(),
+            window_end: 1_700_000_900,
+            tick: Decimal::from_str("0.01").unwrap(),
+            config: config.maker.clone(),
+            band: (config.strategy.min_price, config.strategy.max_price),
+            resting: None,
+            filled: Decimal::ZERO,
+        }
+    }
+
+    fn book(bid: f64, ask: f64) -> BookTop {
+        BookTop { bid: Some(bid), ask: Some(ask), ..BookTop::default() }
+    }
+
+    fn price(s: &str) -> Option<Decimal> {
+        Some(Decimal::from_str(s).unwrap())
+    }
+
+    #[test]
+    fn steps_inside_the_bid() {
+        let entry = entry();
+        assert_eq!(entry.target_price(true, &book(0.40, 0.45)), price("0.41"));
+        // the down token's book is the yes book mirrored, its bid 1 - the yes ask
+        assert_eq!(entry.target_price(false, &book(0.40, 0.45)), price("0.56"));
+
+        let mut entry = entry;
+        entry.config.improve_ticks = 0;
+        assert_eq!(entry.target_price(true, &book(0.40, 0.45)), price("0.40"));
+    }
+
+    #[test]
+    fn rests_below_the_ask() {
+        let entry = entry();
+        // a one tick spread leaves no room inside, it joins the bid
+        assert_eq!(entry.target_price(true, &book(0.40, 0.41)), price("0.40"));
+        assert_eq!(entry.target_price(false, &book(0.40, 0.41)), price("0.59"));
+        // a locked book has nowhere to rest
+        assert_eq!(entry.target_price(true, &book(0.40, 0.40)), None);
+
+        let mut entry = entry;
+        entry.config.improve_ticks = 5;
+        assert_eq!(entry.target_price(true, &book(0.40, 0.43)), price("0.40"));
+    }
+
+    #[test]
+    fn stays_inside_the_price_band() {
+        let entry = entry();
+        assert_eq!(entry.target_price(true, &book(0.96, 0.98)), None);
+        assert_eq!(entry.target_price(false, &book(0.96, 0.98)), None);
+        assert_eq!(entry.target_price(true, &book(0.02, 0.04)), None);
+        assert_eq!(entry.target_price(false, &book(0.02, 0.04)), None);
+        // the band is on the mid of the side being bought
+        assert_eq!(entry.target_price(true, &book(0.93, 0.97)), price("0.94"));
+        assert_eq!(entry.target_price(true, &BookTop { bid: Some(0.40), ..BookTop::default() }), None);
+    }
+}
@@ -0,0 +1,30 @@
+use chrono::DateTime;
+
+use crate::clock::Clock;
+use crate::error;
+use crate::util_functions::{WINDOW_S, get_token_ids, window_slug, window_start};
+
+// Prints the current and next up/down windows with their tokens. The next one is usually listed
+// well before it opens, a lookup error there just means it isn't yet.
+pub async fn list(clock: &dyn Clock) {
+    let current = window_start(clock.now_s());
+
+    for (label, start) in [("current", current), ("next", current + WINDOW_S)] {
+        let slug = window_slug(start);
+        let time = |s: u64| DateTime::from_timestamp(s as i64, 0)
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+            .unwrap_or_default();
+        println!("{label}: {slug}");
+        println!("  open {} to {}", time(start), time(start + WINDOW_S));
+        match get_token_ids(&slug).await {
+            Ok(tokens) => {
+                println!("  up   {}", tokens[0]);
+                println!("  down {}", tokens[1]);
+            }
+            Err(e) => {
+                error::count(&e);
+                println!("  not listed: {e}");
+            }
+        }
+    }
+}
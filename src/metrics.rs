@@ -0,0 +1,206 @@
+use prometheus::{
+    Encoder, Gauge, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use std::sync::LazyLock;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+use crate::clock::Clock;
+use crate::error::{ConfigError, Result};
+
+pub static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+fn register<T: prometheus::core::Collector + Clone + 'static>(metric: T) -> T {
+    REGISTRY.register(Box::new(metric.clone())).expect("metric registered twice");
+    metric
+}
+
+pub static FEED_MESSAGES: LazyLock<IntCounterVec> = LazyLock::new(|| register(
+    IntCounterVec::new(Opts::new("feed_messages_total", "Trade or book messages received per venue"), &["venue"]).unwrap()
+));
+pub static FEED_LATENCY: LazyLock<HistogramVec> = LazyLock::new(|| register(
+    HistogramVec::new(
+        HistogramOpts::new("feed_latency_seconds", "Local receive time minus the venue's event timestamp")
+            .buckets(vec![0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0]),
+        &["venue"],
+    ).unwrap()
+));
+pub static FEED_CONNECTS: LazyLock<IntCounterVec> = LazyLock::new(|| register(
+    IntCounterVec::new(Opts::new("feed_connects_total", "Successful websocket connections per venue"), &["venue"]).unwrap()
+));
+pub static FEED_RECONNECTS: LazyLock<IntCounterVec> = LazyLock::new(|| register(
+    IntCounterVec::new(Opts::new("feed_reconnects_total", "Connections per venue after the first one"), &["venue"]).unwrap()
+));
+pub static AGGREGATE_PRICE: LazyLock<Gauge> = LazyLock::new(|| register(
+    Gauge::new("aggregate_price_usd", "Weighted BTC price across the live venues").unwrap()
+));
+pub static ORDER_FLOW_IMBALANCE: LazyLock<GaugeVec> = LazyLock::new(|| register(
+    GaugeVec::new(Opts::new("order_flow_imbalance", "Taker buy minus sell notional over their sum, across venues, per window"), &["window_ms"]).unwrap()
+));
+pub static BOOK_GAPS: LazyLock<IntCounterVec> = LazyLock::new(|| register(
+    IntCounterVec::new(Opts::new("book_gaps_total", "Venue order books resynced after a missed update"), &["venue"]).unwrap()
+));
+pub static BOOK_IMBALANCE: LazyLock<GaugeVec> = LazyLock::new(|| register(
+    GaugeVec::new(Opts::new("book_imbalance", "Best bid size minus best ask size over their sum, per venue"), &["venue"]).unwrap()
+));
+pub static USDT_USD: LazyLock<Gauge> = LazyLock::new(|| register(
+    Gauge::new("usdt_usd", "USD per USDT the USDT quoted venues are converted at").unwrap()
+));
+pub static TICKS_REJECTED: LazyLock<IntCounterVec> = LazyLock::new(|| register(
+    IntCounterVec::new(Opts::new("ticks_rejected_total", "Venue ticks dropped as bad prices"), &["venue"]).unwrap()
+));
+pub static VENUE_EXCLUSIONS: LazyLock<IntCounterVec> = LazyLock::new(|| register(
+    IntCounterVec::new(Opts::new("venue_exclusions_total", "Times a venue was left out of the fused price for disagreeing with the others"), &["venue"]).unwrap()
+));
+pub static VENUE_WEIGHT: LazyLock<GaugeVec> = LazyLock::new(|| register(
+    GaugeVec::new(Opts::new("venue_weight", "Each venue's share of the aggregate"), &["venue"]).unwrap()
+));
+pub static VENUE_BIAS: LazyLock<GaugeVec> = LazyLock::new(|| register(
+    GaugeVec::new(Opts::new("venue_bias_usd", "Each venue's price over the fused one, as the Kalman fuser has learned it"), &["venue"]).unwrap()
+));
+pub static TREND_SLOPE: LazyLock<Gauge> = LazyLock::new(|| register(
+    Gauge::new("trend_slope", "Current trend slope of the aggregate price, in $/s").unwrap()
+));
+pub static TREND_HORIZON_SLOPE: LazyLock<GaugeVec> = LazyLock::new(|| register(
+    GaugeVec::new(Opts::new("trend_horizon_slope", "Trend slope of the aggregate price over each horizon, in $/s"), &["horizon_ms"]).unwrap()
+));
+pub static TREND_R_SQUARED: LazyLock<Gauge> = LazyLock::new(|| register(
+    Gauge::new("trend_r_squared", "R² of the current trend fit").unwrap()
+));
+pub static TREND_Z_SCORE: LazyLock<Gauge> = LazyLock::new(|| register(
+    Gauge::new("trend_z_score", "Current trend slope over what the recent volatility would give a random walk").unwrap()
+));
+pub static VOLATILITY: LazyLock<Gauge> = LazyLock::new(|| register(
+    Gauge::new("volatility_per_sqrt_s", "Realised volatility of the aggregate's log price per √s").unwrap()
+));
+pub static BOOK_SPREAD: LazyLock<Gauge> = LazyLock::new(|| register(
+    Gauge::new("book_spread", "Best ask minus best bid on the up token").unwrap()
+));
+pub static ORDERS_PLACED: LazyLock<IntCounterVec> = LazyLock::new(|| register(
+    IntCounterVec::new(Opts::new("orders_placed_total", "Orders accepted by the CLOB"), &["side"]).unwrap()
+));
+pub static ORDERS_FILLED: LazyLock<IntCounterVec> = LazyLock::new(|| register(
+    IntCounterVec::new(Opts::new("orders_filled_total", "Placed orders that got at least a partial fill"), &["side"]).unwrap()
+));
+pub static ORDERS_REJECTED: LazyLock<IntCounterVec> = LazyLock::new(|| register(
+    IntCounterVec::new(Opts::new("orders_rejected_total", "Orders that failed to build, post or were rejected"), &["reason"]).unwrap()
+));
+pub static FILL_RATIO: LazyLock<Gauge> = LazyLock::new(|| register(
+    Gauge::new("order_fill_ratio", "Filled orders over placed orders").unwrap()
+));
+pub static REALISED_PNL: LazyLock<Gauge> = LazyLock::new(|| register(
+    Gauge::new("realised_pnl_usdc", "Realised PnL of sells against average cost, before fees").unwrap()
+));
+pub static FEES: LazyLock<Gauge> = LazyLock::new(|| register(
+    Gauge::new("fees_usdc", "Taker fees paid").unwrap()
+));
+pub static ERRORS_HANDLED: LazyLock<IntCounterVec> = LazyLock::new(|| register(
+    IntCounterVec::new(Opts::new("errors_handled_total", "Errors the bot logged and carried on from"), &["kind"]).unwrap()
+));
+pub static CLOCK_OFFSET_MS: LazyLock<Gauge> = LazyLock::new(|| register(
+    Gauge::new("clock_offset_ms", "NTP time minus the system clock, added to every time the bot reads").unwrap()
+));
+pub static TASK_RESTARTS: LazyLock<IntCounterVec> = LazyLock::new(|| register(
+    IntCounterVec::new(Opts::new("task_restarts_total", "Background tasks restarted by the supervisor"), &["task"]).unwrap()
+));
+
+// registers every metric up front so the families show up before their first update
+pub fn init() {
+    LazyLock::force(&FEED_MESSAGES);
+    LazyLock::force(&FEED_LATENCY);
+    LazyLock::force(&FEED_CONNECTS);
+    LazyLock::force(&FEED_RECONNECTS);
+    LazyLock::force(&AGGREGATE_PRICE);
+    LazyLock::force(&ORDER_FLOW_IMBALANCE);
+    LazyLock::force(&BOOK_GAPS);
+    LazyLock::force(&BOOK_IMBALANCE);
+    LazyLock::force(&USDT_USD);
+    LazyLock::force(&TICKS_REJECTED);
+    LazyLock::force(&VENUE_EXCLUSIONS);
+    LazyLock::force(&VENUE_WEIGHT);
+    LazyLock::force(&VENUE_BIAS);
+    LazyLock::force(&TREND_SLOPE);
+    LazyLock::force(&TREND_R_SQUARED);
+    LazyLock::force(&TREND_Z_SCORE);
+    LazyLock::force(&VOLATILITY);
+    LazyLock::force(&TREND_HORIZON_SLOPE);
+    LazyLock::force(&BOOK_SPREAD);
+    LazyLock::force(&ORDERS_PLACED);
+    LazyLock::force(&ORDERS_FILLED);
+    LazyLock::force(&ORDERS_REJECTED);
+    LazyLock::force(&FILL_RATIO);
+    LazyLock::force(&REALISED_PNL);
+    LazyLock::force(&FEES);
+    LazyLock::force(&ERRORS_HANDLED);
+    LazyLock::force(&CLOCK_OFFSET_MS);
+    LazyLock::force(&TASK_RESTARTS);
+}
+
+// counts the message and how far behind the venue's own timestamp (unix ms) it arrived
+pub fn feed_message(venue: &str, event_ms: u64, clock: &dyn Clock) {
+    FEED_MESSAGES.with_label_values(&[venue]).inc();
+    let now_ms = clock.now_ms() as f64;
+    FEED_LATENCY.with_label_values(&[venue]).observe(((now_ms - event_ms as f64) / 1000.0).max(0.0));
+}
+
+pub fn feed_connected(venue: &str) {
+    let connects = FEED_CONNECTS.with_label_values(&[venue]);
+    if connects.get() > 0 {
+        FEED_RECONNECTS.with_label_values(&[venue]).inc();
+    }
+    connects.inc();
+}
+
+pub fn order_placed(side: &str) {
+    ORDERS_PLACED.with_label_values(&[side]).inc();
+    update_fill_ratio();
+}
+
+pub fn order_filled(side: &str) {
+    ORDERS_FILLED.with_label_values(&[side]).inc();
+    update_fill_ratio();
+}
+
+fn update_fill_ratio() {
+    let total = |counter: &IntCounterVec| ["buy", "sell"].iter().map(|s| counter.with_label_values(&[*s]).get()).sum::<u64>();
+    let placed = total(&ORDERS_PLACED);
+    if placed > 0 {
+        FILL_RATIO.set(total(&ORDERS_FILLED) as f64 / placed as f64);
+    }
+}
+
+// Serves the registry in the Prometheus text format on every request to `addr`.
+pub async fn serve(addr: String) -> Result<()> {
+    let listener = TcpListener::bind(&addr).await
+        .map_err(|e| ConfigError::Invalid { name: "METRICS_ADDR", reason: format!("{addr}: {e}") })?;
+    info!(%addr, "serving metrics");
+
+    loop {
+        let (mut stream, _) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!(error = %e, "metrics connection failed");
+                continue;
+            }
+        };
+        tokio::spawn(async move {
+            // every path gets the metrics, the request itself doesn't matter
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request).await;
+
+            let mut body = Vec::new();
+            let encoder = TextEncoder::new();
+            if let Err(e) = encoder.encode(&REGISTRY.gather(), &mut body) {
+                warn!(error = %e, "encoding metrics failed");
+                return;
+            }
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                encoder.format_type(), body.len()
+            );
+            let _ = stream.write_all(header.as_bytes()).await;
+            let _ = stream.write_all(&body).await;
+        });
+    }
+}
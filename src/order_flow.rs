@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::get_trend::{Aggressor, VENUES};
+
+// Who's been crossing the spread over the last window_ms, each imbalance being
+// (buys - sells) / (buys + sells) in -1..=1. Takers leaning one way tends to move the price
+// their way over the next seconds to minutes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OrderFlow {
+    pub window_ms: u64,
+    pub volume_imbalance: f64, // by notional, over every venue
+    pub count_imbalance: f64, // by number of trades
+    pub notional: f64, // $ traded in the window
+    pub venues: Vec<VenueFlow>, // in the order of get_trend::VENUES
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct VenueFlow {
+    pub volume_imbalance: f64,
+    pub count_imbalance: f64,
+}
+
+// The signed sums over one window, updated as trades enter and leave it.
+pub struct FlowWindow {
+    window_ms: u64,
+    trades: VecDeque<(u64, usize, f64, Aggressor)>, // time, venue, notional, side
+    sums: Vec<Sums>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Sums {
+    buy_notional: f64,
+    sell_notional: f64,
+    buys: u64,
+    sells: u64,
+}
+
+impl Sums {
+    // sign is 1 as a trade enters the window and -1 as it leaves
+    fn add(&mut self, notional: f64, aggressor: Aggressor, sign: i64) {
+        let (side_notional, count) = match aggressor {
+            Aggressor::Buy => (&mut self.buy_notional, &mut self.buys),
+            Aggressor::Sell => (&mut self.sell_notional, &mut self.sells),
+        };
+        *side_notional += sign as f64 * notional;
+        *count = count.saturating_add_signed(sign);
+    }
+
+    fn flow(&self) -> VenueFlow {
+        VenueFlow {
+            volume_imbalance: imbalance(self.buy_notional, self.sell_notional),
+            count_imbalance: imbalance(self.buys as f64, self.sells as f64),
+        }
+    }
+}
+
+impl FlowWindow {
+    pub fn new(window_ms: u64) -> Self {
+        FlowWindow { window_ms, trades: VecDeque::new(), sums: vec![Sums::default(); VENUES.len()] }
+    }
+
+    pub fn push(&mut self, time_ms: u64, venue: usize, notional: f64, aggressor: Aggressor) {
+        if notional.is_finite() && notional > 0.0 {
+            self.trades.push_back((time_ms, venue, notional, aggressor));
+            self.sums[venue].add(notional, aggressor, 1);
+        }
+        let start = time_ms.saturating_sub(self.window_ms);
+        while let Some(&(t, venue, notional, aggressor)) = self.trades.front() && t < start {
+            self.trades.pop_front();
+            self.sums[venue].add(notional, aggressor, -1);
+        }
+    }
+
+    pub fn flow(&self) -> OrderFlow {
+        let total = self.sums.iter().fold(Sums::default(), |mut total, s| {
+            total.buy_notional += s.buy_notional;
+            total.sell_notional += s.sell_notional;
+            total.buys += s.buys;
+            total.sells += s.sells;
+            total
+        });
+        let VenueFlow { volume_imbalance, count_imbalance } = total.flow();
+        OrderFlow {
+            window_ms: self.window_ms,
+            volume_imbalance,
+            count_imbalance,
+            notional: (total.buy_notional + total.sell_notional).max(0.0),
+            venues: self.sums.iter().map(Sums::flow).collect(),
+        }
+    }
+}
+
+// 0 with nothing traded, and while rounding leaves an emptied window a hair off zero
+fn imbalance(buys: f64, sells: f64) -> f64 {
+    let total = buys + sells;
+    if total > 1e-9 { ((buys - sells) / total).clamp(-1.0, 1.0) } else { 0.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: u64 = 1_700_000_000_000;
+
+    #[test]
+    fn trades_leave_the_window() {
+        let mut window = FlowWindow::new(10_000);
+        window.push(START, 0, 300.0, Aggressor::Buy);
+        window.push(START + 5_000, 0, 100.0, Aggressor::Sell);
+        let flow = window.flow();
+        assert_eq!(flow.notional, 400.0);
+        assert_eq!(flow.volume_imbalance, 0.5);
+        assert_eq!(flow.count_imbalance, 0.0);
+
+        // on the edge the buy still counts, a millisecond past it it's gone
+        window.push(START + 10_000, 0, 0.0, Aggressor::Buy);
+        assert_eq!(window.flow().notional, 400.0);
+        window.push(START + 10_001, 0, 0.0, Aggressor::Buy);
+        let flow = window.flow();
+        assert_eq!(flow.notional, 100.0);
+        assert_eq!((flow.volume_imbalance, flow.count_imbalance), (-1.0, -1.0));
+    }
+
+    #[test]
+    fn venues_have_their_own_imbalance() {
+        let mut window = FlowWindow::new(10_000);
+        window.push(START, 0, 300.0, Aggressor::Buy);
+        window.push(START, 1, 100.0, Aggressor::Sell);
+        window.push(START, 1, 100.0, Aggressor::Sell);
+        let flow = window.flow();
+
+        assert_eq!(flow.venues.len(), VENUES.len());
+        assert_eq!(flow.venues[0], VenueFlow { volume_imbalance: 1.0, count_imbalance: 1.0 });
+        assert_eq!(flow.venues[1], VenueFlow { volume_imbalance: -1.0, count_imbalance: -1.0 });
+        assert_eq!(flow.venues[2], VenueFlow::default());
+        // the aggregate weighs every trade, not every venue
+        assert!((flow.volume_imbalance - 0.2).abs() < 1e-12);
+        assert!((flow.count_imbalance - -1.0 / 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn drops_trades_without_a_notional() {
+        let mut window = FlowWindow::new(10_000);
+        for notional in [f64::NAN, f64::INFINITY, 0.0, -50.0] {
+            window.push(START, 0, notional, Aggressor::Buy);
+        }
+        assert_eq!(window.flow(), FlowWindow::new(10_000).flow());
+    }
+
+    #[test]
+    fn an_emptied_window_is_balanced() {
+        let mut window = FlowWindow::new(1_000);
+        // notionals that don't add back to exactly zero
+        window.push(START, 0, 0.1, Aggressor::Buy);
+        window.push(START, 0, 0.2, Aggressor::Buy);
+        window.push(START, 0, 0.7, Aggressor::Sell);
+        window.push(START + 2_000, 0, 0.0, Aggressor::Buy);
+
+        let flow = window.flow();
+        assert_eq!((flow.volume_imbalance, flow.count_imbalance), (0.0, 0.0));
+        assert_eq!(flow.venues[0], VenueFlow::default());
+        assert!(flow.notional < 1e-9);
+    }
+}
@@ -0,0 +1,374 @@
+use polymarket_client_sdk::auth::ApiKey;
+use polymarket_client_sdk::clob::types::Side;
+use polymarket_client_sdk::clob::ws::types::response::{OrderMessage, TradeMessage};
+use polymarket_client_sdk::types::Decimal;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+use crate::clock::SharedClock;
+use crate::metrics;
+
+pub type SharedTracker = Arc<Mutex<OrderTracker>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    Live,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+}
+
+#[derive(Debug, Clone)]
+pub struct TrackedOrder {
+    pub order_id: String,
+    pub token_id: String,
+    pub side: Side,
+    pub price: Decimal,
+    pub original_size: Decimal,
+    pub size_matched: Decimal, // as reported by order updates
+    pub filled: Decimal, // as counted from trades, taker orders only ever get these
+    pub status: OrderStatus,
+    pub updated_at: Instant,
+}
+
+impl TrackedOrder {
+    pub fn is_open(&self) -> bool {
+        matches!(self.status, OrderStatus::Live | OrderStatus::PartiallyFilled)
+    }
+
+    pub fn matched(&self) -> Decimal {
+        self.size_matched.max(self.filled)
+    }
+
+    fn refresh_status(&mut self, now: Instant) {
+        self.updated_at = now;
+        if self.status == OrderStatus::Cancelled {
+            return;
+        }
+        let was_live = self.status == OrderStatus::Live;
+        self.status = if !self.original_size.is_zero() && self.matched() >= self.original_size {
+            OrderStatus::Filled
+        } else if self.matched() > Decimal::ZERO {
+            OrderStatus::PartiallyFilled
+        } else {
+            OrderStatus::Live
+        };
+        if was_live && self.status != OrderStatus::Live {
+            metrics::order_filled(side_label(self.side));
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Fill {
+    pub trade_id: String,
+    pub order_id: String,
+    pub token_id: String,
+    pub side: Side,
+    pub price: Decimal,
+    pub size: Decimal,
+    pub fee: Decimal, // in USDC, only takers pay
+}
+
+// Our view of every order and fill, fed by the authenticated user channel.
+// Trades arrive several times as they move MATCHED -> MINED -> CONFIRMED, each is counted once
+// and reversed if it later comes back FAILED.
+pub struct OrderTracker {
+    api_key: ApiKey,
+    clock: SharedClock,
+    orders: HashMap<String, TrackedOrder>,
+    fills: HashMap<String, Vec<Fill>>, // trade id -> our side(s) of that trade
+}
+
+impl OrderTracker {
+    pub fn new(api_key: ApiKey, clock: SharedClock) -> Self {
+        OrderTracker { api_key, clock, orders: HashMap::new(), fills: HashMap::new() }
+    }
+
+    pub fn shared(api_key: ApiKey, clock: SharedClock) -> SharedTracker {
+        Arc::new(Mutex::new(Self::new(api_key, clock)))
+    }
+
+    // registers an order from the post_order response so fills can be matched to it
+    // even if the user channel's placement message is late
+    pub fn record_post(&mut self, order_id: &str, token_id: &str, side: Side, price: Decimal, size: Decimal) {
+        metrics::order_placed(side_label(side));
+        let now = self.clock.instant();
+        self.orders.entry(order_id.to_string()).or_insert_with(|| TrackedOrder {
+            order_id: order_id.to_string(),
+            token_id: token_id.to_string(),
+            side,
+            price,
+            original_size: size,
+            size_matched: Decimal::ZERO,
+            filled: Decimal::ZERO,
+            status: OrderStatus::Live,
+            updated_at: now,
+        });
+    }
+
+    pub fn on_order(&mut self, msg: &OrderMessage) {
+        let now = self.clock.instant();
+        let order = self.orders.entry(msg.id.clone()).or_insert_with(|| TrackedOrder {
+            order_id: msg.id.clone(),
+            token_id: msg.asset_id.clone(),
+            side: msg.side,
+            price: msg.price,
+            original_size: msg.original_size.unwrap_or_default(),
+            size_matched: Decimal::ZERO,
+            filled: Decimal::ZERO,
+            status: OrderStatus::Live,
+            updated_at: now,
+        });
+
+        if let Some(original_size) = msg.original_size {
+            order.original_size = original_size;
+        }
+        if let Some(size_matched) = msg.size_matched {
+            order.size_matched = order.size_matched.max(size_matched);
+        }
+        if msg.msg_type.as_deref() == Some("CANCELLATION") {
+            order.status = OrderStatus::Cancelled;
+        }
+        order.refresh_status(now);
+    }
+
+    // returns our new fills, with negated sizes for trades that came back FAILED
+    pub fn on_trade(&mut self, msg: &TradeMessage) -> Vec<Fill> {
+        let status = msg.status.to_uppercase();
+
+        if status == "FAILED" {
+            let Some(fills) = self.fills.remove(&msg.id) else {
+                return Vec::new();
+            };
+            return fills.into_iter().map(|mut fill| {
+                self.apply(&fill, -fill.size);
+                fill.size = -fill.size;
+                fill.fee = -fill.fee;
+                fill
+            }).collect();
+        }
+
+        if self.fills.contains_key(&msg.id) {
+            return Vec::new();
+        }
+
+        let mut fills = Vec::new();
+
+        let taker_order_id = msg.taker_order_id.clone().unwrap_or_default();
+        if msg.trade_owner == Some(self.api_key) || self.orders.contains_key(&taker_order_id) {
+            fills.push(Fill {
+                trade_id: msg.id.clone(),
+                order_id: taker_order_id,
+                token_id: msg.asset_id.clone(),
+                side: msg.side,
+                price: msg.price,
+                size: msg.size,
+                fee: taker_fee(msg),
+            });
+        }
+
+        for maker in msg.maker_orders.iter().filter(|m| m.owner == self.api_key) {
+            // the maker side isn't on the wire, take it from the order we placed
+            let side = match self.orders.get(&maker.order_id) {
+                Some(order) => order.side,
+                None if maker.asset_id == msg.asset_id => opposite(msg.side),
+                None => msg.side,
+            };
+            fills.push(Fill {
+                trade_id: msg.id.clone(),
+                order_id: maker.order_id.clone(),
+                token_id: maker.asset_id.clone(),
+                side,
+                price: maker.price,
+                size: maker.matched_amount,
+                fee: Decimal::ZERO,
+            });
+        }
+
+        for fill in &fills {
+            self.apply(fill, fill.size);
+        }
+        self.fills.insert(msg.id.clone(), fills.clone());
+        fills
+    }
+
+    fn apply(&mut self, fill: &Fill, size: Decimal) {
+        if let Some(order) = self.orders.get_mut(&fill.order_id) {
+            order.filled += size;
+            order.refresh_status(self.clock.instant());
+        }
+    }
+
+    pub fn order(&self, order_id: &str) -> Option<&TrackedOrder> {
+        self.orders.get(order_id)
+    }
+
+    pub fn open_orders(&self) -> impl Iterator<Item = &TrackedOrder> {
+        self.orders.values().filter(|o| o.is_open())
+    }
+
+    // the n most recently updated orders, newest first
+    pub fn recent(&self, n: usize) -> Vec<TrackedOrder> {
+        let mut orders: Vec<_> = self.orders.values().cloned().collect();
+        orders.sort_by_key(|o| std::cmp::Reverse(o.updated_at));
+        orders.truncate(n);
+        orders
+    }
+}
+
+// polymarket charges takers rate * min(p, 1 - p) per share
+fn taker_fee(msg: &TradeMessage) -> Decimal {
+    let bps = msg.fee_rate_bps.as_deref().and_then(|b| Decimal::from_str(b).ok()).unwrap_or_default();
+    bps / Decimal::from(10_000) * msg.price.min(Decimal::ONE - msg.price) * msg.size
+}
+
+pub fn side_label(side: Side) -> &'static str {
+    match side {
+        Side::Buy => "buy",
+        Side::Sell => "sell",
+        _ => "unknown",
+    }
+}
+
+fn opposite(side: Side) -> Side {
+    match side {
+        Side::Buy => Side::Sell,
+        Side::Sell => Side::Buy,
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SimulatedClock;
+    use serde_json::{Value, json};
+
+    const OURS: &str = "00000000-0000-0000-0000-000000000001";
+    const THEIRS: &str = "00000000-0000-0000-0000-000000000002";
+
+    fn tracker() -> OrderTracker {
+        OrderTracker::new(ApiKey::from_str(OURS).unwrap(), Arc::new(SimulatedClock::new(1_700_000_000_000)))
+    }
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    // a trade of 10 yes shares at 0.40, the taker buying from one maker order
+    fn trade(status: &str, taker_owner: &str, maker: Value) -> TradeMessage {
+        serde_json::from_value(json!({
+            "id": "trade-1",
+            "market": "market",
+            "asset_id": "yes",
+            "side": "BUY",
+            "size": "10",
+            "price": "0.40",
+            "status": status,
+            "trade_owner": taker_owner,
+            "taker_order_id": "taker-order",
+            "maker_orders": [maker],
+            "fee_rate_bps": "1000",
+        })).unwrap()
+    }
+
+    fn maker(order_id: &str, owner: &str, asset_id: &str, price: &str) -> Value {
+        json!({ "asset_id": asset_id, "matched_amount": "10", "order_id": order_id, "outcome": "Yes", "owner": owner, "price": price })
+    }
+
+    #[test]
+    fn a_trade_counts_once_across_its_statuses() {
+        let mut tracker = tracker();
+        tracker.record_post("taker-order", "yes", Side::Buy, dec("0.40"), dec("10"));
+        let theirs = maker("their-order", THEIRS, "yes", "0.40");
+
+        let fills = tracker.on_trade(&trade("MATCHED", OURS, theirs.clone()));
+        assert_eq!(fills.len(), 1);
+        assert_eq!((fills[0].side, fills[0].size, fills[0].order_id.as_str()), (Side::Buy, dec("10"), "taker-order"));
+        assert!(tracker.on_trade(&trade("MINED", OURS, theirs.clone())).is_empty());
+        assert!(tracker.on_trade(&trade("CONFIRMED", OURS, theirs)).is_empty());
+
+        let order = tracker.order("taker-order").unwrap();
+        assert_eq!(order.filled, dec("10"));
+        assert_eq!(order.status, OrderStatus::Filled);
+    }
+
+    #[test]
+    fn a_failed_trade_is_reversed() {
+        let mut tracker = tracker();
+        tracker.record_post("taker-order", "yes", Side::Buy, dec("0.40"), dec("10"));
+        let theirs = maker("their-order", THEIRS, "yes", "0.40");
+        tracker.on_trade(&trade("MATCHED", OURS, theirs.clone()));
+
+        let reversed = tracker.on_trade(&trade("FAILED", OURS, theirs.clone()));
+        assert_eq!(reversed.len(), 1);
+        assert_eq!(reversed[0].size, dec("-10"));
+        assert_eq!(reversed[0].fee, -taker_fee(&trade("MATCHED", OURS, theirs.clone())));
+        assert_eq!(tracker.order("taker-order").unwrap().filled, Decimal::ZERO);
+        assert_eq!(tracker.order("taker-order").unwrap().status, OrderStatus::Live);
+
+        // only once, and a failure for a trade never seen is nothing
+        assert!(tracker.on_trade(&trade("FAILED", OURS, theirs.clone())).is_empty());
+        let mut fresh = self::tracker();
+        assert!(fresh.on_trade(&trade("FAILED", OURS, theirs)).is_empty());
+    }
+
+    #[test]
+    fn maker_fills_take_their_side_and_size() {
+        // our resting sell of yes, taken by a buyer
+        let mut tracker = tracker();
+        tracker.record_post("our-order", "yes", Side::Sell, dec("0.40"), dec("20"));
+        let fills = tracker.on_trade(&trade("MATCHED", THEIRS, maker("our-order", OURS, "yes", "0.40")));
+        assert_eq!(fills.len(), 1);
+        assert_eq!((fills[0].side, fills[0].size, fills[0].fee), (Side::Sell, dec("10"), Decimal::ZERO));
+        assert_eq!(tracker.order("our-order").unwrap().status, OrderStatus::PartiallyFilled);
+
+        // an order the tracker never saw on the taker's token is the other side of it
+        let mut tracker = self::tracker();
+        let fills = tracker.on_trade(&trade("MATCHED", THEIRS, maker("unknown", OURS, "yes", "0.40")));
+        assert_eq!(fills[0].side, Side::Sell);
+
+        // on the complementary token, a buy matched against a buy
+        let mut tracker = self::tracker();
+        let fills = tracker.on_trade(&trade("MATCHED", THEIRS, maker("unknown", OURS, "no", "0.60")));
+        assert_eq!((fills[0].side, fills[0].token_id.as_str(), fills[0].price), (Side::Buy, "no", dec("0.60")));
+
+        // someone else's trade isn't ours
+        let mut tracker = self::tracker();
+        assert!(tracker.on_trade(&trade("MATCHED", THEIRS, maker("their-order", THEIRS, "yes", "0.40"))).is_empty());
+    }
+
+    #[test]
+    fn takers_pay_the_rate_on_the_cheaper_side() {
+        // 10% of min(0.40, 0.60) on 10 shares
+        assert_eq!(taker_fee(&trade("MATCHED", OURS, maker("o", THEIRS, "yes", "0.40"))), dec("0.4"));
+        let mut expensive = trade("MATCHED", OURS, maker("o", THEIRS, "yes", "0.90"));
+        expensive.price = dec("0.90");
+        assert_eq!(taker_fee(&expensive), dec("0.1"));
+        expensive.fee_rate_bps = None;
+        assert_eq!(taker_fee(&expensive), Decimal::ZERO);
+    }
+
+    #[test]
+    fn order_updates_keep_the_largest_match_and_cancellations() {
+        let mut tracker = tracker();
+        let update = |kind: &str, matched: &str| -> OrderMessage {
+            serde_json::from_value(json!({
+                "id": "order", "market": "market", "asset_id": "yes", "side": "BUY", "price": "0.40",
+                "type": kind, "original_size": "10", "size_matched": matched,
+            })).unwrap()
+        };
+        tracker.on_order(&update("PLACEMENT", "0"));
+        assert_eq!(tracker.order("order").unwrap().status, OrderStatus::Live);
+        tracker.on_order(&update("UPDATE", "4"));
+        tracker.on_order(&update("UPDATE", "3"));
+        assert_eq!(tracker.order("order").unwrap().matched(), dec("4"));
+        assert_eq!(tracker.order("order").unwrap().status, OrderStatus::PartiallyFilled);
+        tracker.on_order(&update("CANCELLATION", "4"));
+        assert!(!tracker.order("order").unwrap().is_open());
+        assert_eq!(tracker.open_orders().count(), 0);
+    }
+}
@@ -0,0 +1,228 @@
+use std::fmt;
+use tracing::{info, warn};
+
+use crate::config::FeedConfig;
+use crate::get_trend::VENUES;
+use crate::metrics;
+
+// Keeps bad prices out of the fused one. A single tick that jumps away from the venue's last
+// price and from the other venues is dropped. A venue that keeps disagreeing with the others'
+// median for exclude_after_ms, a stuck feed or a quote currency off peg, is left out of the
+// fusion until it's agreed again after exclude_for_ms.
+pub struct OutlierFilter {
+    max_jump_bps: f64,
+    max_deviation_bps: f64,
+    exclude_after_ms: u64,
+    exclude_for_ms: u64,
+    venues: Vec<VenueState>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct VenueState {
+    deviating_since: Option<u64>,
+    excluded_until: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TickRejection {
+    NotAPrice { price: f64 },
+    Jump { price: f64, jump_bps: f64, deviation_bps: f64 },
+}
+
+impl fmt::Display for TickRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TickRejection::NotAPrice { price } => write!(f, "{price} is not a price"),
+            TickRejection::Jump { price, jump_bps, deviation_bps } => {
+                write!(f, "{price:.2} jumped {jump_bps:.1}bps from the venue's last price and is {deviation_bps:.1}bps off the others")
+            }
+        }
+    }
+}
+
+// the other venues' median a venue is held to needs at least this many behind it
+const MIN_VENUES_FOR_MEDIAN: usize = 2;
+
+impl OutlierFilter {
+    pub fn new(config: &FeedConfig) -> Self {
+        OutlierFilter {
+            max_jump_bps: config.max_jump_bps,
+            max_deviation_bps: config.max_deviation_bps,
+            exclude_after_ms: config.exclude_after_ms,
+            exclude_for_ms: config.exclude_for_ms,
+            venues: vec![VenueState::default(); VENUES.len()],
+        }
+    }
+
+    // A tick from `venue` before it's taken in, against the latest prices of the live venues.
+    // A jump the other venues made too is the market moving and passes.
+    pub fn check_tick(&self, venue: usize, price: f64, prices: &[f64], live: &[bool]) -> Result<(), TickRejection> {
+        if !(price.is_finite() && price > 0.0) {
+            return Err(TickRejection::NotAPrice { price });
+        }
+        if !live[venue] {
+            return Ok(());
+        }
+        let jump_bps = bps(price, prices[venue]);
+        if jump_bps <= self.max_jump_bps {
+            return Ok(());
+        }
+        let Some(median) = self.median_of_others(venue, prices, live) else {
+            return Ok(());
+        };
+        let deviation_bps = bps(price, median);
+        if deviation_bps <= self.max_deviation_bps {
+            return Ok(());
+        }
+        Err(TickRejection::Jump { price, jump_bps, deviation_bps })
+    }
+
+    // moves each live venue in or out of the fusion by how it compares to the others
+    pub fn update(&mut self, time_ms: u64, prices: &[f64], live: &[bool]) {
+        for venue in 0..self.venues.len() {
+            let deviation_bps = live[venue].then(|| self.median_of_others(venue, prices, live)).flatten()
+                .map(|median| bps(prices[venue], median));
+            let Some(deviation_bps) = deviation_bps else {
+                self.venues[venue].deviating_since = None;
+                continue;
+            };
+            let agrees = deviation_bps <= self.max_deviation_bps;
+
+            let state = &mut self.venues[venue];
+            if let Some(until) = state.excluded_until {
+                if time_ms >= until && agrees {
+                    state.excluded_until = None;
+                    state.deviating_since = None;
+                    info!(venue = VENUES[venue], deviation_bps = format!("{deviation_bps:.1}"), "venue back in the fused price");
+                }
+                continue;
+            }
+            if agrees {
+                state.deviating_since = None;
+                continue;
+            }
+            let since = *state.deviating_since.get_or_insert(time_ms);
+            if time_ms.saturating_sub(since) >= self.exclude_after_ms {
+                state.excluded_until = Some(time_ms + self.exclude_for_ms);
+                metrics::VENUE_EXCLUSIONS.with_label_values(&[VENUES[venue]]).inc();
+                warn!(venue = VENUES[venue], price = prices[venue], deviation_bps = format!("{deviation_bps:.1}"),
+                    for_ms = time_ms - since, max_bps = self.max_deviation_bps,
+                    "dropping venue from the fused price, it disagrees with the others' median");
+            }
+        }
+    }
+
+    pub fn is_excluded(&self, venue: usize) -> bool {
+        self.venues[venue].excluded_until.is_some()
+    }
+
+    // excluded venues still count, the median is robust to one bad price and they have to be
+    // measured against the rest to come back
+    fn median_of_others(&self, venue: usize, prices: &[f64], live: &[bool]) -> Option<f64> {
+        let mut others: Vec<f64> = (0..prices.len()).filter(|i| *i != venue && live[*i]).map(|i| prices[i]).collect();
+        if others.len() < MIN_VENUES_FOR_MEDIAN {
+            return None;
+        }
+        others.sort_by(f64::total_cmp);
+        let mid = others.len() / 2;
+        Some(if others.len().is_multiple_of(2) { (others[mid - 1] + others[mid]) / 2.0 } else { others[mid] })
+    }
+}
+
+fn bps(price: f64, reference: f64) -> f64 {
+    (price / reference - 1.0).abs() * 10_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: u64 = 1_700_000_000_000;
+
+    // every venue live at $60,000, with the default limits: 50bps jumps, 25bps off the median,
+    // excluded after 5s for at least 60s
+    fn market() -> (OutlierFilter, Vec<f64>, Vec<bool>) {
+        (OutlierFilter::new(&FeedConfig::default()), vec![60_000.0; VENUES.len()], vec![true; VENUES.len()])
+    }
+
+    #[test]
+    fn a_single_venue_jump_is_rejected() {
+        let (filter, prices, live) = market();
+
+        // 1% up on one venue alone
+        let rejection = filter.check_tick(0, 60_600.0, &prices, &live).unwrap_err();
+        let TickRejection::Jump { jump_bps, deviation_bps, .. } = rejection else {
+            panic!("{rejection:?}");
+        };
+        assert!((jump_bps - 100.0).abs() < 1e-6 && (deviation_bps - 100.0).abs() < 1e-6);
+
+        // a small move passes, and so does anything from a venue not yet live
+        assert_eq!(filter.check_tick(0, 60_100.0, &prices, &live), Ok(()));
+        let mut not_live = live.clone();
+        not_live[0] = false;
+        assert_eq!(filter.check_tick(0, 60_600.0, &prices, &not_live), Ok(()));
+        assert!(matches!(filter.check_tick(0, f64::NAN, &prices, &live), Err(TickRejection::NotAPrice { .. })));
+    }
+
+    #[test]
+    fn a_market_wide_jump_is_accepted() {
+        let (filter, mut prices, live) = market();
+
+        // the others have already moved 1%, the last venue catching up is the market
+        for price in prices.iter_mut().skip(1) {
+            *price = 60_600.0;
+        }
+        assert_eq!(filter.check_tick(0, 60_600.0, &prices, &live), Ok(()));
+    }
+
+    #[test]
+    fn a_deviating_venue_is_excluded_after_exclude_after_ms() {
+        let (mut filter, mut prices, live) = market();
+        prices[3] = 60_300.0; // 50bps off
+
+        filter.update(START, &prices, &live);
+        filter.update(START + 4_999, &prices, &live);
+        assert!(!filter.is_excluded(3));
+        filter.update(START + 5_000, &prices, &live);
+        assert!(filter.is_excluded(3));
+        assert!((0..VENUES.len()).filter(|v| *v != 3).all(|v| !filter.is_excluded(v)));
+
+        // agreeing again resets the clock before it's excluded
+        let (mut filter, mut prices, live) = market();
+        prices[3] = 60_300.0;
+        filter.update(START, &prices, &live);
+        prices[3] = 60_000.0;
+        filter.update(START + 3_000, &prices, &live);
+        prices[3] = 60_300.0;
+        filter.update(START + 6_000, &prices, &live);
+        assert!(!filter.is_excluded(3));
+        filter.update(START + 11_000, &prices, &live);
+        assert!(filter.is_excluded(3));
+    }
+
+    #[test]
+    fn readmission_needs_exclude_for_ms_and_agreement() {
+        let (mut filter, mut prices, live) = market();
+        prices[3] = 60_300.0;
+        filter.update(START, &prices, &live);
+        filter.update(START + 5_000, &prices, &live);
+        assert!(filter.is_excluded(3));
+        let until = START + 5_000 + 60_000;
+
+        // agreeing early isn't enough
+        prices[3] = 60_000.0;
+        filter.update(until - 1, &prices, &live);
+        assert!(filter.is_excluded(3));
+
+        // nor is the time served while it still disagrees
+        prices[3] = 60_300.0;
+        filter.update(until, &prices, &live);
+        filter.update(until + 30_000, &prices, &live);
+        assert!(filter.is_excluded(3));
+
+        // both together let it back in
+        prices[3] = 60_010.0;
+        filter.update(until + 30_001, &prices, &live);
+        assert!(!filter.is_excluded(3));
+    }
+}
@@ -0,0 +1,364 @@
+use polymarket_client_sdk::types::Decimal;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::time::Duration;
+use tracing::{Instrument, info, info_span, warn};
+
+use crate::clock::SharedClock;
+use crate::config::{Config, EntryMode};
+use crate::error::{self, Result};
+use crate::get_price_info::{self, BookTop};
+use crate::get_trend::{self, TrendSignal, VenueSnapshot};
+use crate::health::{MarketHealth, WsStatus};
+use crate::recorder::Recorder;
+use crate::shutdown::{self, Shutdown};
+use crate::strategy::{self, Tick};
+use crate::supervisor::{Scope, Supervisor};
+use crate::util_functions::{WINDOW_S, get_token_ids, window_slug, window_start};
+
+#[derive(Debug, Clone)]
+pub struct PaperTrade {
+    pub slug: String,
+    pub up: bool,
+    pub shares: f64,
+    pub entry: f64,
+    pub exit: f64,
+    pub fees: f64,
+    pub pnl: f64, // after fees
+    pub exit_reason: &'static str,
+}
+
+#[derive(Debug, Clone)]
+pub enum PaperEvent {
+    Entered { slug: String, up: bool, shares: f64, price: f64, fee: f64 },
+    Closed(PaperTrade),
+}
+
+impl fmt::Display for PaperEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let side = |up: bool| if up { "up" } else { "down" };
+        match self {
+            PaperEvent::Entered { slug, up, shares, price, fee } => {
+                write!(f, "{slug}: bought {shares:.2} {} at {price:.3} (fee {fee:.4})", side(*up))
+            }
+            PaperEvent::Closed(t) => write!(f, "{}: closed {:.2} {} at {:.3} from {:.3}, {} (pnl {:+.4})",
+                t.slug, t.shares, side(t.up), t.exit, t.entry, t.exit_reason, t.pnl),
+        }
+    }
+}
+
+struct OpenTrade {
+    slug: String,
+    up: bool,
+    shares: f64,
+    entry: f64,
+    fee: f64,
+    floor: f64,
+    entered_ms: u64,
+    window_end_s: u64,
+    last_mid: f64, // of the side held, what the shares are marked at if the window closes on them
+}
+
+// Simulates taker entries and exits on a stream of ticks: buy at the ask, hold for hold_s, then
+// sell at the bid once it's at or above the floor, the same rules the live loop trades by.
+// Recordings don't carry the market's resolution, so shares still held when their window closes
+// are marked at the last mid. Time comes from `clock`, replays move a simulated one along with
+// the ticks.
+pub struct PaperTrader {
+    config: Arc<Config>,
+    clock: SharedClock,
+    open: Option<OpenTrade>,
+    pub trades: Vec<PaperTrade>,
+}
+
+impl PaperTrader {
+    pub fn new(config: Arc<Config>, clock: SharedClock) -> Self {
+        if config.entry_mode == EntryMode::Maker {
+            warn!("paper trading simulates taker entries only, maker settings are ignored");
+        }
+        PaperTrader { config, clock, open: None, trades: Vec::new() }
+    }
+
+    pub fn on_tick(&mut self, tick: &Tick) -> Vec<PaperEvent> {
+        let mut events = Vec::new();
+        let (now_ms, now_s) = (self.clock.now_ms(), self.clock.now_s());
+
+        if let Some(mut open) = self.open.take() {
+            if open.slug != tick.slug || now_s >= open.window_end_s {
+                let mark = open.last_mid;
+                events.push(self.close(open, mark, 0.0, "held to window close"));
+            } else {
+                if let Some((bid, ask)) = tick.side_quotes(open.up) {
+                    open.last_mid = (bid + ask) / 2.0;
+                }
+                let held_long_enough = now_ms >= open.entered_ms + self.config.taker.hold_s * 1000;
+                let before_deadline = now_s + self.config.exit.deadline_lead_s < open.window_end_s;
+                match tick.side_quotes(open.up) {
+                    Some((bid, _)) if held_long_enough && before_deadline && bid >= open.floor => {
+                        let fee = self.fee(bid, open.shares);
+                        events.push(self.close(open, bid, fee, "sold"));
+                    }
+                    // still holding, the live loop doesn't look for entries while it holds
+                    _ => self.open = Some(open),
+                }
+                return events;
+            }
+        }
+
+        if let Some(entered) = self.try_enter(tick) {
+            events.push(entered);
+        }
+        events
+    }
+
+    fn try_enter(&mut self, tick: &Tick) -> Option<PaperEvent> {
+        let seconds_in = self.clock.now_s() % WINDOW_S;
+        if !strategy::in_window(seconds_in, &self.config) || tick.health.is_some() {
+            return None;
+        }
+        let up = strategy::signal(&tick.trend, seconds_in, &self.config)?;
+        let mid = tick.mid()?;
+        if !strategy::in_band(mid, &self.config) {
+            return None;
+        }
+        let (side_bid, ask) = tick.side_quotes(up)?;
+
+        let size_usdc = to_f64(self.config.taker.size_usdc);
+        let shares = size_usdc / ask;
+        let fee = self.fee(ask, shares);
+        let entry_mid = (side_bid + ask) / 2.0;
+        let floor = (entry_mid - to_f64(self.config.exit.floor_below_entry)).max(0.01);
+
+        self.open = Some(OpenTrade {
+            slug: tick.slug.clone(),
+            up,
+            shares,
+            entry: ask,
+            fee,
+            floor,
+            entered_ms: self.clock.now_ms(),
+            window_end_s: tick.window_end_s,
+            last_mid: entry_mid,
+        });
+        Some(PaperEvent::Entered { slug: tick.slug.clone(), up, shares, price: ask, fee })
+    }
+
+    fn close(&mut self, open: OpenTrade, exit: f64, exit_fee: f64, exit_reason: &'static str) -> PaperEvent {
+        let fees = open.fee + exit_fee;
+        let trade = PaperTrade {
+            slug: open.slug,
+            up: open.up,
+            shares: open.shares,
+            entry: open.entry,
+            exit,
+            fees,
+            pnl: (exit - open.entry) * open.shares - fees,
+            exit_reason,
+        };
+        self.trades.push(trade.clone());
+        PaperEvent::Closed(trade)
+    }
+
+    // same formula as the live taker fee in orders.rs
+    fn fee(&self, price: f64, shares: f64) -> f64 {
+        self.config.paper.fee_rate_bps / 10_000.0 * price.min(1.0 - price) * shares
+    }
+
+    // closes anything still open at its last mark, for the end of a replay
+    pub fn finish(&mut self) -> Option<PaperEvent> {
+        let open = self.open.take()?;
+        let mark = open.last_mid;
+        Some(self.close(open, mark, 0.0, "marked at the end of the data"))
+    }
+
+    pub fn summary(&self) -> Summary {
+        Summary::of(&self.trades)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Summary {
+    pub trades: usize,
+    pub wins: usize,
+    pub fees: f64,
+    pub pnl: f64,
+}
+
+impl Summary {
+    pub fn of(trades: &[PaperTrade]) -> Self {
+        Summary {
+            trades: trades.len(),
+            wins: trades.iter().filter(|t| t.pnl > 0.0).count(),
+            // folded from +0.0, an empty f64 sum is -0.0 and prints as such
+            fees: trades.iter().fold(0.0, |sum, t| sum + t.fees),
+            pnl: trades.iter().fold(0.0, |sum, t| sum + t.pnl),
+        }
+    }
+
+    pub fn add(&mut self, other: &Summary) {
+        self.trades += other.trades;
+        self.wins += other.wins;
+        self.fees += other.fees;
+        self.pnl += other.pnl;
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let win_rate = if self.trades > 0 { 100.0 * self.wins as f64 / self.trades as f64 } else { 0.0 };
+        write!(f, "{} trades, {} won ({:.0}%), fees {:.4}, pnl {:+.4} after fees",
+            self.trades, self.wins, win_rate, self.fees, self.pnl)
+    }
+}
+
+fn to_f64(d: Decimal) -> f64 {
+    d.to_string().parse().unwrap_or_default()
+}
+
+// Runs the strategy on the live feeds with simulated fills, no keys needed, until ctrl-c.
+pub async fn run(config: Arc<Config>, clock: SharedClock, record: Option<PathBuf>, supervisor: Supervisor, mut shutdown: Shutdown) -> Result<()> {
+    let (tx_trend, mut rx_trend) = watch::channel(TrendSignal::default());
+    let (tx_venues, rx_venues) = watch::channel(VenueSnapshot::default());
+    let restart = Duration::from_millis(config.feeds.reconnect_ms);
+    let (config_for_trend, clock_for_trend) = (config.clone(), clock.clone());
+    let supervisor_for_trend = supervisor.clone();
+    supervisor.spawn("trend", Scope::Run, restart, move || {
+        get_trend::connect(config_for_trend.clone(), clock_for_trend.clone(), supervisor_for_trend.clone(), tx_trend.clone(), tx_venues.clone())
+    });
+
+    let mut recorder = record.as_deref().map(Recorder::create).transpose()?;
+    let mut trader = PaperTrader::new(config.clone(), clock.clone());
+
+    while !*shutdown.borrow() {
+        let now_s = clock.now_s();
+        if !strategy::in_window(now_s % WINDOW_S, &config) {
+            shutdown::pause(&mut shutdown, Duration::from_secs(5)).await;
+            continue;
+        }
+
+        let start = window_start(now_s);
+        let slug = window_slug(start);
+        let tokens = match get_token_ids(&slug).await {
+            Ok(t) => t,
+            Err(e) => {
+                error::handled(&format!("skipping {slug}"), &e);
+                shutdown::pause(&mut shutdown, Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+
+        let span = info_span!("window", slug = %slug, up = %tokens[0], down = %tokens[1], paper = true);
+        let (tx_book, rx_book) = watch::channel(BookTop::default());
+        let (up_token, clock_for_book) = (tokens[0].clone(), clock.clone());
+        span.in_scope(|| supervisor.spawn("book", Scope::Window, restart, move || {
+            let (tx, token, clock) = (tx_book.clone(), up_token.clone(), clock_for_book.clone());
+            async move { get_price_info::connect(tx, &token, clock).await }
+        }));
+
+        async {
+            info!("window opened");
+            loop {
+                let changed = tokio::select! {
+                    biased;
+                    _ = shutdown::requested(&mut shutdown) => break,
+                    changed = rx_trend.changed() => changed,
+                };
+                if changed.is_err() {
+                    break;
+                }
+                if !strategy::in_window(clock.now_s() % WINDOW_S, &config) {
+                    break;
+                }
+
+                let book = *rx_book.borrow();
+                let book_ws = if supervisor.is_running("book") { WsStatus::Connected } else { WsStatus::Closed };
+                let health = MarketHealth::snapshot(&rx_venues.borrow(), &book, book_ws, &*clock, &config);
+                let tick = Tick::observe(&*clock, &slug, start + WINDOW_S, rx_trend.borrow().clone(), &health, &book, &config);
+
+                if let Some(recorder) = recorder.as_mut()
+                    && let Err(e) = recorder.record(&tick) {
+                    error::handled("recording tick failed", &e);
+                }
+                for event in trader.on_tick(&tick) {
+                    info!(%event, "paper trade");
+                    if matches!(event, PaperEvent::Closed(_)) {
+                        info!(summary = %trader.summary(), "paper results so far");
+                    }
+                }
+            }
+        }.instrument(span).await;
+        supervisor.cancel(Scope::Window);
+    }
+    supervisor.cancel_all();
+
+    if let Some(event) = trader.finish() {
+        info!(%event, "paper trade");
+    }
+    info!(summary = %trader.summary(), "paper results");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, SimulatedClock};
+
+    // a window boundary, 1_700_000_100 being a multiple of 900
+    const BOUNDARY_S: u64 = 1_700_000_100;
+
+    fn tick(clock: &dyn Clock, window_end_s: u64, bid: f64, ask: f64) -> Tick {
+        let trend = TrendSignal { slope: 50.0, z_score: 50.0, r_squared: 0.9, points: 20, ..TrendSignal::default() };
+        Tick {
+            ts_ms: clock.now_ms(),
+            slug: window_slug(window_end_s - WINDOW_S),
+            window_end_s,
+            trend,
+            live_venues: 5,
+            bid: Some(bid),
+            ask: Some(ask),
+            health: None,
+        }
+    }
+
+    fn trader(clock: &Arc<SimulatedClock>) -> PaperTrader {
+        PaperTrader::new(Arc::new(Config::default()), clock.clone())
+    }
+
+    #[test]
+    fn sells_after_hold_s() {
+        let clock = Arc::new(SimulatedClock::new((BOUNDARY_S - 600) * 1000));
+        let mut paper = trader(&clock);
+
+        assert!(matches!(paper.on_tick(&tick(&*clock, BOUNDARY_S, 0.50, 0.52))[..], [PaperEvent::Entered { up: true, .. }]));
+        clock.set((BOUNDARY_S - 598) * 1000);
+        assert!(paper.on_tick(&tick(&*clock, BOUNDARY_S, 0.60, 0.62)).is_empty(), "held for hold_s first");
+        clock.set((BOUNDARY_S - 597) * 1000);
+        let events = paper.on_tick(&tick(&*clock, BOUNDARY_S, 0.60, 0.62));
+        assert!(matches!(&events[..], [PaperEvent::Closed(PaperTrade { exit_reason: "sold", exit, .. })] if *exit == 0.60));
+    }
+
+    #[test]
+    fn holds_into_the_window_close_and_rolls_over() {
+        let clock = Arc::new(SimulatedClock::new((BOUNDARY_S - 600) * 1000));
+        let mut paper = trader(&clock);
+        paper.on_tick(&tick(&*clock, BOUNDARY_S, 0.50, 0.52));
+
+        // under the floor, then inside the sell deadline, so nothing sells
+        clock.set((BOUNDARY_S - 100) * 1000);
+        assert!(paper.on_tick(&tick(&*clock, BOUNDARY_S, 0.30, 0.32)).is_empty());
+        clock.set((BOUNDARY_S - 10) * 1000);
+        assert!(paper.on_tick(&tick(&*clock, BOUNDARY_S, 0.70, 0.72)).is_empty());
+
+        // the first tick of the next window closes it at the last mark and doesn't enter, it's too early
+        clock.set(BOUNDARY_S * 1000);
+        let events = paper.on_tick(&tick(&*clock, BOUNDARY_S + WINDOW_S, 0.50, 0.52));
+        assert!(matches!(&events[..], [PaperEvent::Closed(PaperTrade { exit_reason: "held to window close", exit, .. })]
+            if (*exit - 0.71).abs() < 1e-9));
+
+        clock.set((BOUNDARY_S + 10) * 1000);
+        let events = paper.on_tick(&tick(&*clock, BOUNDARY_S + WINDOW_S, 0.50, 0.52));
+        assert!(matches!(&events[..], [PaperEvent::Entered { slug, .. }] if *slug == window_slug(BOUNDARY_S)));
+    }
+}
@@ -0,0 +1,366 @@
+use alloy_primitives::U256;
+use polymarket_client_sdk::clob::types::{AssetType, Side};
+use polymarket_client_sdk::clob::types::request::BalanceAllowanceRequest;
+use polymarket_client_sdk::types::{Address, Decimal};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::clock::SharedClock;
+use crate::error::{self, AccountError, Result};
+use crate::maker::ClobClient;
+use crate::metrics;
+use crate::orders::Fill;
+
+pub type SharedPositions = Arc<Mutex<Positions>>;
+
+// polymarket's conditional tokens (ERC1155) on polygon
+const CTF_ADDRESS: &str = "0x4D97DCd97eC945f40cF65F87097ACe5EA0476045";
+const DEFAULT_POLYGON_RPC: &str = "https://polygon-rpc.com";
+const SHARE_DECIMALS: u32 = 6;
+const USDC_DECIMALS: u32 = 6;
+const TOLERANCE: &str = "0.01"; // differences below a lot are rounding, not drift
+const SETTLE_S: u64 = 10; // the CLOB balance lags fresh fills, leave recently traded tokens alone
+const DAY_S: u64 = 86_400;
+
+#[derive(Debug, Clone)]
+pub struct Discrepancy {
+    pub token_id: String,
+    pub ledger: Decimal,
+    pub clob: Decimal,
+    pub on_chain: Option<Decimal>,
+}
+
+// shares held per token, built from our own fills and corrected by reconcile()
+pub struct Positions {
+    clock: SharedClock,
+    held: HashMap<String, Decimal>,
+    cost: HashMap<String, Decimal>, // USDC paid for the shares still held
+    last_fill: HashMap<String, Instant>,
+    pub realised_pnl: Decimal, // sells against average cost, before fees
+    pub fees: Decimal,
+    day: u64, // UTC days since the epoch, of the fills in day_pnl and day_fees
+    day_pnl: Decimal,
+    day_fees: Decimal,
+}
+
+impl Positions {
+    pub fn shared(clock: SharedClock) -> SharedPositions {
+        Arc::new(Mutex::new(Positions {
+            clock,
+            held: HashMap::new(),
+            cost: HashMap::new(),
+            last_fill: HashMap::new(),
+            realised_pnl: Decimal::ZERO,
+            fees: Decimal::ZERO,
+            day: 0,
+            day_pnl: Decimal::ZERO,
+            day_fees: Decimal::ZERO,
+        }))
+    }
+
+    pub fn apply(&mut self, fill: &Fill) {
+        let today = self.clock.now_s() / DAY_S;
+        if today != self.day {
+            self.day = today;
+            self.day_pnl = Decimal::ZERO;
+            self.day_fees = Decimal::ZERO;
+        }
+        let shares = self.held.entry(fill.token_id.clone()).or_default();
+        let cost = self.cost.entry(fill.token_id.clone()).or_default();
+        match fill.side {
+            Side::Buy => {
+                *shares += fill.size;
+                *cost += fill.price * fill.size;
+            }
+            Side::Sell => {
+                // a sell of shares the ledger doesn't have (a missed buy, or drift reconcile hasn't
+                // caught) has no cost to book against, only the shares held count
+                let held = (*shares).max(Decimal::ZERO);
+                let sold = if fill.size > held {
+                    warn!(token_id = %fill.token_id, %held, size = %fill.size, "sell larger than the position, booking pnl on the shares held");
+                    held
+                } else {
+                    fill.size
+                };
+                let average = if held.is_zero() { Decimal::ZERO } else { *cost / held };
+                *shares -= sold;
+                *cost -= average * sold;
+                self.realised_pnl += (fill.price - average) * sold;
+                self.day_pnl += (fill.price - average) * sold;
+            }
+            _ => {}
+        }
+        self.fees += fill.fee;
+        self.day_fees += fill.fee;
+        self.last_fill.insert(fill.token_id.clone(), self.clock.instant());
+
+        metrics::REALISED_PNL.set(self.realised_pnl.to_string().parse().unwrap_or_default());
+        metrics::FEES.set(self.fees.to_string().parse().unwrap_or_default());
+    }
+
+    // realised pnl before fees and the fees, of the fills since the last UTC midnight
+    pub fn today(&self) -> (Decimal, Decimal) {
+        if self.clock.now_s() / DAY_S == self.day { (self.day_pnl, self.day_fees) } else { (Decimal::ZERO, Decimal::ZERO) }
+    }
+
+    pub fn settling(&self, token_id: &str) -> bool {
+        self.last_fill.get(token_id).is_some_and(|t| self.clock.instant().duration_since(*t) < Duration::from_secs(SETTLE_S))
+    }
+
+    pub fn held(&self, token_id: &str) -> Decimal {
+        self.held.get(token_id).copied().unwrap_or_default()
+    }
+
+    // the cost basis is scaled with the corrected share count, the average cost stays the same
+    pub fn set(&mut self, token_id: &str, shares: Decimal) {
+        let previous = self.held.insert(token_id.to_string(), shares).unwrap_or_default();
+        if let Some(cost) = self.cost.get_mut(token_id) {
+            *cost = if previous > Decimal::ZERO { *cost * shares.max(Decimal::ZERO) / previous } else { Decimal::ZERO };
+        }
+    }
+
+    pub fn tokens(&self) -> Vec<String> {
+        self.held.keys().cloned().collect()
+    }
+}
+
+// Checks the ledger against the CLOB balance and the on-chain ERC1155 balance of every token we
+// know about, plus anything the data api says the funder holds. The CLOB balance is what can
+// actually be sold, so it overwrites the ledger when they disagree.
+pub async fn reconcile(
+    client: &ClobClient,
+    positions: &SharedPositions,
+    funder: Address,
+    extra_tokens: &[String],
+) -> Result<Vec<Discrepancy>> {
+    let mut tokens = positions.lock().unwrap().tokens();
+    tokens.extend(extra_tokens.iter().cloned());
+    match held_per_data_api(funder).await {
+        Ok(held) => tokens.extend(held),
+        Err(e) => error::handled("skipping data api positions in reconciliation", &e),
+    }
+    tokens.sort();
+    tokens.dedup();
+
+    let tolerance = Decimal::from_str(TOLERANCE).unwrap();
+    let mut discrepancies = Vec::new();
+
+    for token in tokens {
+        if positions.lock().unwrap().settling(&token) {
+            continue;
+        }
+        // a resolved or closed market's token can fail the lookup, it mustn't stop the others
+        let clob = match clob_balance(client, &token).await {
+            Ok(b) => b,
+            Err(e) => {
+                error::handled("skipping a token in reconciliation", &e);
+                continue;
+            }
+        };
+        let ledger = positions.lock().unwrap().held(&token);
+
+        if (clob - ledger).abs() < tolerance || positions.lock().unwrap().settling(&token) {
+            continue;
+        }
+
+        let on_chain = match on_chain_balance(funder, &token).await {
+            Ok(b) => Some(b),
+            Err(e) => {
+                error::handled("reconciling without the on-chain balance", &e);
+                None
+            }
+        };
+
+        warn!(token_id = %token, %ledger, %clob, on_chain = %on_chain.map(|b| b.to_string()).unwrap_or("-".into()),
+            "position drift, taking the CLOB balance");
+        positions.lock().unwrap().set(&token, clob);
+        discrepancies.push(Discrepancy { token_id: token, ledger, clob, on_chain });
+    }
+
+    Ok(discrepancies)
+}
+
+pub async fn clob_balance(client: &ClobClient, token_id: &str) -> Result<Decimal> {
+    let request = BalanceAllowanceRequest::builder()
+        .asset_type(AssetType::Conditional)
+        .token_id(token_id)
+        .build();
+    let response = client.balance_allowance(request).await
+        .map_err(|source| AccountError::ClobBalance { token_id: token_id.to_string(), source })?;
+    Ok(response.balance / Decimal::from(10u64.pow(SHARE_DECIMALS)))
+}
+
+// the funder's USDC as the CLOB sees it, and what each exchange contract may spend of it
+#[derive(Debug, Clone)]
+pub struct Collateral {
+    pub balance: Decimal,
+    pub allowances: Vec<(Address, Option<Decimal>)>, // None for allowances too large to count, usually the max approval
+}
+
+pub async fn collateral(client: &ClobClient) -> Result<Collateral> {
+    let request = BalanceAllowanceRequest::builder()
+        .asset_type(AssetType::Collateral)
+        .build();
+    let response = client.balance_allowance(request).await.map_err(AccountError::Collateral)?;
+    let scale = Decimal::from(10u64.pow(USDC_DECIMALS));
+    let mut allowances: Vec<_> = response.allowances.iter()
+        .map(|(spender, raw)| (*spender, Decimal::from_str(raw).ok().map(|a| a / scale)))
+        .collect();
+    allowances.sort_by_key(|(spender, _)| *spender);
+    Ok(Collateral { balance: response.balance / scale, allowances })
+}
+
+// ERC1155 balanceOf(owner, id) through eth_call
+pub async fn on_chain_balance(owner: Address, token_id: &str) -> Result<Decimal> {
+    let on_chain_err = |reason: String| AccountError::OnChain { token_id: token_id.to_string(), reason };
+
+    let id = U256::from_str_radix(token_id, 10).map_err(|e| on_chain_err(e.to_string()))?;
+    let owner = owner.to_string().trim_start_matches("0x").to_lowercase();
+    let data = format!("0x00fdd58e{:0>64}{:064x}", owner, id);
+
+    let raw = eth_call(CTF_ADDRESS, data).await.map_err(on_chain_err)?;
+    let raw = Decimal::from_str(&raw.to_string()).map_err(|e| on_chain_err(e.to_string()))?;
+    Ok(raw / Decimal::from(10u64.pow(SHARE_DECIMALS)))
+}
+
+// ERC1155 isApprovedForAll(owner, operator), whether the exchange may move the owner's shares
+pub async fn tokens_approved(owner: Address, operator: Address) -> Result<bool> {
+    let owner = owner.to_string().trim_start_matches("0x").to_lowercase();
+    let operator = operator.to_string().trim_start_matches("0x").to_lowercase();
+    let data = format!("0xe985e9c5{:0>64}{:0>64}", owner, operator);
+    let approved = eth_call(CTF_ADDRESS, data).await.map_err(AccountError::Approval)?;
+    Ok(!approved.is_zero())
+}
+
+async fn eth_call(to: &str, data: String) -> std::result::Result<U256, String> {
+    let rpc = std::env::var("POLYGON_RPC_URL").unwrap_or(DEFAULT_POLYGON_RPC.to_string());
+    let body = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "eth_call",
+        "params": [{ "to": to, "data": data }, "latest"]
+    });
+    let response = async { reqwest::Client::new().post(rpc).json(&body).send().await?.json::<Value>().await }
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let Some(result) = response["result"].as_str() else {
+        return Err(format!("eth_call failed: {}", response["error"]));
+    };
+    U256::from_str_radix(result.trim_start_matches("0x"), 16).map_err(|e| e.to_string())
+}
+
+// token ids the funder holds according to the data api, catches positions opened outside the bot
+async fn held_per_data_api(funder: Address) -> Result<Vec<String>> {
+    let url = format!("https://data-api.polymarket.com/positions?user={}&sizeThreshold=0.01", funder);
+    let response = async { reqwest::Client::new().get(url).send().await?.json::<Value>().await }
+        .await
+        .map_err(AccountError::DataApi)?;
+
+    Ok(response.as_array()
+        .map(|positions| positions.iter().filter_map(|p| p["asset"].as_str().map(String::from)).collect())
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SimulatedClock;
+
+    const START_MS: u64 = 1_700_000_000_000;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    fn fill(side: Side, price: &str, size: &str, fee: &str) -> Fill {
+        Fill {
+            trade_id: "trade".to_string(),
+            order_id: "order".to_string(),
+            token_id: "yes".to_string(),
+            side,
+            price: dec(price),
+            size: dec(size),
+            fee: dec(fee),
+        }
+    }
+
+    fn positions() -> (Arc<SimulatedClock>, SharedPositions) {
+        let clock = Arc::new(SimulatedClock::new(START_MS));
+        let positions = Positions::shared(clock.clone());
+        (clock, positions)
+    }
+
+    #[test]
+    fn sells_book_against_the_average_cost() {
+        let (_, positions) = positions();
+        let mut positions = positions.lock().unwrap();
+        positions.apply(&fill(Side::Buy, "0.40", "10", "0.1"));
+        positions.apply(&fill(Side::Buy, "0.60", "10", "0"));
+        assert_eq!(positions.held("yes"), dec("20"));
+
+        // a partial sell at 0.70 against the 0.50 average, the rest keeps its cost
+        positions.apply(&fill(Side::Sell, "0.70", "5", "0.05"));
+        assert_eq!(positions.held("yes"), dec("15"));
+        assert_eq!(positions.realised_pnl, dec("1"));
+        assert_eq!(positions.fees, dec("0.15"));
+        positions.apply(&fill(Side::Sell, "0.40", "15", "0"));
+        assert_eq!(positions.held("yes"), Decimal::ZERO);
+        assert_eq!(positions.realised_pnl, dec("-0.5"));
+        assert_eq!(positions.today(), (dec("-0.5"), dec("0.15")));
+    }
+
+    #[test]
+    fn an_oversell_books_no_phantom_pnl() {
+        let (_, positions) = positions();
+        let mut positions = positions.lock().unwrap();
+        positions.apply(&fill(Side::Sell, "0.70", "10", "0"));
+        assert_eq!(positions.realised_pnl, Decimal::ZERO);
+        assert_eq!(positions.held("yes"), Decimal::ZERO);
+
+        positions.apply(&fill(Side::Buy, "0.50", "4", "0"));
+        positions.apply(&fill(Side::Sell, "0.70", "10", "0"));
+        assert_eq!(positions.realised_pnl, dec("0.8"));
+        assert_eq!(positions.held("yes"), Decimal::ZERO);
+    }
+
+    #[test]
+    fn reconciling_keeps_the_average_cost() {
+        let (_, positions) = positions();
+        let mut positions = positions.lock().unwrap();
+        positions.apply(&fill(Side::Buy, "0.40", "10", "0"));
+
+        // the CLOB says 5 shares, the 0.40 average stays
+        positions.set("yes", dec("5"));
+        assert_eq!(positions.held("yes"), dec("5"));
+        positions.apply(&fill(Side::Sell, "0.50", "5", "0"));
+        assert_eq!(positions.realised_pnl, dec("0.5"));
+
+        // shares the ledger never saw bought come in at no cost
+        positions.set("no", dec("3"));
+        assert_eq!(positions.held("no"), dec("3"));
+        assert!(positions.tokens().contains(&"no".to_string()));
+    }
+
+    #[test]
+    fn todays_pnl_resets_at_utc_midnight() {
+        let (clock, positions) = positions();
+        let mut positions = positions.lock().unwrap();
+        positions.apply(&fill(Side::Buy, "0.40", "10", "0.1"));
+        positions.apply(&fill(Side::Sell, "0.50", "10", "0"));
+        assert_eq!(positions.today(), (dec("1"), dec("0.1")));
+        assert!(positions.settling("yes"));
+
+        let next_day_ms = (START_MS / 1000 / DAY_S + 1) * DAY_S * 1000;
+        clock.set(next_day_ms);
+        assert_eq!(positions.today(), (Decimal::ZERO, Decimal::ZERO));
+        assert!(!positions.settling("yes"));
+        positions.apply(&fill(Side::Buy, "0.40", "10", "0.2"));
+        assert_eq!(positions.today(), (Decimal::ZERO, dec("0.2")));
+        assert_eq!((positions.realised_pnl, positions.fees), (dec("1"), dec("0.3")));
+    }
+}
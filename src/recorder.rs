@@ -0,0 +1,50 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use crate::error::{IoError, Result};
+use crate::strategy::Tick;
+
+// Appends ticks as JSON lines, the input to replay and backtest.
+pub struct Recorder {
+    path: String,
+    out: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)
+            .map_err(|e| IoError::Write { path: path.display().to_string(), reason: e.to_string() })?;
+        Ok(Recorder { path: path.display().to_string(), out: BufWriter::new(file) })
+    }
+
+    // on the way out, gets what's been written onto the disk
+    pub fn finish(mut self) -> Result<()> {
+        self.out.flush().and_then(|_| self.out.get_ref().sync_all())
+            .map_err(|e| IoError::Write { path: self.path.clone(), reason: e.to_string() }.into())
+    }
+
+    // flushed per tick so a killed run keeps everything up to its last update
+    pub fn record(&mut self, tick: &Tick) -> Result<()> {
+        let write = serde_json::to_writer(&mut self.out, tick).map_err(std::io::Error::from)
+            .and_then(|_| self.out.write_all(b"\n"))
+            .and_then(|_| self.out.flush());
+        write.map_err(|e| IoError::Write { path: self.path.clone(), reason: e.to_string() }.into())
+    }
+}
+
+pub fn read(path: &Path) -> Result<Vec<Tick>> {
+    let file_err = |reason: String| IoError::Read { path: path.display().to_string(), reason };
+    let file = File::open(path).map_err(|e| file_err(e.to_string()))?;
+
+    let mut ticks = Vec::new();
+    for (n, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| file_err(e.to_string()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let tick = serde_json::from_str(&line).map_err(|e| file_err(format!("line {}: {e}", n + 1)))?;
+        ticks.push(tick);
+    }
+    Ok(ticks)
+}
@@ -0,0 +1,143 @@
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::error::{IoError, Result};
+
+// What a LOG_FILE says happened: windows traded, fills and their cash flow, exits and problems.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub first: Option<String>,
+    pub last: Option<String>,
+    pub windows: usize,
+    pub entries: usize,
+    pub buys: usize,
+    pub sells: usize,
+    pub reversals: usize, // fills taken back when their trade FAILED, their cash is netted out of the totals
+    pub bought_usdc: f64,
+    pub sold_usdc: f64,
+    pub fees: f64,
+    pub exits: BTreeMap<&'static str, usize>,
+    pub warnings: usize,
+    pub errors: BTreeMap<String, usize>, // handled errors by kind
+}
+
+impl Report {
+    pub fn read(path: &Path) -> Result<Self> {
+        let file_err = |reason: String| IoError::Read { path: path.display().to_string(), reason };
+        let file = File::open(path).map_err(|e| file_err(e.to_string()))?;
+
+        let mut report = Report::default();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| file_err(e.to_string()))?;
+            // lines from before LOG_FILE was JSON, or cut off by a crash, are skipped
+            if let Ok(event) = serde_json::from_str::<Value>(&line) {
+                report.add(&event);
+            }
+        }
+        Ok(report)
+    }
+
+    fn add(&mut self, event: &Value) {
+        let fields = &event["fields"];
+        let number = |name: &str| fields[name].as_str().and_then(|v| v.parse::<f64>().ok()).unwrap_or_default();
+
+        if let Some(ts) = event["timestamp"].as_str() {
+            self.first.get_or_insert_with(|| ts.to_string());
+            self.last = Some(ts.to_string());
+        }
+        if event["level"] == "WARN" {
+            self.warnings += 1;
+            if let Some(kind) = fields["kind"].as_str() {
+                *self.errors.entry(kind.to_string()).or_default() += 1;
+            }
+        }
+
+        match fields["message"].as_str().unwrap_or_default() {
+            "window opened" => self.windows += 1,
+            "bought" | "maker fill" => self.entries += 1,
+            "fill" => {
+                let size = number("size");
+                let notional = size * number("price");
+                let reversal = size < 0.0;
+                match fields["side"].as_str() {
+                    Some("Buy") => {
+                        self.buys += usize::from(!reversal);
+                        self.bought_usdc += notional;
+                    }
+                    Some("Sell") => {
+                        self.sells += usize::from(!reversal);
+                        self.sold_usdc += notional;
+                    }
+                    _ => {}
+                }
+                self.reversals += usize::from(reversal);
+                self.fees += number("fee");
+            }
+            "exit" => {
+                let outcome = fields["outcome"].as_str().unwrap_or_default();
+                let kind = if outcome.starts_with("fully exited") {
+                    "fully exited"
+                } else if outcome.starts_with("partial exit") {
+                    "partial"
+                } else {
+                    "stranded"
+                };
+                *self.exits.entry(kind).or_default() += 1;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "from {} to {}", self.first.as_deref().unwrap_or("-"), self.last.as_deref().unwrap_or("-"))?;
+        writeln!(f, "windows opened: {}", self.windows)?;
+        writeln!(f, "entries: {}", self.entries)?;
+        writeln!(f, "fills: {} buys for {:.4} usdc, {} sells for {:.4} usdc, {} reversed", self.buys, self.bought_usdc, self.sells, self.sold_usdc, self.reversals)?;
+        writeln!(f, "fees: {:.4}", self.fees)?;
+        // shares held into resolution pay out later and aren't in the log
+        writeln!(f, "net cash: {:+.4} (before resolution payouts)", self.sold_usdc - self.bought_usdc - self.fees)?;
+        let exits: Vec<_> = self.exits.iter().map(|(kind, n)| format!("{kind} {n}")).collect();
+        writeln!(f, "exits: {}", if exits.is_empty() { "-".to_string() } else { exits.join(", ") })?;
+        let errors: Vec<_> = self.errors.iter().map(|(kind, n)| format!("{kind} {n}")).collect();
+        write!(f, "warnings: {} (handled errors: {})", self.warnings, if errors.is_empty() { "-".to_string() } else { errors.join(", ") })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_fixture_log() {
+        let report = Report::read(&Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/report.log")).unwrap();
+
+        assert_eq!(report.first.as_deref(), Some("2026-01-05T10:00:01.000000Z"));
+        assert_eq!(report.last.as_deref(), Some("2026-01-05T10:14:50.000000Z"));
+        assert_eq!((report.windows, report.entries), (2, 2));
+        // the failed buy stays a buy, its reversal is counted on its own
+        assert_eq!((report.buys, report.sells, report.reversals), (3, 1, 1));
+        assert!((report.bought_usdc - 4.0).abs() < 1e-9);
+        assert!((report.sold_usdc - 5.0).abs() < 1e-9);
+        assert!((report.fees - 0.24).abs() < 1e-9);
+        assert_eq!(report.exits.get("fully exited"), Some(&1));
+        assert_eq!(report.exits.get("stranded"), Some(&1));
+        assert_eq!(report.warnings, 2);
+        assert_eq!(report.errors.get("feed"), Some(&1));
+
+        let shown = report.to_string();
+        assert!(shown.contains("fills: 3 buys for 4.0000 usdc, 1 sells for 5.0000 usdc, 1 reversed"), "{shown}");
+        assert!(shown.contains("net cash: +0.7600"), "{shown}");
+    }
+
+    #[test]
+    fn a_missing_log_is_an_io_error() {
+        let e = Report::read(Path::new("/nonexistent/report.log")).unwrap_err();
+        assert_eq!(e.kind().as_str(), "io");
+    }
+}
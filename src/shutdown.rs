@@ -0,0 +1,60 @@
+use tokio::sync::watch;
+use tokio::time::{Duration, sleep};
+use tracing::warn;
+
+// Set once when the bot is asked to stop, by a signal or the dashboard's quit key. Loops check it
+// between steps, so a trade that has started still gets its exit.
+pub type Shutdown = watch::Receiver<bool>;
+
+// Listens for ctrl-c and SIGTERM. The first one asks for a graceful shutdown, a second one exits
+// straight away for when that gets stuck.
+pub fn listen() -> watch::Sender<bool> {
+    let (tx, _) = watch::channel(false);
+    let tx_signal = tx.clone();
+    tokio::spawn(async move {
+        let mut requested = false;
+        loop {
+            if signal().await.is_err() {
+                warn!("can't listen for signals, ctrl-c will kill the bot without cleaning up");
+                return;
+            }
+            if requested || *tx_signal.borrow() {
+                warn!("second stop request, exiting now");
+                std::process::exit(130);
+            }
+            requested = true;
+            warn!("shutting down, press ctrl-c again to exit immediately");
+            tx_signal.send_replace(true);
+        }
+    });
+    tx
+}
+
+// resolves once a shutdown has been asked for, never if the sender is gone without asking
+pub async fn requested(shutdown: &mut Shutdown) {
+    if shutdown.wait_for(|stop| *stop).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+// sleeps for `duration` unless a shutdown comes first, true if it did
+pub async fn pause(shutdown: &mut Shutdown, duration: Duration) -> bool {
+    tokio::select! {
+        _ = sleep(duration) => false,
+        _ = requested(shutdown) => true,
+    }
+}
+
+#[cfg(unix)]
+async fn signal() -> std::io::Result<()> {
+    let mut term = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result,
+        _ = term.recv() => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+async fn signal() -> std::io::Result<()> {
+    tokio::signal::ctrl_c().await
+}
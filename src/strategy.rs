@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+
+use crate::clock::Clock;
+use crate::config::{Config, Threshold};
+use crate::get_price_info::BookTop;
+use crate::get_trend::TrendSignal;
+use crate::health::MarketHealth;
+
+// Everything the entry rule looks at on one trend update. Live and paper runs can record these,
+// replay and backtest feed them back through the same rule.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tick {
+    pub ts_ms: u64,
+    pub slug: String,
+    pub window_end_s: u64,
+    pub trend: TrendSignal,
+    pub live_venues: usize,
+    pub bid: Option<f64>, // the up token's book
+    pub ask: Option<f64>,
+    pub health: Option<String>, // why the feeds were unhealthy, None if orders were allowed
+}
+
+impl Tick {
+    pub fn observe(clock: &dyn Clock, slug: &str, window_end_s: u64, trend: TrendSignal, health: &MarketHealth, book: &BookTop, config: &Config) -> Self {
+        Tick {
+            ts_ms: clock.now_ms(),
+            slug: slug.to_string(),
+            window_end_s,
+            trend,
+            live_venues: health.live_venues,
+            bid: book.bid,
+            ask: book.ask,
+            health: health.check(config).err().map(|rejection| rejection.to_string()),
+        }
+    }
+
+    pub fn mid(&self) -> Option<f64> {
+        Some((self.bid? + self.ask?) / 2.0)
+    }
+
+    // best bid and ask on the side being bought, the down book is the mirror of the up book
+    pub fn side_quotes(&self, up: bool) -> Option<(f64, f64)> {
+        let (bid, ask) = (self.bid?, self.ask?);
+        Some(if up { (bid, ask) } else { (1.0 - ask, 1.0 - bid) })
+    }
+}
+
+// Some(true) to buy up, Some(false) to buy down, None inside the limit or on a fit too poor to
+// trust. seconds_in is how far into the window it is.
+pub fn signal(trend: &TrendSignal, seconds_in: u64, config: &Config) -> Option<bool> {
+    if !fit_ok(trend, config) {
+        return None;
+    }
+    let value = match config.strategy.threshold {
+        Threshold::ZScore => trend.z_score,
+        Threshold::Slope => trend.slope,
+    };
+    let limit = limit(seconds_in, config);
+    let up = if value > limit {
+        true
+    } else if value < -limit {
+        false
+    } else {
+        return None;
+    };
+    let agreeing = trend.horizons.iter().filter(|h| if up { h.slope > 0.0 } else { h.slope < 0.0 }).count();
+    (agreeing >= config.strategy.min_agreeing_horizons).then_some(up)
+}
+
+// what the slope (in $/s) or its z-score has to clear, the z-score limit moves linearly from
+// z_limit_open to z_limit_close across the trading part of the window
+pub fn limit(seconds_in: u64, config: &Config) -> f64 {
+    let strategy = &config.strategy;
+    match strategy.threshold {
+        Threshold::Slope => strategy.trend_limit,
+        Threshold::ZScore => {
+            let (open, close) = (config.window.open_after_s, config.window.close_at_s);
+            let through = seconds_in.clamp(open, close).saturating_sub(open) as f64 / (close - open) as f64;
+            strategy.z_limit_open + (strategy.z_limit_close - strategy.z_limit_open) * through
+        }
+    }
+}
+
+fn fit_ok(trend: &TrendSignal, config: &Config) -> bool {
+    trend.points >= config.strategy.min_points && trend.r_squared >= config.strategy.min_r_squared
+}
+
+// whether the window is open for trading, now_s into the window
+pub fn in_window(seconds_in: u64, config: &Config) -> bool {
+    (config.window.open_after_s..=config.window.close_at_s).contains(&seconds_in)
+}
+
+// only enter while the up token's mid is inside the band, outside it the move is priced in
+pub fn in_band(mid: f64, config: &Config) -> bool {
+    mid > config.strategy.min_price && mid < config.strategy.max_price
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_trend::HorizonTrend;
+
+    fn z_config(open: f64, close: f64) -> Config {
+        let mut config = Config::default();
+        config.strategy.threshold = Threshold::ZScore;
+        (config.strategy.z_limit_open, config.strategy.z_limit_close) = (open, close);
+        config
+    }
+
+    #[test]
+    fn z_limit_moves_linearly_across_the_window() {
+        let config = z_config(4.0, 2.0);
+        let (open, close) = (config.window.open_after_s, config.window.close_at_s);
+        assert_eq!(limit(open, &config), 4.0);
+        assert_eq!(limit(close, &config), 2.0);
+        assert!((limit((open + close) / 2, &config) - 3.0).abs() < 1e-12);
+        assert!((limit(open + (close - open) / 4, &config) - 3.5).abs() < 1e-12);
+        // held at the ends outside the trading part
+        assert_eq!(limit(0, &config), 4.0);
+        assert_eq!(limit(899, &config), 2.0);
+    }
+
+    #[test]
+    fn slope_limit_is_fixed() {
+        let config = Config::default();
+        assert_eq!(config.strategy.threshold, Threshold::Slope);
+        assert_eq!(limit(config.window.open_after_s, &config), config.strategy.trend_limit);
+        assert_eq!(limit(config.window.close_at_s, &config), config.strategy.trend_limit);
+    }
+
+    #[test]
+    fn signal_compares_the_configured_value() {
+        let trend = TrendSignal { slope: 5.0, z_score: 2.5, points: 10, ..TrendSignal::default() };
+        let seconds_in = 100;
+        assert_eq!(signal(&trend, seconds_in, &Config::default()), Some(true), "5$/s is over the 4$/s limit");
+        assert_eq!(signal(&trend, seconds_in, &z_config(3.0, 3.0)), None, "z 2.5 is under 3");
+        assert_eq!(signal(&trend, seconds_in, &z_config(2.0, 2.0)), Some(true));
+        let falling = TrendSignal { slope: -5.0, z_score: -2.5, ..trend };
+        assert_eq!(signal(&falling, seconds_in, &z_config(2.0, 2.0)), Some(false));
+    }
+
+    #[test]
+    fn signal_needs_a_good_enough_fit_and_agreeing_horizons() {
+        let mut config = Config::default();
+        let mut trend = TrendSignal { slope: 5.0, points: 1, ..TrendSignal::default() };
+        assert_eq!(signal(&trend, 100, &config), None, "one point is no fit");
+        trend.points = 10;
+        config.strategy.min_r_squared = 0.5;
+        assert_eq!(signal(&trend, 100, &config), None);
+        trend.r_squared = 0.6;
+        assert_eq!(signal(&trend, 100, &config), Some(true));
+
+        config.strategy.min_agreeing_horizons = 1;
+        trend.horizons = vec![HorizonTrend { slope: -1.0, ..HorizonTrend::default() }];
+        assert_eq!(signal(&trend, 100, &config), None);
+        trend.horizons.push(HorizonTrend { slope: 1.0, ..HorizonTrend::default() });
+        assert_eq!(signal(&trend, 100, &config), Some(true));
+    }
+}
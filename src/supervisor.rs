@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::task::{AbortHandle, JoinHandle};
+use tokio::time::{Duration, Instant, sleep};
+use tracing::{Instrument, Span, error, info};
+
+use crate::error::{self, Result};
+use crate::metrics;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    Run, // lives as long as the bot
+    Window, // belongs to the window being traded, cancelled when it rolls over
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    Running,
+    Restarting, // waiting out its backoff after it stopped
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskStatus {
+    pub name: String,
+    pub scope: Scope,
+    pub state: TaskState,
+    pub since: Instant, // when it entered its state
+    pub restarts: u32,
+    pub last_error: Option<String>,
+}
+
+struct Task {
+    id: u64,
+    status: TaskStatus,
+    handle: AbortHandle,
+}
+
+// Owns the bot's background tasks. Every task is kept running, restarted after its backoff
+// whenever it returns, fails or panics, until it's cancelled with its scope or replaced by a task
+// spawned under the same name.
+#[derive(Clone, Default)]
+pub struct Supervisor {
+    tasks: Arc<Mutex<BTreeMap<String, Task>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl Supervisor {
+    pub fn spawn<F, Fut>(&self, name: &str, scope: Scope, backoff: Duration, mut make: F)
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let supervisor = self.clone();
+        let task_name = name.to_string();
+
+        // the lock is held until the task is registered, so its first status update can't miss it
+        let mut tasks = self.tasks.lock().unwrap();
+        let handle = tokio::spawn(async move {
+            loop {
+                supervisor.update(&task_name, id, |status| status.state = TaskState::Running);
+
+                // run as its own task so a panic ends up here rather than killing the supervisor,
+                // and aborted along with this one
+                let mut run = AbortOnDrop(tokio::spawn(make().in_current_span()));
+                let stopped = match (&mut run.0).await {
+                    Ok(Ok(())) => {
+                        info!(task = %task_name, "task stopped, restarting");
+                        None
+                    }
+                    Ok(Err(e)) => {
+                        error::handled(&format!("{task_name} failed, restarting"), &e);
+                        Some(e.to_string())
+                    }
+                    Err(e) => {
+                        error!(task = %task_name, error = %e, "task panicked, restarting");
+                        Some(e.to_string())
+                    }
+                };
+
+                metrics::TASK_RESTARTS.with_label_values(&[&task_name]).inc();
+                supervisor.update(&task_name, id, |status| {
+                    status.state = TaskState::Restarting;
+                    status.restarts += 1;
+                    if stopped.is_some() {
+                        status.last_error = stopped;
+                    }
+                });
+                sleep(backoff).await;
+            }
+        }.instrument(Span::current()));
+
+        let status = TaskStatus {
+            name: name.to_string(),
+            scope,
+            state: TaskState::Running,
+            since: Instant::now(),
+            restarts: 0,
+            last_error: None,
+        };
+        if let Some(replaced) = tasks.insert(name.to_string(), Task { id, status, handle: handle.abort_handle() }) {
+            replaced.handle.abort();
+        }
+    }
+
+    // cancels every task of the scope, e.g. the book feed when the window rolls over
+    pub fn cancel(&self, scope: Scope) {
+        self.tasks.lock().unwrap().retain(|_, task| {
+            if task.status.scope == scope {
+                task.handle.abort();
+            }
+            task.status.scope != scope
+        });
+    }
+
+    pub fn cancel_all(&self) {
+        for (_, task) in std::mem::take(&mut *self.tasks.lock().unwrap()) {
+            task.handle.abort();
+        }
+    }
+
+    pub fn is_running(&self, name: &str) -> bool {
+        self.tasks.lock().unwrap().get(name).is_some_and(|task| task.status.state == TaskState::Running)
+    }
+
+    pub fn statuses(&self) -> Vec<TaskStatus> {
+        self.tasks.lock().unwrap().values().map(|task| task.status.clone()).collect()
+    }
+
+    // a replaced task may still be winding down, it mustn't touch its successor's status
+    fn update(&self, name: &str, id: u64, change: impl FnOnce(&mut TaskStatus)) {
+        if let Some(task) = self.tasks.lock().unwrap().get_mut(name)
+            && task.id == id {
+            let state = task.status.state;
+            change(&mut task.status);
+            if task.status.state != state {
+                task.status.since = Instant::now();
+            }
+        }
+    }
+}
+
+struct AbortOnDrop<T>(JoinHandle<T>);
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
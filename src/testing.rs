@@ -0,0 +1,56 @@
+// Helpers shared by the unit tests.
+
+// A seeded generator, so the synthetic series are the same on every run.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    // splitmix64
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // uniform in (0, 1)
+    pub fn uniform(&mut self) -> f64 {
+        ((self.next_u64() >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+    }
+
+    // standard normal, by Box-Muller
+    pub fn normal(&mut self) -> f64 {
+        (-2.0 * self.uniform().ln()).sqrt() * (std::f64::consts::TAU * self.uniform()).cos()
+    }
+}
+
+// A geometric Brownian motion without drift sampled every step_ms, sigma per √s of the log price.
+pub fn gbm(rng: &mut Rng, start_ms: u64, price: f64, sigma: f64, step_ms: u64, steps: usize) -> Vec<(u64, f64)> {
+    let dt = step_ms as f64 / 1000.0;
+    let mut log_price = price.ln();
+    (0..steps).map(|i| {
+        if i > 0 {
+            log_price += sigma * dt.sqrt() * rng.normal() - sigma * sigma * dt / 2.0;
+        }
+        (start_ms + i as u64 * step_ms, log_price.exp())
+    }).collect()
+}
+
+// ordinary least squares of price on seconds: slope, the fitted price at the first point, r², residual se
+pub fn ols(points: &[(u64, f64)]) -> (f64, f64, f64, f64) {
+    let n = points.len() as f64;
+    let t: Vec<f64> = points.iter().map(|(ms, _)| (*ms - points[0].0) as f64 / 1000.0).collect();
+    let p: Vec<f64> = points.iter().map(|(_, p)| *p).collect();
+    let (mean_t, mean_p) = (t.iter().sum::<f64>() / n, p.iter().sum::<f64>() / n);
+    let s_tt: f64 = t.iter().map(|t| (t - mean_t).powi(2)).sum();
+    let s_tp: f64 = t.iter().zip(&p).map(|(t, p)| (t - mean_t) * (p - mean_p)).sum();
+    let s_pp: f64 = p.iter().map(|p| (p - mean_p).powi(2)).sum();
+    let slope = s_tp / s_tt;
+    let intercept = mean_p - slope * mean_t;
+    let sse: f64 = t.iter().zip(&p).map(|(t, p)| (p - intercept - slope * t).powi(2)).sum();
+    (slope, intercept, 1.0 - sse / s_pp, (sse / (n - 2.0)).sqrt())
+}
@@ -0,0 +1,267 @@
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph, Row, Table};
+use ratatui::Frame;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+use crate::clock::SharedClock;
+use crate::config::Config;
+use crate::get_price_info::BookTop;
+use crate::get_trend::{TrendSignal, VENUES, VenueSnapshot};
+use crate::orders::{SharedTracker, side_label};
+use crate::positions::SharedPositions;
+use crate::supervisor::{Supervisor, TaskState};
+
+const REFRESH_MS: u64 = 250;
+const RECENT_ORDERS: usize = 10;
+
+// the window being traded, sent by the main loop each time it picks up a new market
+#[derive(Debug, Clone)]
+pub struct WindowView {
+    pub slug: String,
+    pub up_token: String,
+    pub down_token: String,
+    pub end_s: u64,
+    pub book: watch::Receiver<BookTop>,
+}
+
+pub struct Dashboard {
+    pub config: Arc<Config>,
+    pub clock: SharedClock,
+    pub venues: watch::Receiver<VenueSnapshot>,
+    pub trend: watch::Receiver<TrendSignal>,
+    pub window: watch::Receiver<Option<WindowView>>,
+    pub positions: SharedPositions,
+    pub tracker: SharedTracker,
+    pub supervisor: Supervisor,
+    pub stop: watch::Sender<bool>,
+}
+
+// Draws the dashboard on its own thread until q or ctrl-c, which asks for the same graceful
+// shutdown ctrl-c does without the dashboard (raw mode swallows the signal). The terminal is handed
+// back as soon as a shutdown starts, for the summary of what is still held.
+pub fn spawn(dashboard: Dashboard) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let mut terminal = ratatui::init();
+        while !*dashboard.stop.borrow() {
+            if terminal.draw(|frame| dashboard.draw(frame)).is_err() {
+                break;
+            }
+            if event::poll(Duration::from_millis(REFRESH_MS)).unwrap_or(false)
+                && let Ok(Event::Key(key)) = event::read()
+                && key.kind == KeyEventKind::Press
+                && (key.code == KeyCode::Char('q')
+                    || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))) {
+                dashboard.stop.send_replace(true);
+            }
+        }
+        ratatui::restore();
+    })
+}
+
+impl Dashboard {
+    fn draw(&self, frame: &mut Frame) {
+        let [header, feeds, account, orders] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length((VENUES.len() + self.config.feeds.horizons_ms.len() + self.config.feeds.flow_windows_ms.len()) as u16 + 7),
+            Constraint::Length(8),
+            Constraint::Min(4),
+        ]).areas(frame.area());
+        let [venues, books] = Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)]).areas(feeds);
+        let [positions, pnl] = Layout::horizontal([Constraint::Percentage(70), Constraint::Percentage(30)]).areas(account);
+        let [orders, tasks] = Layout::horizontal([Constraint::Percentage(65), Constraint::Percentage(35)]).areas(orders);
+
+        let window = self.window.borrow().clone();
+        self.draw_header(frame, header, window.as_ref());
+        self.draw_venues(frame, venues);
+        draw_books(frame, books, window.as_ref(), self.clock.instant());
+        self.draw_positions(frame, positions, window.as_ref());
+        self.draw_pnl(frame, pnl);
+        self.draw_orders(frame, orders);
+        self.draw_tasks(frame, tasks);
+    }
+
+    fn draw_header(&self, frame: &mut Frame, area: Rect, window: Option<&WindowView>) {
+        let now = self.clock.now_s();
+        let text = match window {
+            Some(w) => {
+                let left = w.end_s.saturating_sub(now);
+                format!(" {}  |  {:02}:{:02} left  |  q to quit", w.slug, left / 60, left % 60)
+            }
+            None => " waiting for the next window  |  q to quit".to_string(),
+        };
+        frame.render_widget(Paragraph::new(text).style(Style::new().add_modifier(Modifier::BOLD)), area);
+    }
+
+    fn draw_venues(&self, frame: &mut Frame, area: Rect) {
+        let snapshot = *self.venues.borrow();
+        let trend = self.trend.borrow().clone();
+        let now = self.clock.instant();
+        let max_age = Duration::from_millis(self.config.feeds.max_venue_age_ms);
+
+        let mut rows: Vec<Row> = VENUES.iter().enumerate().map(|(i, venue)| {
+            let age = snapshot.updated[i].map(|t| now.duration_since(t));
+            let style = match age {
+                Some(_) if snapshot.excluded[i] => Style::new().fg(Color::Yellow),
+                Some(age) if age < max_age => Style::new(),
+                _ => Style::new().fg(Color::Red),
+            };
+            Row::new(vec![
+                if snapshot.excluded[i] { format!("{venue} (out)") } else { venue.to_string() },
+                age.map(|_| format!("{:.2}", snapshot.prices[i])).unwrap_or("-".into()),
+                match (age, snapshot.books[i]) {
+                    (Some(a), Some(book)) => format!("{}ms, book {:+.2}", a.as_millis(), book.imbalance()),
+                    (Some(a), None) => format!("{}ms", a.as_millis()),
+                    (None, _) => "never".into(),
+                },
+            ]).style(style)
+        }).collect();
+        rows.push(Row::new(vec![
+            "aggregate".to_string(),
+            snapshot.aggregate.map(|p| format!("{:.2}", p)).unwrap_or("no quorum".into()),
+            String::new(),
+        ]).style(Style::new().add_modifier(Modifier::BOLD)));
+        rows.push(Row::new(vec![
+            "usdt/usd".to_string(),
+            if snapshot.usdt_usd > 0.0 { format!("{:.5}", snapshot.usdt_usd) } else { "-".into() },
+            String::new(),
+        ]));
+        rows.push(Row::new(vec![
+            format!("slope ({}ms)", self.config.feeds.trend_window_ms),
+            format!("{:+.3}$/s", trend.slope),
+            format!("z {:+.2}, r² {:.2}, {} pts", trend.z_score, trend.r_squared, trend.points),
+        ]).style(Style::new().fg(slope_color(trend.slope))));
+        for h in &trend.horizons {
+            rows.push(Row::new(vec![
+                format!("  {}s", h.horizon_ms as f64 / 1000.0),
+                format!("{:+.3}$/s", h.slope),
+                format!("z {:+.2}, r² {:.2}, {} pts", h.z_score, h.r_squared, h.points),
+            ]).style(Style::new().fg(slope_color(h.slope))));
+        }
+        for flow in &trend.order_flow {
+            rows.push(Row::new(vec![
+                format!("flow {}s", flow.window_ms as f64 / 1000.0),
+                format!("{:+.2} vol", flow.volume_imbalance),
+                format!("{:+.2} count, ${:.0}k", flow.count_imbalance, flow.notional / 1000.0),
+            ]).style(Style::new().fg(slope_color(flow.volume_imbalance))));
+        }
+        rows.push(Row::new(vec![
+            "volatility".to_string(),
+            format!("{:.2e}/√s", trend.volatility.per_second()),
+            format!("ewma {:.2e}", trend.volatility.ewma),
+        ]));
+
+        let table = Table::new(rows, [Constraint::Length(16), Constraint::Length(12), Constraint::Min(8)])
+            .header(Row::new(["venue", "price", "age"]).style(Style::new().add_modifier(Modifier::UNDERLINED)))
+            .block(Block::bordered().title(" venues "));
+        frame.render_widget(table, area);
+    }
+
+    fn draw_positions(&self, frame: &mut Frame, area: Rect, window: Option<&WindowView>) {
+        let positions = self.positions.lock().unwrap();
+        let rows: Vec<Row> = positions.tokens().into_iter()
+            .filter(|token| !positions.held(token).is_zero())
+            .map(|token| {
+                let label = match window {
+                    Some(w) if w.up_token == token => "up".to_string(),
+                    Some(w) if w.down_token == token => "down".to_string(),
+                    _ => short(&token),
+                };
+                Row::new(vec![label, positions.held(&token).to_string()])
+            })
+            .collect();
+        let table = Table::new(rows, [Constraint::Min(16), Constraint::Length(14)])
+            .header(Row::new(["token", "shares"]).style(Style::new().add_modifier(Modifier::UNDERLINED)))
+            .block(Block::bordered().title(" positions "));
+        frame.render_widget(table, area);
+    }
+
+    fn draw_pnl(&self, frame: &mut Frame, area: Rect) {
+        let (realised, fees) = self.positions.lock().unwrap().today();
+        let net = realised - fees;
+        let lines = vec![
+            Line::from(format!("realised  {:.4}", realised)),
+            Line::from(format!("fees      {:.4}", fees)),
+            Line::from(Span::styled(
+                format!("net       {:.4}", net),
+                Style::new().fg(if net.is_sign_negative() { Color::Red } else { Color::Green }),
+            )),
+        ];
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(" pnl today (utc) ")), area);
+    }
+
+    fn draw_orders(&self, frame: &mut Frame, area: Rect) {
+        let orders = self.tracker.lock().unwrap().recent(RECENT_ORDERS);
+        let now = self.clock.instant();
+        let rows: Vec<Row> = orders.iter().map(|o| Row::new(vec![
+            format!("{}s ago", now.duration_since(o.updated_at).as_secs()),
+            short(&o.order_id),
+            side_label(o.side).to_string(),
+            o.price.to_string(),
+            o.original_size.to_string(),
+            o.matched().to_string(),
+            format!("{:?}", o.status),
+        ])).collect();
+        let table = Table::new(rows, [
+            Constraint::Length(9),
+            Constraint::Length(14),
+            Constraint::Length(5),
+            Constraint::Length(8),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Min(10),
+        ])
+            .header(Row::new(["updated", "order", "side", "price", "size", "matched", "status"])
+                .style(Style::new().add_modifier(Modifier::UNDERLINED)))
+            .block(Block::bordered().title(" recent orders "));
+        frame.render_widget(table, area);
+    }
+
+    fn draw_tasks(&self, frame: &mut Frame, area: Rect) {
+        let rows: Vec<Row> = self.supervisor.statuses().into_iter().map(|task| {
+            let (state, style) = match task.state {
+                TaskState::Running => ("up", Style::new()),
+                TaskState::Restarting => ("restarting", Style::new().fg(Color::Red)),
+            };
+            Row::new(vec![
+                task.name,
+                state.to_string(),
+                format!("{}s", task.since.elapsed().as_secs()),
+                task.restarts.to_string(),
+                task.last_error.unwrap_or_default(),
+            ]).style(style)
+        }).collect();
+        let table = Table::new(rows, [Constraint::Length(14), Constraint::Length(10), Constraint::Length(7), Constraint::Length(8), Constraint::Min(10)])
+            .header(Row::new(["task", "state", "for", "restarts", "last error"]).style(Style::new().add_modifier(Modifier::UNDERLINED)))
+            .block(Block::bordered().title(" tasks "));
+        frame.render_widget(table, area);
+    }
+}
+
+// the down book is the mirror of the up book
+fn draw_books(frame: &mut Frame, area: Rect, window: Option<&WindowView>, now: Instant) {
+    let book = window.map(|w| *w.book.borrow()).unwrap_or_default();
+    let fmt = |p: Option<f64>| p.map(|p| format!("{:.3}", p)).unwrap_or("-".into());
+    let rows = vec![
+        Row::new(vec!["up".to_string(), fmt(book.bid), fmt(book.ask)]),
+        Row::new(vec!["down".to_string(), fmt(book.ask.map(|a| 1.0 - a)), fmt(book.bid.map(|b| 1.0 - b))]),
+    ];
+    let age = book.received_at.map(|t| format!(" book ({}ms old) ", now.duration_since(t).as_millis())).unwrap_or(" book ".into());
+    let table = Table::new(rows, [Constraint::Length(6), Constraint::Length(8), Constraint::Length(8)])
+        .header(Row::new(["", "bid", "ask"]).style(Style::new().add_modifier(Modifier::UNDERLINED)))
+        .block(Block::bordered().title(age));
+    frame.render_widget(table, area);
+}
+
+fn slope_color(slope: f64) -> Color {
+    if slope > 0.0 { Color::Green } else if slope < 0.0 { Color::Red } else { Color::Reset }
+}
+
+fn short(id: &str) -> String {
+    if id.len() > 12 { format!("{}…", &id[..12]) } else { id.to_string() }
+}
@@ -0,0 +1,55 @@
+use futures::StreamExt as _;
+use polymarket_client_sdk::auth::Normal;
+use polymarket_client_sdk::auth::state::Authenticated;
+use polymarket_client_sdk::clob::ws::Client;
+use polymarket_client_sdk::clob::ws::types::response::WsMessage;
+use tracing::{debug, info};
+
+use crate::error::{self, FeedError, Result};
+use crate::orders::SharedTracker;
+use crate::positions::SharedPositions;
+
+pub type UserWsClient = Client<Authenticated<Normal>>;
+
+const VENUE: &str = "polymarket user channel";
+
+// subscribes to every market on the user channel, feeds order and trade events to the tracker
+// and our fills to the position ledger
+pub async fn connect(
+    client: UserWsClient,
+    tracker: SharedTracker,
+    positions: SharedPositions,
+) -> Result<()> {
+    let stream = client.subscribe_user_events(Vec::new())
+        .map_err(|source| FeedError::Subscribe { venue: VENUE, source })?;
+    let mut stream = Box::pin(stream);
+
+    info!("User channel connected");
+
+    while let Some(msg) = stream.next().await {
+        match msg {
+            Ok(WsMessage::Order(order)) => {
+                debug!(order_id = %order.id,
+                    update = order.msg_type.as_deref().unwrap_or("-"),
+                    size_matched = %order.size_matched.unwrap_or_default(),
+                    original_size = %order.original_size.unwrap_or_default(),
+                    "order update");
+                tracker.lock().unwrap().on_order(&order);
+            }
+            Ok(WsMessage::Trade(trade)) => {
+                let fills = tracker.lock().unwrap().on_trade(&trade);
+                for fill in fills {
+                    positions.lock().unwrap().apply(&fill);
+                    info!(trade_id = %fill.trade_id, status = %trade.status, order_id = %fill.order_id, token_id = %fill.token_id,
+                        side = ?fill.side, size = %fill.size, price = %fill.price, fee = %fill.fee, "fill");
+                }
+            }
+            Ok(_) => {}
+            Err(source) => {
+                error::handled("user channel message dropped", &FeedError::Stream { venue: VENUE, source }.into());
+            }
+        }
+    }
+
+    Ok(())
+}
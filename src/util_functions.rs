@@ -1,24 +1,45 @@
 use serde_json::Value;
 
-pub async fn get_token_ids(event_slug: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+use crate::error::{MarketError, Result};
+
+pub async fn get_token_ids(event_slug: &str) -> Result<Vec<String>> {
     
     let url = format!("https://gamma-api.polymarket.com/events/slug/{}", event_slug);
     
     let client = reqwest::Client::new();
     
-    let response = client.get(url).send().await?.json::<Value>().await?;
+    let http_err = |source| MarketError::Http { slug: event_slug.to_string(), source };
+    let response = client.get(url).send().await.map_err(http_err)?.json::<Value>().await.map_err(http_err)?;
 
     if let Some(raw_tokens_string) = response["markets"][0]["clobTokenIds"].as_str() {
         
         if response["markets"][0]["slug"].as_str() == Some(event_slug) {
             
-            let tokens: Vec<String> = serde_json::from_str(raw_tokens_string)?;
-            return Ok(tokens);
+            let tokens: Vec<String> = serde_json::from_str(raw_tokens_string)
+                .map_err(|source| MarketError::TokenIds { slug: event_slug.to_string(), source })?;
+            if tokens.len() < 2 {
+                return Err(MarketError::NotFound { slug: event_slug.to_string() }.into());
+            }
+            Ok(tokens)
             
         } else {
-            return Err("Wrong market found: Slug mismatch".into());
+            Err(MarketError::SlugMismatch {
+                slug: event_slug.to_string(),
+                found: response["markets"][0]["slug"].as_str().unwrap_or_default().to_string(),
+            }.into())
         }
     } else {
-        return Err("No markets have been found or clobTokenIds are missing".into());
+        Err(MarketError::NotFound { slug: event_slug.to_string() }.into())
     }
-}
\ No newline at end of file
+}
+
+pub const WINDOW_S: u64 = 900;
+
+// the btc up/down market that opened at window_start (unix seconds, a multiple of WINDOW_S)
+pub fn window_slug(window_start: u64) -> String {
+    format!("btc-updown-15m-{}", window_start)
+}
+
+pub fn window_start(now_s: u64) -> u64 {
+    now_s - now_s % WINDOW_S
+}
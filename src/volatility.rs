@@ -0,0 +1,226 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+// Realised volatility of the aggregate, as the standard deviation of its log price per √s. Each
+// estimate is 0 until it has data.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Volatility {
+    pub ewma: f64, // from squared log returns, weighted by feeds.vol_half_life_s
+    pub ranges: Vec<RangeVolatility>, // Parkinson estimates from the high and low of each window
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct RangeVolatility {
+    pub window_ms: u64,
+    pub vol: f64,
+}
+
+impl Volatility {
+    // the largest estimate, so a calm window doesn't hide a burst the others have seen
+    pub fn per_second(&self) -> f64 {
+        self.ranges.iter().map(|r| r.vol).fold(self.ewma, f64::max)
+    }
+
+    // How unusual a fitted slope ($/s over span_ms) is for a random walk at this volatility. The
+    // least squares slope of a random walk over T seconds has a standard deviation of
+    // σ·√(6 / 5T), with σ in $/√s. 0 while the volatility isn't known yet.
+    pub fn z_score(&self, slope: f64, span_ms: u64, price: f64) -> f64 {
+        let sigma = self.per_second() * price;
+        let span_s = span_ms as f64 / 1000.0;
+        if sigma <= 0.0 || span_s <= 0.0 {
+            return 0.0;
+        }
+        slope / (sigma * (6.0 / (5.0 * span_s)).sqrt())
+    }
+}
+
+// Keeps the estimates up to date as aggregates arrive, O(1) per update apart from the range
+// windows' amortised pops.
+pub struct VolatilityEstimator {
+    half_life_s: f64,
+    last: Option<(u64, f64)>,
+    ewma_var: Option<f64>, // per second
+    ranges: Vec<RangeWindow>,
+}
+
+impl VolatilityEstimator {
+    pub fn new(half_life_s: f64, windows_ms: &[u64]) -> Self {
+        VolatilityEstimator {
+            half_life_s,
+            last: None,
+            ewma_var: None,
+            ranges: windows_ms.iter().map(|ms| RangeWindow::new(*ms)).collect(),
+        }
+    }
+
+    pub fn push(&mut self, time_ms: u64, price: f64) {
+        if !(price.is_finite() && price > 0.0) {
+            return;
+        }
+        for range in &mut self.ranges {
+            range.push(time_ms, price);
+        }
+
+        // returns are measured between distinct timestamps, same-millisecond updates fold into the next
+        match self.last {
+            Some((last_ms, last_price)) if time_ms > last_ms => {
+                let dt = (time_ms - last_ms) as f64 / 1000.0;
+                let rate = (price / last_price).ln().powi(2) / dt;
+                let alpha = 1.0 - (-dt * std::f64::consts::LN_2 / self.half_life_s).exp();
+                self.ewma_var = Some(match self.ewma_var {
+                    Some(var) => var + alpha * (rate - var),
+                    None => rate,
+                });
+                self.last = Some((time_ms, price));
+            }
+            Some(_) => {}
+            None => self.last = Some((time_ms, price)),
+        }
+    }
+
+    pub fn snapshot(&self, now_ms: u64) -> Volatility {
+        Volatility {
+            ewma: self.ewma_var.unwrap_or_default().sqrt(),
+            ranges: self.ranges.iter().map(|r| RangeVolatility { window_ms: r.window_ms, vol: r.vol(now_ms) }).collect(),
+        }
+    }
+}
+
+// Parkinson's estimator over a sliding window: σ²·T = ln(high / low)² / (4 ln 2). The high and
+// low come from monotonic queues, so each price is pushed and popped once.
+struct RangeWindow {
+    window_ms: u64,
+    first_ms: Option<u64>,
+    highs: VecDeque<(u64, f64)>,
+    lows: VecDeque<(u64, f64)>,
+}
+
+impl RangeWindow {
+    fn new(window_ms: u64) -> Self {
+        RangeWindow { window_ms, first_ms: None, highs: VecDeque::new(), lows: VecDeque::new() }
+    }
+
+    fn push(&mut self, time_ms: u64, price: f64) {
+        self.first_ms.get_or_insert(time_ms);
+        while self.highs.back().is_some_and(|&(_, p)| p <= price) {
+            self.highs.pop_back();
+        }
+        self.highs.push_back((time_ms, price));
+        while self.lows.back().is_some_and(|&(_, p)| p >= price) {
+            self.lows.pop_back();
+        }
+        self.lows.push_back((time_ms, price));
+
+        let start = time_ms.saturating_sub(self.window_ms);
+        while self.highs.front().is_some_and(|&(t, _)| t < start) {
+            self.highs.pop_front();
+        }
+        while self.lows.front().is_some_and(|&(t, _)| t < start) {
+            self.lows.pop_front();
+        }
+    }
+
+    // over the time actually seen while the window is still filling
+    fn vol(&self, now_ms: u64) -> f64 {
+        let (Some(first_ms), Some(&(_, high)), Some(&(_, low))) = (self.first_ms, self.highs.front(), self.lows.front()) else {
+            return 0.0;
+        };
+        let span_s = now_ms.saturating_sub(first_ms).min(self.window_ms) as f64 / 1000.0;
+        if span_s <= 0.0 {
+            return 0.0;
+        }
+        ((high / low).ln().powi(2) / (4.0 * std::f64::consts::LN_2) / span_s).sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Rng, gbm, ols};
+
+    const SIGMA: f64 = 2e-4; // per √s, about 50% a year like btc
+
+    #[test]
+    fn ewma_recovers_the_volatility_of_a_random_walk() {
+        let mut rng = Rng::new(1);
+        let mut estimator = VolatilityEstimator::new(3600.0, &[]);
+        let series = gbm(&mut rng, 0, 60_000.0, SIGMA, 1000, 36_000);
+        for (t, p) in &series {
+            estimator.push(*t, *p);
+        }
+        let vol = estimator.snapshot(series.last().unwrap().0);
+        assert!((vol.ewma / SIGMA - 1.0).abs() < 0.05, "ewma {} vs {SIGMA}", vol.ewma);
+        assert_eq!(vol.per_second(), vol.ewma);
+    }
+
+    #[test]
+    fn ewma_follows_a_change_in_volatility() {
+        let mut rng = Rng::new(2);
+        let mut estimator = VolatilityEstimator::new(60.0, &[]);
+        let calm = gbm(&mut rng, 0, 60_000.0, SIGMA, 1000, 1200);
+        let (last_ms, last_price) = *calm.last().unwrap();
+        let busy = gbm(&mut rng, last_ms, last_price, 4.0 * SIGMA, 1000, 1200);
+        for (t, p) in calm.iter().chain(&busy[1..]) {
+            estimator.push(*t, *p);
+        }
+        let ewma = estimator.snapshot(busy.last().unwrap().0).ewma;
+        assert!((ewma / (4.0 * SIGMA) - 1.0).abs() < 0.25, "ewma {ewma} vs {}", 4.0 * SIGMA);
+    }
+
+    #[test]
+    fn parkinson_recovers_the_volatility_on_average() {
+        // one range is a noisy estimate, the mean of the variance over many windows isn't
+        let mut rng = Rng::new(3);
+        let paths = 400;
+        let mean_var = (0..paths).map(|_| {
+            let mut estimator = VolatilityEstimator::new(60.0, &[60_000]);
+            let series = gbm(&mut rng, 0, 60_000.0, SIGMA, 50, 1201);
+            for (t, p) in &series {
+                estimator.push(*t, *p);
+            }
+            estimator.snapshot(60_000).ranges[0].vol.powi(2)
+        }).sum::<f64>() / paths as f64;
+        // sampling every 50ms misses a little of the true range
+        let vol = mean_var.sqrt();
+        assert!(vol < SIGMA && vol / SIGMA > 0.9, "parkinson {vol} vs {SIGMA}");
+    }
+
+    #[test]
+    fn range_window_forgets_old_extremes() {
+        let mut window = RangeWindow::new(10_000);
+        window.push(0, 100.0);
+        window.push(1_000, 120.0);
+        window.push(2_000, 80.0);
+        for t in (3_000..=13_000).step_by(1_000) {
+            window.push(t, 100.0 + (t % 2_000) as f64 / 1_000.0);
+        }
+        // the spike at 1s and the dip at 2s have left, 100 and 101 remain
+        assert_eq!(window.highs.front().unwrap().1, 101.0);
+        assert_eq!(window.lows.front().unwrap().1, 100.0);
+        assert_eq!(window.vol(0), 0.0);
+    }
+
+    #[test]
+    fn z_score_is_a_standard_normal_for_a_random_walk() {
+        let mut rng = Rng::new(4);
+        let (paths, span_s, price) = (2000, 300, 60_000.0);
+        let vol = Volatility { ewma: SIGMA, ranges: Vec::new() };
+        let z: Vec<f64> = (0..paths).map(|_| {
+            let series = gbm(&mut rng, 0, price, SIGMA, 1000, span_s + 1);
+            let (slope, ..) = ols(&series);
+            vol.z_score(slope, span_s as u64 * 1000, price)
+        }).collect();
+        let mean = z.iter().sum::<f64>() / paths as f64;
+        let sd = (z.iter().map(|z| (z - mean).powi(2)).sum::<f64>() / paths as f64).sqrt();
+        assert!(mean.abs() < 0.1, "mean {mean}");
+        assert!((sd - 1.0).abs() < 0.06, "sd {sd}");
+    }
+
+    #[test]
+    fn z_score_is_zero_without_a_volatility() {
+        assert_eq!(Volatility::default().z_score(5.0, 60_000, 60_000.0), 0.0);
+        let vol = Volatility { ewma: SIGMA, ranges: vec![RangeVolatility { window_ms: 60_000, vol: 2.0 * SIGMA }] };
+        assert_eq!(vol.per_second(), 2.0 * SIGMA);
+        assert_eq!(vol.z_score(5.0, 0, 60_000.0), 0.0);
+    }
+}
@@ -0,0 +1,142 @@
+use polymarket_client_sdk::{POLYGON, contract_config, derive_proxy_wallet, derive_safe_wallet};
+use polymarket_client_sdk::auth::{Credentials, LocalSigner, Signer};
+use polymarket_client_sdk::clob::{Client, Config as ClobConfig};
+use polymarket_client_sdk::clob::types::{AssetType, SignatureType};
+use polymarket_client_sdk::clob::types::request::UpdateBalanceAllowanceRequest;
+use polymarket_client_sdk::types::{Address, Decimal};
+use std::str::FromStr;
+
+use crate::config::{Config, EntryMode, WalletType};
+use crate::error::{self, AccountError, ConfigError, Result, SigningError};
+use crate::maker::ClobClient;
+use crate::positions;
+
+pub const CLOB_URL: &str = "https://clob.polymarket.com";
+
+// PRIVATE_KEY and FUNDER_KEY from the environment, parsed
+pub fn keys() -> Result<(impl Signer + Send + Sync + 'static, Address)> {
+    let private_key = std::env::var("PRIVATE_KEY").map_err(|_| ConfigError::MissingEnv("PRIVATE_KEY"))?;
+    let funder_key = std::env::var("FUNDER_KEY").map_err(|_| ConfigError::MissingEnv("FUNDER_KEY"))?;
+    let signer = LocalSigner::from_str(&private_key)
+        .map_err(|e| ConfigError::Invalid { name: "PRIVATE_KEY", reason: format!("{e}, it should be your private key starting with 0x") })?
+        .with_chain_id(Some(POLYGON));
+    let funder = funder_key.parse()
+        .map_err(|e| ConfigError::Invalid { name: "FUNDER_KEY", reason: format!("{e}, it should be the public address of your polymarket proxy wallet, starting with 0x") })?;
+    Ok((signer, funder))
+}
+
+// derives the api key for the signer and logs in as the funder's wallet
+pub async fn authenticate<S: Signer>(signer: &S, funder: Address, wallet: WalletType) -> Result<(ClobClient, Credentials)> {
+    let credentials = Client::new(CLOB_URL, ClobConfig::default())
+        .map_err(SigningError::Auth)?
+        .create_or_derive_api_key(signer, None)
+        .await
+        .map_err(SigningError::Credentials)?;
+    let signature_type = match wallet {
+        WalletType::Eoa => SignatureType::Eoa,
+        WalletType::Proxy => SignatureType::Proxy,
+        WalletType::GnosisSafe => SignatureType::GnosisSafe,
+    };
+    let client = Client::new(CLOB_URL, ClobConfig::default())
+        .map_err(SigningError::Auth)?
+        .authentication_builder(signer)
+        .credentials(credentials.clone())
+        .signature_type(signature_type)
+        .funder(funder)
+        .authenticate()
+        .await
+        .map_err(SigningError::Auth)?;
+    Ok((client, credentials))
+}
+
+// What the account looks like before trading. Anything that would make orders fail is in
+// `problems`, phrased as what to fix.
+#[derive(Debug, Clone)]
+pub struct Preflight {
+    pub exchange: Address,
+    pub balance: Decimal, // usdc the clob will let the funder spend
+    pub needed: Decimal, // usdc one entry takes
+    pub usdc_allowance: Option<Decimal>, // None when unlimited
+    pub shares_approved: bool,
+    pub problems: Vec<String>,
+}
+
+impl Preflight {
+    pub fn ok(self) -> Result<Self> {
+        if self.problems.is_empty() {
+            Ok(self)
+        } else {
+            Err(AccountError::Preflight(self.problems).into())
+        }
+    }
+}
+
+pub async fn preflight(client: &ClobClient, signer: Address, funder: Address, config: &Config) -> Result<Preflight> {
+    let mut problems = Vec::new();
+    if let Some(problem) = wallet_problem(signer, funder, config.account.wallet) {
+        problems.push(problem);
+    }
+
+    // the up/down markets aren't neg risk, so it's the plain exchange that takes the orders
+    let exchange = contract_config(POLYGON, false).expect("polygon is configured").exchange;
+
+    // the clob caches balances and allowances, refresh them in case they changed since
+    let refresh = UpdateBalanceAllowanceRequest::builder().asset_type(AssetType::Collateral).build();
+    if let Err(source) = client.update_balance_allowance(refresh).await {
+        error::handled("could not refresh the clob's balance", &AccountError::Collateral(source).into());
+    }
+    let collateral = positions::collateral(client).await?;
+
+    let needed = match config.entry_mode {
+        EntryMode::Taker => config.taker.size_usdc,
+        EntryMode::Maker => config.maker.size_shares * Decimal::try_from(config.strategy.max_price).unwrap_or(Decimal::ONE),
+    };
+    if collateral.balance < needed {
+        problems.push(format!("the tradable balance is {} usdc but one entry takes {needed}, deposit usdc to {funder}", collateral.balance));
+    }
+
+    let usdc_allowance = collateral.allowances.iter().find(|(spender, _)| *spender == exchange).map(|(_, a)| *a);
+    match usdc_allowance {
+        Some(Some(allowance)) if allowance < needed => problems.push(format!(
+            "the exchange {exchange} may only spend {allowance} usdc, enable trading on polymarket.com (or approve it from an eoa wallet)")),
+        None => problems.push(format!(
+            "the exchange {exchange} has no usdc allowance, enable trading on polymarket.com (or approve it from an eoa wallet)")),
+        _ => {}
+    }
+
+    let shares_approved = positions::tokens_approved(funder, exchange).await?;
+    if !shares_approved {
+        problems.push(format!(
+            "the exchange {exchange} can't move the funder's shares so exits would fail, enable trading on polymarket.com (or setApprovalForAll from an eoa wallet)"));
+    }
+
+    Ok(Preflight {
+        exchange,
+        balance: collateral.balance,
+        needed,
+        usdc_allowance: usdc_allowance.flatten(),
+        shares_approved,
+        problems,
+    })
+}
+
+// orders are signed for FUNDER_KEY as account.wallet, which only works if it's that wallet of PRIVATE_KEY
+fn wallet_problem(signer: Address, funder: Address, wallet: WalletType) -> Option<String> {
+    let derived = |wallet: WalletType| match wallet {
+        WalletType::Eoa => Some(signer),
+        WalletType::Proxy => derive_proxy_wallet(signer, POLYGON),
+        WalletType::GnosisSafe => derive_safe_wallet(signer, POLYGON),
+    };
+    if derived(wallet) == Some(funder) {
+        return None;
+    }
+
+    Some(match WalletType::ALL.into_iter().find(|w| derived(*w) == Some(funder)) {
+        Some(WalletType::Eoa) => format!("FUNDER_KEY {funder} is PRIVATE_KEY's own address, set account.wallet = \"eoa\" (it is \"{}\")",
+            wallet.as_str()),
+        Some(actual) => format!("FUNDER_KEY {funder} is the {} wallet of PRIVATE_KEY, set account.wallet = \"{}\" (it is \"{}\")",
+            actual.as_str(), actual.as_str(), wallet.as_str()),
+        None => format!("FUNDER_KEY {funder} isn't a wallet of PRIVATE_KEY's address {signer} (its {} wallet is {}), check both come from the same polymarket account",
+            wallet.as_str(), derived(wallet).map(|a| a.to_string()).unwrap_or("-".to_string())),
+    })
+}
@@ -0,0 +1,211 @@
+use tracing::info;
+
+use crate::config::{FeedConfig, Weighting};
+use crate::get_trend::VENUES;
+use crate::metrics;
+
+// How much each venue counts in the aggregate. The configured weights hold until there's data to
+// do better: the measured weights are blended in as it builds up, fully trusted after a few
+// half-lives.
+pub struct VenueWeighting {
+    mode: Weighting,
+    half_life_ms: f64,
+    sample_ms: u64,
+    configured: Vec<f64>,
+    first_ms: Option<u64>,
+    weights: Vec<f64>,
+    last_log_ms: u64,
+    // volume: each venue's traded notional, decaying with the half-life
+    notional: Vec<f64>,
+    notional_ms: u64,
+    // leadership: venue prices sampled every sample_ms
+    next_sample_ms: u64,
+    prices: Vec<Option<f64>>,
+    leads: Vec<LeadLag>,
+}
+
+// How well a venue's last move predicts the other venues' next one, from exponentially weighted
+// moments of its sampled log returns r and the others' following ones s. Zero-mean returns are
+// assumed, over a second they're noise around zero.
+#[derive(Debug, Clone, Copy, Default)]
+struct LeadLag {
+    last: Option<(f64, f64)>, // log price, the others' mean log price
+    last_return: Option<f64>,
+    rs: f64,
+    rr: f64,
+    ss: f64,
+}
+
+impl LeadLag {
+    fn correlation(&self) -> f64 {
+        let denominator = (self.rr * self.ss).sqrt();
+        if denominator > 0.0 { self.rs / denominator } else { 0.0 }
+    }
+}
+
+const LOG_INTERVAL_MS: u64 = 60_000;
+
+impl VenueWeighting {
+    pub fn new(config: &FeedConfig) -> Self {
+        let configured = config.weights.as_array().to_vec();
+        let configured = normalise(&configured).unwrap_or(configured);
+        let venues = configured.len();
+        let weighting = VenueWeighting {
+            mode: config.weighting,
+            half_life_ms: config.weights_half_life_s * 1000.0,
+            sample_ms: config.lead_lag_ms,
+            weights: configured.clone(),
+            configured,
+            first_ms: None,
+            last_log_ms: 0,
+            notional: vec![0.0; venues],
+            notional_ms: 0,
+            next_sample_ms: 0,
+            prices: vec![None; venues],
+            leads: vec![LeadLag::default(); venues],
+        };
+        weighting.set_gauges();
+        weighting.publish(0.0);
+        weighting
+    }
+
+    pub fn weights(&self) -> &[f64] {
+        &self.weights
+    }
+
+    // a trade of `notional` dollars on the venue, which now has a VWAP of `vwap`
+    pub fn trade(&mut self, venue: usize, time_ms: u64, vwap: f64, notional: f64) {
+        if self.mode == Weighting::Static {
+            return;
+        }
+        let first_ms = *self.first_ms.get_or_insert(time_ms);
+
+        match self.mode {
+            Weighting::Volume => {
+                self.decay_notional(time_ms);
+                if notional.is_finite() && notional > 0.0 {
+                    self.notional[venue] += notional;
+                }
+                let measured = normalise(&self.notional);
+                self.blend(first_ms, time_ms, measured);
+            }
+            Weighting::Leadership => {
+                self.prices[venue] = Some(vwap).filter(|p| p.is_finite() && *p > 0.0);
+                if time_ms < self.next_sample_ms {
+                    return;
+                }
+                self.next_sample_ms = time_ms + self.sample_ms;
+                self.sample();
+                let correlations: Vec<f64> = self.leads.iter().map(|l| l.correlation().max(0.0)).collect();
+                self.blend(first_ms, time_ms, normalise(&correlations));
+            }
+            Weighting::Static => {}
+        }
+    }
+
+    fn decay_notional(&mut self, time_ms: u64) {
+        let dt = time_ms.saturating_sub(self.notional_ms) as f64;
+        self.notional_ms = self.notional_ms.max(time_ms);
+        if dt > 0.0 {
+            let decay = (-dt * std::f64::consts::LN_2 / self.half_life_ms).exp();
+            for n in &mut self.notional {
+                *n *= decay;
+            }
+        }
+    }
+
+    fn sample(&mut self) {
+        let alpha = 1.0 - (-(self.sample_ms as f64) * std::f64::consts::LN_2 / self.half_life_ms).exp();
+        let logs: Vec<Option<f64>> = self.prices.iter().map(|p| p.map(f64::ln)).collect();
+        for (i, lead) in self.leads.iter_mut().enumerate() {
+            let others: Vec<f64> = logs.iter().enumerate().filter(|(j, _)| *j != i).filter_map(|(_, l)| *l).collect();
+            let (Some(own), false) = (logs[i], others.is_empty()) else {
+                lead.last = None;
+                lead.last_return = None;
+                continue;
+            };
+            let others_mean = others.iter().sum::<f64>() / others.len() as f64;
+
+            if let Some((last_own, last_others)) = lead.last {
+                let others_return = others_mean - last_others;
+                if let Some(r) = lead.last_return {
+                    lead.rs += alpha * (r * others_return - lead.rs);
+                    lead.rr += alpha * (r * r - lead.rr);
+                    lead.ss += alpha * (others_return * others_return - lead.ss);
+                }
+                lead.last_return = Some(own - last_own);
+            }
+            lead.last = Some((own, others_mean));
+        }
+    }
+
+    // Leans from the configured weights to the measured ones as the data builds up. Measured
+    // weights that are all zero (no volume, nobody leading) leave the configured ones. The
+    // result is normalised here, the gauge, the log and the fusion all read the same shares.
+    fn blend(&mut self, first_ms: u64, time_ms: u64, measured: Option<Vec<f64>>) {
+        let Some(measured) = measured else {
+            return;
+        };
+        let trust = 1.0 - (-(time_ms.saturating_sub(first_ms) as f64) * std::f64::consts::LN_2 / self.half_life_ms).exp();
+        let blended: Vec<f64> = self.configured.iter().zip(&measured).map(|(c, m)| c + trust * (m - c)).collect();
+        self.weights = normalise(&blended).unwrap_or(blended);
+
+        self.set_gauges();
+        if time_ms.saturating_sub(self.last_log_ms) >= LOG_INTERVAL_MS {
+            self.last_log_ms = time_ms;
+            self.publish(trust);
+        }
+    }
+
+    fn set_gauges(&self) {
+        for (venue, weight) in VENUES.iter().zip(&self.weights) {
+            metrics::VENUE_WEIGHT.with_label_values(&[venue]).set(*weight);
+        }
+    }
+
+    // a log line, trust is the share of the weights that was measured
+    fn publish(&self, trust: f64) {
+        let shown: Vec<String> = VENUES.iter().zip(&self.weights).map(|(v, w)| format!("{v} {w:.3}")).collect();
+        info!(mode = ?self.mode, trust = format!("{trust:.2}"), weights = %shown.join(", "), "venue weights");
+    }
+}
+
+// shares of the total, None when there's nothing to share
+fn normalise(values: &[f64]) -> Option<Vec<f64>> {
+    let total: f64 = values.iter().sum();
+    (total > 0.0 && total.is_finite()).then(|| values.iter().map(|v| v / total).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FeedConfig;
+
+    fn assert_normalised(weights: &[f64]) {
+        assert!((weights.iter().sum::<f64>() - 1.0).abs() < 1e-12, "weights {weights:?}");
+    }
+
+    fn gauges() -> Vec<f64> {
+        VENUES.iter().map(|v| metrics::VENUE_WEIGHT.with_label_values(&[v]).get()).collect()
+    }
+
+    // one test, the gauge is shared
+    #[test]
+    fn weights_are_normalised_and_match_the_gauge() {
+        let mut config = FeedConfig { weighting: Weighting::Volume, ..FeedConfig::default() };
+        config.weights.binance = 2.0;
+        let mut weighting = VenueWeighting::new(&config);
+        assert_normalised(weighting.weights());
+        assert!(weighting.weights()[0] > weighting.weights()[1]);
+        assert_eq!(gauges(), weighting.weights());
+
+        // all the volume on kraken, for many half-lives
+        let start = 1_700_000_000_000;
+        for second in 0..20_000u64 {
+            weighting.trade(2, start + second * 1000, 60_000.0, 1_000.0);
+            assert_normalised(weighting.weights());
+        }
+        assert!(weighting.weights()[2] > 0.99, "weights {:?}", weighting.weights());
+        assert_eq!(gauges(), weighting.weights());
+    }
+}